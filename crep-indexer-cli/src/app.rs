@@ -1,15 +1,25 @@
 use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
 use std::sync::mpsc::channel;
+use std::time::Duration;
 
 use chrono::DateTime;
 use chrono::Local;
+use crep_indexer::index::git_index::GitIndex;
+use crep_indexer::search::cancellation::CancellationToken;
+use crep_indexer::search::fuzzy::default_max_edits;
 use crep_indexer::search::git_searcher::Query;
-use crep_indexer::search::result::search_result::SearchResult;
-use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+use crep_indexer::search::git_searcher::SearchOption;
+use crep_indexer::search::suggest::suggest_identifiers;
+use crep_indexer::search::suggest::suggest_paths;
 use ratatui::DefaultTerminal;
 use ratatui::Frame;
 use ratatui::crossterm::event::Event;
 use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyModifiers;
 use ratatui::crossterm::event::{self};
 use ratatui::layout::Constraint;
 use ratatui::layout::Layout;
@@ -21,6 +31,9 @@ use ratatui::style::Stylize;
 use ratatui::text::Line;
 use ratatui::text::Span;
 use ratatui::widgets::Block;
+use ratatui::widgets::List;
+use ratatui::widgets::ListItem;
+use ratatui::widgets::ListState;
 use ratatui::widgets::Paragraph;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -28,68 +41,176 @@ use std::sync::mpsc;
 use tui_input::Input;
 use tui_input::backend::crossterm::EventHandler;
 
+use crate::clipboard::copy_to_clipboard;
+use crate::commit_log::CommitLog;
+use crate::history::HistoryEntry;
+use crate::history::QueryHistory;
+use crate::history::SavedSearches;
+use crate::preview::Preview;
+use crate::searcher::Hit;
 use crate::searcher::Searcher;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum State {
     Control,
     Input(QueryType),
+    // Ctrl-R search over the query history.
+    ReverseSearch,
+    // Typing the name of the query to save.
+    SaveSearch,
+    // Picking one of the saved searches.
+    SavedSearches,
+    // Browsing the commits that modified the file of the selected hit.
+    CommitLog,
     Terminate,
 }
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum QueryType {
     Regex,
     RawString,
+    Fuzzy,
+}
+
+impl QueryType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryType::Regex => "regex",
+            QueryType::RawString => "plain",
+            QueryType::Fuzzy => "fuzzy",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "regex" => Some(QueryType::Regex),
+            "plain" => Some(QueryType::RawString),
+            "fuzzy" => Some(QueryType::Fuzzy),
+            _ => None,
+        }
+    }
+
+    pub fn to_query(self, text: &str) -> Query {
+        match self {
+            QueryType::Regex => Query::Regex(text.to_owned()),
+            QueryType::RawString => Query::Plain(text.to_owned()),
+            QueryType::Fuzzy => {
+                let term = text.trim();
+                Query::Fuzzy {
+                    term: term.to_owned(),
+                    max_edits: default_max_edits(term),
+                }
+            }
+        }
+    }
 }
 
 pub struct App<'a> {
     state: State,
     prev_query_type: Option<QueryType>,
 
-    scroll_y: u16,
+    // Only matches the terms at the word boundaries. Toggled by `w`.
+    whole_word: bool,
+    // Regex matches can span multiple lines. Toggled by `m`.
+    multiline: bool,
+    // Shows every match in the file. Toggled by `a`.
+    all_matches: bool,
+
+    index: &'a GitIndex,
+    repo_path: String,
     searcher: Arc<Mutex<Searcher<'a>>>,
     input: Input,
 
+    // Completions of the word under the cursor. Tab accepts the first one.
+    suggestions: Vec<String>,
+
+    history: QueryHistory,
+    // The text of the Ctrl-R search and the history entry it found.
+    reverse_search: Input,
+    reverse_match: Option<usize>,
+
+    saved_searches: SavedSearches,
+    save_name: Input,
+    saved_selected: usize,
+
     ui_send: mpsc::Sender<Message>,
     ui_recv: mpsc::Receiver<Message>,
 
     search_send: mpsc::Sender<SearchMessage>,
     search_recv: Option<mpsc::Receiver<SearchMessage>>,
 
-    search_result: Vec<SearchResult>,
+    // Cancels the search of the previous input once the input changes.
+    running_search: Option<CancellationToken>,
+
+    search_result: Vec<Hit>,
+    // The hit shown in the preview pane.
+    selected: usize,
+    preview: Option<Preview>,
+    commit_log: Option<CommitLog>,
+
+    // The file and the line to open once the terminal is released.
+    editor_request: Option<(PathBuf, usize)>,
+    // Stops reading the terminal events while the editor owns the terminal.
+    input_control_send: mpsc::Sender<InputControl>,
+    input_control_recv: Option<mpsc::Receiver<InputControl>>,
+
     log: Vec<(DateTime<Local>, String)>,
 }
 
 #[derive(Debug)]
 enum Message {
     Event(Event),
-    SearchResults(Vec<SearchResult>),
+    SearchResults(Vec<Hit>),
     Terminate,
     Log(String),
 }
 
 enum SearchMessage {
-    SearchRequest(Query),
+    SearchRequest(Query, SearchOption),
     Terminate,
 }
 
+enum InputControl {
+    // The input thread acknowledges once it stopped reading the terminal.
+    Pause(mpsc::Sender<()>),
+    Resume,
+}
+
 impl<'a> App<'a> {
     pub fn new(searcher: Searcher<'a>) -> Self {
         let (ui_send, ui_recv) = channel();
         let (search_send, search_recv) = channel();
+        let (input_control_send, input_control_recv) = channel();
 
         Self {
             state: State::Input(QueryType::RawString),
             prev_query_type: None,
-            scroll_y: 0,
+            whole_word: false,
+            multiline: false,
+            all_matches: false,
+            index: searcher.index(),
+            repo_path: searcher.repo_path().to_owned(),
             input: Input::default(),
+            suggestions: vec![],
+            history: QueryHistory::load(),
+            reverse_search: Input::default(),
+            reverse_match: None,
+            saved_searches: SavedSearches::load(),
+            save_name: Input::default(),
+            saved_selected: 0,
             searcher: Arc::new(Mutex::new(searcher)),
             ui_send,
             ui_recv,
             search_send,
             search_recv: Some(search_recv),
+            running_search: None,
             search_result: vec![],
+            selected: 0,
+            preview: None,
+            commit_log: None,
+            editor_request: None,
+            input_control_send,
+            input_control_recv: Some(input_control_recv),
             log: vec![],
         }
     }
@@ -98,9 +219,35 @@ impl<'a> App<'a> {
         {
             // Create a thread that handles the user input.
             let ui_send = self.ui_send.clone();
+            let input_control_recv = self.input_control_recv.take().unwrap();
             std::thread::spawn(move || {
-                while let Ok(event) = event::read() {
-                    ui_send.send(Message::Event(event)).unwrap()
+                loop {
+                    if let Ok(InputControl::Pause(ack)) =
+                        input_control_recv.try_recv()
+                    {
+                        ack.send(()).unwrap();
+
+                        // Nothing reads the terminal until it is resumed.
+                        match input_control_recv.recv() {
+                            Ok(InputControl::Resume) => {}
+                            _ => break,
+                        }
+                    }
+
+                    // Polls so that the pause takes effect without waiting
+                    // for the next key.
+                    if !event::poll(INPUT_POLL_INTERVAL).unwrap_or(false) {
+                        continue;
+                    }
+
+                    match event::read() {
+                        Ok(event) => {
+                            if ui_send.send(Message::Event(event)).is_err() {
+                                break;
+                            }
+                        }
+                        Err(_) => break,
+                    }
                 }
             });
         }
@@ -123,16 +270,28 @@ impl<'a> App<'a> {
                     let mut searcher = searcher.lock().unwrap();
                     match last {
                         SearchMessage::Terminate => break,
-                        SearchMessage::SearchRequest(query) => {
+                        SearchMessage::SearchRequest(query, option) => {
                             ui_send
                                 .send(Message::Log(format!(
                                     "Search Start -- {query:?}"
                                 )))
                                 .unwrap();
 
-                            if let Ok(search_results) =
-                                searcher.handle_query(&query)
+                            let search_results =
+                                searcher.handle_query(&query, option.clone());
+
+                            if option
+                                .cancellation
+                                .is_some_and(|c| c.is_cancelled())
                             {
+                                // Superseded by a newer query; the partial
+                                // results are useless.
+                                ui_send
+                                    .send(Message::Log(format!(
+                                        "Search Cancelled -- {query:?}"
+                                    )))
+                                    .unwrap();
+                            } else if let Ok(search_results) = search_results {
                                 ui_send
                                     .send(Message::SearchResults(
                                         search_results,
@@ -159,9 +318,18 @@ impl<'a> App<'a> {
 
                 if let Ok(message) = self.ui_recv.recv() {
                     match message {
-                        Message::Event(e) => self.handle_event(e).unwrap(),
+                        Message::Event(e) => {
+                            self.handle_event(e).unwrap();
+
+                            if let Some((path, line)) =
+                                self.editor_request.take()
+                            {
+                                self.open_in_editor(terminal, &path, line);
+                            }
+                        }
                         Message::SearchResults(results) => {
                             self.search_result = results;
+                            self.select(0);
                         }
                         Message::Terminate => break,
                         Message::Log(l) => {
@@ -197,12 +365,53 @@ impl<'a> App<'a> {
                     State::Input(_) => {
                         self.state = State::Control;
                     }
+                    State::ReverseSearch => {
+                        self.state = State::Input(self.query_type());
+                    }
+                    State::SaveSearch | State::SavedSearches => {
+                        self.state = State::Control;
+                    }
+                    State::CommitLog => {
+                        self.commit_log = None;
+                        self.state = State::Control;
+                    }
                     State::Terminate => { /* Ignore */ }
                 }
             } else if key_event.code == KeyCode::Enter {
-                if let State::Input(_) = state {
-                    // Clear the input on enter.
-                    self.input.reset();
+                match state {
+                    State::Input(query_type) => {
+                        // Keeps the query, so that it can be refined later.
+                        self.suggestions.clear();
+                        self.push_history(query_type);
+                        self.state = State::Control;
+                    }
+                    State::Control => self.request_editor(),
+                    State::ReverseSearch => {
+                        if let Some(entry) = self
+                            .reverse_match
+                            .and_then(|index| self.history.get(index))
+                            .cloned()
+                        {
+                            self.load_entry(entry);
+                        } else {
+                            self.state = State::Input(self.query_type());
+                        }
+                    }
+                    State::SaveSearch => {
+                        self.save_search();
+                        self.state = State::Control;
+                    }
+                    State::SavedSearches => {
+                        if let Some(saved) = self
+                            .saved_searches
+                            .searches
+                            .get(self.saved_selected)
+                        {
+                            self.load_entry(saved.entry.clone());
+                        }
+                    }
+                    State::CommitLog => self.preview_selected_commit(),
+                    State::Terminate => { /* Ignore */ }
                 }
             } else {
                 match state {
@@ -211,12 +420,59 @@ impl<'a> App<'a> {
                             self.state = State::Input(QueryType::RawString);
                         } else if key_event.code == KeyCode::Char('r') {
                             self.state = State::Input(QueryType::Regex);
-                        } else if key_event.code == KeyCode::Char('j') {
-                            self.scroll_y = self.scroll_y.saturating_add(5);
-                        } else if key_event.code == KeyCode::Char('k') {
-                            self.scroll_y = self.scroll_y.saturating_sub(5);
+                        } else if key_event.code == KeyCode::Char('f') {
+                            self.state = State::Input(QueryType::Fuzzy);
+                        } else if key_event.code == KeyCode::Char('w') {
+                            self.whole_word = !self.whole_word;
+                        } else if key_event.code == KeyCode::Char('m') {
+                            self.multiline = !self.multiline;
+                        } else if key_event.code == KeyCode::Char('a') {
+                            self.all_matches = !self.all_matches;
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('j') | KeyCode::Down
+                        ) {
+                            self.select(self.selected.saturating_add(1));
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('k') | KeyCode::Up
+                        ) {
+                            self.select(self.selected.saturating_sub(1));
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('J') | KeyCode::PageDown
+                        ) {
+                            if let Some(preview) = &mut self.preview {
+                                preview.scroll_by(PREVIEW_SCROLL_LINES);
+                            }
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('K') | KeyCode::PageUp
+                        ) {
+                            if let Some(preview) = &mut self.preview {
+                                preview.scroll_by(-PREVIEW_SCROLL_LINES);
+                            }
+                        } else if key_event.code == KeyCode::Char('[') {
+                            self.step_commit(false);
+                        } else if key_event.code == KeyCode::Char(']') {
+                            self.step_commit(true);
+                        } else if key_event.code == KeyCode::Char('y') {
+                            self.copy_commit_sha();
+                        } else if key_event.code == KeyCode::Char('Y') {
+                            self.copy_file_path();
+                        } else if key_event.code == KeyCode::Char('S') {
+                            self.save_name.reset();
+                            self.state = State::SaveSearch;
+                        } else if key_event.code == KeyCode::Char('o') {
+                            self.saved_selected = 0;
+                            self.state = State::SavedSearches;
+                        } else if key_event.code == KeyCode::Char('l') {
+                            self.open_commit_log();
                         } else if key_event.code == KeyCode::Char('q') {
                             self.state = State::Terminate;
+                            if let Some(running) = self.running_search.take() {
+                                running.cancel();
+                            }
                             self.ui_send.send(Message::Terminate).unwrap();
                             self.search_send
                                 .send(SearchMessage::Terminate)
@@ -224,28 +480,118 @@ impl<'a> App<'a> {
                         }
                     }
                     State::Input(query_type) => {
-                        self.input.handle_event(&event);
-                        self.scroll_y = 0;
-
-                        match query_type {
-                            QueryType::Regex => {
-                                self.search_send
-                                    .send(SearchMessage::SearchRequest(
-                                        Query::Regex(
-                                            self.input.value().to_owned(),
-                                        ),
-                                    ))
-                                    .unwrap();
+                        if key_event.code == KeyCode::Up {
+                            if let Some(entry) =
+                                self.history.previous().cloned()
+                            {
+                                self.load_entry(entry);
                             }
-                            QueryType::RawString => {
-                                self.search_send
-                                    .send(SearchMessage::SearchRequest(
-                                        Query::Plain(
-                                            self.input.value().to_owned(),
-                                        ),
-                                    ))
-                                    .unwrap();
+                            return Ok(());
+                        } else if key_event.code == KeyCode::Down {
+                            match self.history.next().cloned() {
+                                Some(entry) => self.load_entry(entry),
+                                None => {
+                                    self.input.reset();
+                                    self.search(query_type);
+                                }
+                            }
+                            return Ok(());
+                        } else if is_ctrl_r(&key_event) {
+                            self.reverse_search.reset();
+                            self.reverse_match = None;
+                            self.state = State::ReverseSearch;
+                            return Ok(());
+                        } else if key_event.code == KeyCode::Tab {
+                            if !self.accept_suggestion() {
+                                return Ok(());
+                            }
+                        } else {
+                            self.input.handle_event(&event);
+                        }
+
+                        self.search(query_type);
+                    }
+                    State::ReverseSearch => {
+                        // Ctrl-R again finds the older match.
+                        let before = match is_ctrl_r(&key_event) {
+                            true => self.reverse_match,
+                            false => {
+                                self.reverse_search.handle_event(&event);
+                                None
                             }
+                        };
+
+                        if let Some((index, _)) = self
+                            .history
+                            .reverse_search(self.reverse_search.value(), before)
+                        {
+                            self.reverse_match = Some(index);
+                        } else if before.is_none() {
+                            self.reverse_match = None;
+                        }
+                    }
+                    State::SaveSearch => {
+                        self.save_name.handle_event(&event);
+                    }
+                    State::SavedSearches => {
+                        if matches!(
+                            key_event.code,
+                            KeyCode::Char('j') | KeyCode::Down
+                        ) {
+                            self.saved_selected = (self.saved_selected + 1)
+                                .min(
+                                    self.saved_searches
+                                        .searches
+                                        .len()
+                                        .saturating_sub(1),
+                                );
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('k') | KeyCode::Up
+                        ) {
+                            self.saved_selected =
+                                self.saved_selected.saturating_sub(1);
+                        } else if key_event.code == KeyCode::Char('d') {
+                            let result =
+                                self.saved_searches.remove(self.saved_selected);
+                            self.log_error(
+                                "Unable to remove the search",
+                                result,
+                            );
+                            self.saved_selected =
+                                self.saved_selected.saturating_sub(1);
+                        }
+                    }
+                    State::CommitLog => {
+                        let Some(log) = &mut self.commit_log else {
+                            return Ok(());
+                        };
+
+                        if matches!(
+                            key_event.code,
+                            KeyCode::Char('j') | KeyCode::Down
+                        ) {
+                            let searcher = self.searcher.lock().unwrap();
+                            log.select(&searcher, log.selected + 1);
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('k') | KeyCode::Up
+                        ) {
+                            let searcher = self.searcher.lock().unwrap();
+                            log.select(
+                                &searcher,
+                                log.selected.saturating_sub(1),
+                            );
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('J') | KeyCode::PageDown
+                        ) {
+                            log.diff.scroll_by(PREVIEW_SCROLL_LINES);
+                        } else if matches!(
+                            key_event.code,
+                            KeyCode::Char('K') | KeyCode::PageUp
+                        ) {
+                            log.diff.scroll_by(-PREVIEW_SCROLL_LINES);
                         }
                     }
                     State::Terminate => { /* Ignore */ }
@@ -256,6 +602,306 @@ impl<'a> App<'a> {
         Ok(())
     }
 
+    // The type of the query in the input box.
+    fn query_type(&self) -> QueryType {
+        match self.state {
+            State::Input(query_type) => query_type,
+            _ => self.prev_query_type.unwrap_or(QueryType::RawString),
+        }
+    }
+
+    fn push_history(&mut self, query_type: QueryType) {
+        let result = self.history.push(HistoryEntry {
+            query_type,
+            query: self.input.value().to_owned(),
+        });
+        self.log_error("Unable to save the history", result);
+    }
+
+    fn save_search(&mut self) {
+        let name = self.save_name.value().trim().to_owned();
+        if name.is_empty() || self.input.value().is_empty() {
+            return;
+        }
+
+        let result = self.saved_searches.save(
+            &name,
+            HistoryEntry {
+                query_type: self.query_type(),
+                query: self.input.value().to_owned(),
+            },
+        );
+        self.log_error("Unable to save the search", result);
+    }
+
+    // Puts the query back in the input box and searches it.
+    fn load_entry(&mut self, entry: HistoryEntry) {
+        self.input = Input::new(entry.query);
+        self.prev_query_type = Some(entry.query_type);
+        self.state = State::Input(entry.query_type);
+        self.search(entry.query_type);
+    }
+
+    fn log_error(&mut self, context: &str, result: io::Result<()>) {
+        if let Err(e) = result {
+            self.log.push((Local::now(), format!("{context}: {e}")));
+        }
+    }
+
+    // Searches the input, cancelling the previous search.
+    fn search(&mut self, query_type: QueryType) {
+        self.suggestions =
+            suggest(self.index, current_word(self.input.value()));
+
+        let query = query_type.to_query(self.input.value());
+
+        let cancellation = CancellationToken::new();
+        if let Some(prev) = self.running_search.replace(cancellation.clone()) {
+            prev.cancel();
+        }
+
+        self.search_send
+            .send(SearchMessage::SearchRequest(
+                query,
+                SearchOption {
+                    cancellation: Some(cancellation),
+                    whole_word: self.whole_word,
+                    multiline: self.multiline,
+                    all_matches: self.all_matches,
+                    ..Default::default()
+                },
+            ))
+            .unwrap();
+    }
+
+    // Replaces the word under the cursor with the first suggestion. Returns
+    // false if there was nothing to complete.
+    fn accept_suggestion(&mut self) -> bool {
+        let Some(suggestion) = self.suggestions.first() else {
+            return false;
+        };
+
+        let value = self.input.value();
+        let completed = format!(
+            "{}{suggestion}",
+            &value[..value.len() - current_word(value).len()]
+        );
+        self.input = Input::new(completed);
+
+        true
+    }
+
+    // Selects the hit and previews the file at its last match.
+    fn select(&mut self, selected: usize) {
+        self.selected =
+            selected.min(self.search_result.len().saturating_sub(1));
+
+        let Some(hit) = self.search_result.get(self.selected) else {
+            self.preview = None;
+            return;
+        };
+
+        let commit_index = hit
+            .result
+            .last_match
+            .as_ref()
+            .unwrap_or(&hit.result.first_match)
+            .commit_id;
+        self.preview = self.load_preview(commit_index);
+    }
+
+    fn load_preview(&self, commit_index: usize) -> Option<Preview> {
+        let hit = self.search_result.get(self.selected)?;
+        let file_id = *self.index.file_name_to_id.get(&hit.result.file_path)?;
+
+        let searcher = self.searcher.lock().unwrap();
+        Some(Preview::load(
+            &searcher,
+            &hit.query,
+            file_id,
+            &hit.result.file_path,
+            commit_index,
+        ))
+    }
+
+    // Moves the preview to the previous (or the next) commit that modified
+    // the file.
+    fn step_commit(&mut self, forward: bool) {
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let Some(document) =
+            self.index.file_id_to_document.get(&preview.file_id)
+        else {
+            return;
+        };
+
+        let current = preview.commit_index as u32;
+        let commit_index = match forward {
+            true => document.doc_modified_commits.iter().find(|c| *c > current),
+            false => document
+                .doc_modified_commits
+                .iter()
+                .rev()
+                .find(|c| *c < current),
+        };
+
+        match commit_index {
+            Some(commit_index) => {
+                self.preview = self.load_preview(commit_index as usize);
+            }
+            None => self.log.push((
+                Local::now(),
+                "No more commits modified the file".to_owned(),
+            )),
+        }
+    }
+
+    // Opens the commit log of the selected hit at the previewed commit.
+    fn open_commit_log(&mut self) {
+        let Some(hit) = self.search_result.get(self.selected) else {
+            return;
+        };
+        let Some(&file_id) =
+            self.index.file_name_to_id.get(&hit.result.file_path)
+        else {
+            return;
+        };
+
+        let commit_index = self
+            .preview
+            .as_ref()
+            .map_or(hit.result.first_match.commit_id, |p| p.commit_index);
+
+        let searcher = self.searcher.lock().unwrap();
+        self.commit_log = Some(CommitLog::load(
+            &searcher,
+            &hit.query,
+            file_id,
+            &hit.result.file_path,
+            commit_index,
+        ));
+        drop(searcher);
+
+        self.state = State::CommitLog;
+    }
+
+    // Shows the whole file at the commit selected in the log.
+    fn preview_selected_commit(&mut self) {
+        let Some(commit_index) =
+            self.commit_log.take().and_then(|log| log.selected_commit())
+        else {
+            return;
+        };
+
+        self.preview = self.load_preview(commit_index);
+        self.state = State::Control;
+    }
+
+    // Only the files at HEAD exist in the working tree.
+    fn request_editor(&mut self) {
+        let Some(hit) = self.search_result.get(self.selected) else {
+            return;
+        };
+
+        let head = self.index.commit_index_to_commit_id.len() - 1;
+        let last_match = hit
+            .result
+            .last_match
+            .as_ref()
+            .unwrap_or(&hit.result.first_match);
+        let line = match &self.preview {
+            Some(preview) if preview.commit_index == head => {
+                preview.first_match_line()
+            }
+            _ if last_match.commit_id == head => {
+                last_match.words_per_line.keys().next().map(|line| line + 1)
+            }
+            _ => None,
+        };
+
+        match line {
+            Some(line) => {
+                self.editor_request = Some((
+                    Path::new(&self.repo_path).join(&hit.result.file_path),
+                    line,
+                ));
+            }
+            None => self.log.push((
+                Local::now(),
+                format!("{} does not match at HEAD", hit.result.file_path),
+            )),
+        }
+    }
+
+    fn open_in_editor(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        path: &Path,
+        line: usize,
+    ) {
+        let editor =
+            std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_owned());
+        let mut args = editor.split_whitespace();
+        let Some(program) = args.next() else {
+            return;
+        };
+
+        // The editor must get the keys instead of the input thread, so wait
+        // until it stops reading the terminal.
+        let (ack_send, ack_recv) = channel();
+        if self
+            .input_control_send
+            .send(InputControl::Pause(ack_send))
+            .is_ok()
+        {
+            // Fails only if the input thread is gone.
+            ack_recv.recv().ok();
+        }
+        ratatui::restore();
+
+        let status = Command::new(program)
+            .args(args)
+            .arg(format!("+{line}"))
+            .arg(path)
+            .status();
+
+        *terminal = ratatui::init();
+        terminal.clear().unwrap();
+        self.input_control_send.send(InputControl::Resume).ok();
+
+        if let Err(e) = status {
+            self.log
+                .push((Local::now(), format!("Unable to run {editor}: {e}")));
+        }
+    }
+
+    fn copy_commit_sha(&mut self) {
+        let Some(preview) = &self.preview else {
+            return;
+        };
+
+        let sha = hex::encode(
+            self.index.commit_index_to_commit_id[preview.commit_index],
+        );
+        self.copy(&sha);
+    }
+
+    fn copy_file_path(&mut self) {
+        if let Some(hit) = self.search_result.get(self.selected) {
+            let path = hit.result.file_path.clone();
+            self.copy(&path);
+        }
+    }
+
+    fn copy(&mut self, text: &str) {
+        let message = match copy_to_clipboard(text) {
+            Ok(()) => format!("Copied {text}"),
+            Err(e) => format!("Unable to copy {text}: {e}"),
+        };
+        self.log.push((Local::now(), message));
+    }
+
     fn render(&self, frame: &mut Frame) {
         let [header, input, search_results, logs, status] = Layout::vertical([
             Constraint::Length(1),
@@ -272,18 +918,84 @@ impl<'a> App<'a> {
             header,
         );
 
+        let [hits, preview] = Layout::horizontal([
+            Constraint::Percentage(40),
+            Constraint::Percentage(60),
+        ])
+        .areas(search_results);
+
         self.render_input(frame, input);
-        self.render_search_result(frame, search_results, &self.search_result);
+        match (self.state, &self.commit_log) {
+            (State::SavedSearches, _) => {
+                self.render_saved_searches(frame, hits);
+                self.render_preview(frame, preview);
+            }
+            (State::CommitLog, Some(log)) => {
+                render_commit_log(frame, hits, log);
+                render_diff(frame, preview, log);
+            }
+            _ => {
+                self.render_hits(frame, hits);
+                self.render_preview(frame, preview);
+            }
+        }
         self.render_log(frame, logs);
         self.render_status(frame, status);
     }
 
     fn render_input(&self, frame: &mut Frame, area: Rect) {
+        match self.state {
+            State::ReverseSearch => {
+                let found = self
+                    .reverse_match
+                    .and_then(|index| self.history.get(index))
+                    .map_or("", |entry| entry.query.as_str());
+                return render_prompt(
+                    frame,
+                    area,
+                    "Search history",
+                    &self.reverse_search,
+                    found,
+                );
+            }
+            State::SaveSearch => {
+                return render_prompt(
+                    frame,
+                    area,
+                    "Save search as",
+                    &self.save_name,
+                    "",
+                );
+            }
+            _ => {}
+        }
+
         let width = area.width.max(3) - 3;
         let scroll = self.input.visual_scroll(width as usize);
-        let input = Paragraph::new(self.input.value())
+
+        // Show the rest of the first suggestion as the inline hint.
+        let mut spans = vec![Span::raw(self.input.value())];
+        if let Some(suggestion) = self.suggestions.first() {
+            let word = current_word(self.input.value());
+            if let Some(rest) = suggestion.strip_prefix(word) {
+                spans.push(Span::styled(
+                    rest,
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+        }
+
+        let mut block = Block::bordered().title("Input");
+        if !self.suggestions.is_empty() {
+            block = block.title_bottom(
+                Line::from(format!("Tab: {}", self.suggestions.join(" | ")))
+                    .style(Style::default().fg(Color::DarkGray)),
+            );
+        }
+
+        let input = Paragraph::new(Line::from(spans))
             .scroll((0, scroll as u16))
-            .block(Block::bordered().title("Input"));
+            .block(block);
         frame.render_widget(input, area);
 
         // Ratatui hides the cursor unless it's explicitly set. Position the  cursor past the
@@ -307,98 +1019,360 @@ impl<'a> App<'a> {
         );
     }
 
-    fn render_search_result(
-        &self,
-        frame: &mut Frame,
-        area: Rect,
-        results: &[SearchResult],
-    ) {
-        let mut lines = vec![];
-        for result in results {
-            lines.push(Line::from(vec![Span::raw(format!(
-                "File: {}",
-                result.file_path
-            ))]));
-
-            let searcher = self.searcher.lock().unwrap();
-
-            let first_commit = searcher
-                .get_commit_info(result.first_match.commit_id)
-                .unwrap();
-
-            match &result.last_match {
-                Some(last) => {
-                    let last_commit =
-                        searcher.get_commit_info(last.commit_id).unwrap();
-
-                    lines.push(Line::from(vec![Span::raw(format!(
-                        "First seen at commit {} ... last seen at {}",
-                        first_commit.display_simple(),
-                        last_commit.display_simple(),
-                    ))]));
-
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        &result.first_match,
-                    ));
-                    lines.extend_from_slice(&[
-                        Line::from(""),
-                        Line::from("---------------------------------------"),
-                        Line::from(""),
-                    ]);
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        last,
-                    ));
-                }
-                None => {
-                    lines.push(Line::from(vec![Span::raw(format!(
-                        "Seen at commit {} and never seen again",
-                        first_commit.display_simple()
-                    ))]));
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        &result.first_match,
-                    ));
-                }
-            };
+    fn render_hits(&self, frame: &mut Frame, area: Rect) {
+        let items = self
+            .search_result
+            .iter()
+            .map(|hit| {
+                let first = self.commit_label(hit.result.first_match.commit_id);
+                let seen = match &hit.result.last_match {
+                    Some(last) => format!(
+                        "{first} .. {}",
+                        self.commit_label(last.commit_id)
+                    ),
+                    None => format!("{first}, never seen again"),
+                };
 
-            lines.push(Line::raw(""));
-        }
+                ListItem::new(vec![
+                    Line::from(hit.result.file_path.as_str().bold()),
+                    Line::styled(
+                        format!("  {seen}"),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!("Hits ({})", self.search_result.len())),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut state = ListState::default().with_selected(
+            (!self.search_result.is_empty()).then_some(self.selected),
+        );
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn render_saved_searches(&self, frame: &mut Frame, area: Rect) {
+        let items = self
+            .saved_searches
+            .searches
+            .iter()
+            .map(|saved| {
+                ListItem::new(vec![
+                    Line::from(saved.name.as_str().bold()),
+                    Line::styled(
+                        format!(
+                            "  {}: {}",
+                            saved.entry.query_type.as_str(),
+                            saved.entry.query
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(Block::bordered().title("Saved searches"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut state = ListState::default().with_selected(
+            (!self.saved_searches.searches.is_empty())
+                .then_some(self.saved_selected),
+        );
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let Some(preview) = &self.preview else {
+            frame.render_widget(Block::bordered().title("Preview"), area);
+            return;
+        };
+
+        let lines = match &preview.error {
+            Some(error) => vec![Line::styled(
+                error.as_str(),
+                Style::default().fg(Color::Red),
+            )],
+            None => preview
+                .lines
+                .iter()
+                .enumerate()
+                .map(|(line_num, line)| {
+                    get_highlighted_line(
+                        line,
+                        line_num,
+                        preview
+                            .words_per_line
+                            .get(&line_num)
+                            .map_or(&[], Vec::as_slice),
+                    )
+                })
+                .collect(),
+        };
+
+        let block = Block::bordered()
+            .title(format!(
+                "{} @ {}",
+                preview.file_path,
+                self.commit_label(preview.commit_index)
+            ))
+            .title_bottom(
+                Line::from("[/]: prev/next commit. Enter: open in $EDITOR")
+                    .style(Style::default().fg(Color::DarkGray)),
+            );
 
         frame.render_widget(
-            Paragraph::new(lines).scroll((self.scroll_y, 0)),
+            Paragraph::new(lines)
+                .scroll((preview.scroll, 0))
+                .block(block),
             area,
         );
     }
 
+    // Short SHA of the commit, or HEAD.
+    fn commit_label(&self, commit_index: usize) -> String {
+        if commit_index + 1 == self.index.commit_index_to_commit_id.len() {
+            return "HEAD".to_owned();
+        }
+
+        hex::encode(&self.index.commit_index_to_commit_id[commit_index][..4])
+    }
+
     fn render_status(&self, frame: &mut Frame, area: Rect) {
-        match self.state {
-            State::Control => {
-                frame.render_widget(
-                    Paragraph::new("Use q to terminate. i: String search. r: Regex search. k: prev. j: next. ESC: back to previous query mode")
-                        .style(Style::default().fg(Color::Yellow)),
-                    area,
-                );
-            }
-            State::Input(query_type) => {
-                let text = match query_type {
-                    QueryType::Regex => "Regex",
-                    QueryType::RawString => "Text",
+        let (help, color) = match self.state {
+            State::Control => (
+                "q: quit. i/r/f: text/regex/fuzzy search. w/m/a: toggle whole word/multi-line/all matches. j/k: next/prev hit. J/K: scroll preview. y/Y: copy SHA/path. l: commit log. S: save search. o: saved searches. ESC: back to previous query mode",
+                Color::Yellow,
+            ),
+            State::Input(_) => (
+                "ESC: escape. Enter: submit. Tab: complete. Up/Down: history. Ctrl-R: search history",
+                Color::Green,
+            ),
+            State::ReverseSearch => (
+                "Ctrl-R: older match. Enter: accept. ESC: cancel",
+                Color::Green,
+            ),
+            State::SaveSearch => (
+                "Enter: save the current query with the name. ESC: cancel",
+                Color::Green,
+            ),
+            State::SavedSearches => (
+                "j/k: select. Enter: search. d: delete. ESC: back",
+                Color::Green,
+            ),
+            State::CommitLog => (
+                "j/k: select commit. J/K: scroll diff. Enter: preview the file at the commit. ESC: back",
+                Color::Green,
+            ),
+            State::Terminate => ("Terminating...", Color::Red),
+        };
+
+        let mut spans = self.mode_indicator();
+        spans.push(Span::styled(help, Style::default().fg(color)));
+
+        frame.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+
+    // The query type and the search options, highlighted when they are on.
+    fn mode_indicator(&self) -> Vec<Span<'static>> {
+        let query_type = self.query_type();
+        let mode = match query_type {
+            QueryType::Regex => " REGEX ",
+            QueryType::RawString => " PLAIN ",
+            QueryType::Fuzzy => " FUZZY ",
+        };
+
+        let options = [
+            // The index is always case sensitive.
+            ("case", true),
+            ("word", self.whole_word),
+            (
+                "multi-line",
+                self.multiline && query_type == QueryType::Regex,
+            ),
+            ("all", self.all_matches),
+        ];
+
+        let mut spans = vec![Span::styled(
+            mode,
+            Style::default().fg(Color::Black).bg(Color::Cyan),
+        )];
+        for (name, on) in options {
+            let style = match on {
+                true => Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+                false => Style::default().fg(Color::DarkGray),
+            };
+            spans.push(Span::styled(format!(" {name}"), style));
+        }
+        spans.push(Span::raw("  "));
+
+        spans
+    }
+}
+
+fn render_commit_log(frame: &mut Frame, area: Rect, log: &CommitLog) {
+    let items = log
+        .entries
+        .iter()
+        .map(|entry| match &entry.info {
+            Some(info) => ListItem::new(vec![
+                Line::from(vec![
+                    Span::styled(
+                        match info.is_commit_head {
+                            true => "HEAD    ".to_owned(),
+                            false => info.commit_id[..8].to_owned(),
+                        },
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::raw(format!(" {}", info.summary)),
+                ]),
+                Line::styled(
+                    format!(
+                        "  {} {}",
+                        info.commit_time.format("%Y-%m-%d %H:%M"),
+                        info.author
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]),
+            None => ListItem::new(Line::styled(
+                format!("Unable to read the commit #{}", entry.commit_index),
+                Style::default().fg(Color::Red),
+            )),
+        })
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(Block::bordered().title(format!(
+            "Commits of {} ({})",
+            log.file_path,
+            log.entries.len()
+        )))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default()
+        .with_selected((!log.entries.is_empty()).then_some(log.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_diff(frame: &mut Frame, area: Rect, log: &CommitLog) {
+    let diff = &log.diff;
+
+    let lines = match &diff.error {
+        Some(error) => vec![Line::styled(
+            error.as_str(),
+            Style::default().fg(Color::Red),
+        )],
+        None => diff
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(line_num, line)| {
+                let style = match line.origin {
+                    '+' | '>' => Style::default().fg(Color::Green),
+                    '-' | '<' => Style::default().fg(Color::Red),
+                    'F' => Style::default().add_modifier(Modifier::BOLD),
+                    'H' => Style::default().fg(Color::Cyan),
+                    _ => Style::default(),
+                };
+                let origin = match line.origin {
+                    '+' | '-' | ' ' => line.origin.to_string(),
+                    _ => String::new(),
                 };
 
-                frame.render_widget(
-                    Paragraph::new(format!("{text} - Use ESC to escape"))
-                        .style(Style::default().fg(Color::Green)),
-                    area,
-                );
-            }
-            State::Terminate => {
-                frame.render_widget(
-                    Paragraph::new("Terminating...")
-                        .style(Style::default().fg(Color::Red)),
-                    area,
+                let mut spans = vec![Span::styled(origin, style)];
+                spans.extend(
+                    get_highlighted_spans(
+                        &line.content,
+                        diff.words_per_line
+                            .get(&line_num)
+                            .map_or(&[], Vec::as_slice),
+                    )
+                    .into_iter()
+                    // The matches keep their highlight over the diff colors.
+                    .map(|mut span| {
+                        span.style = style.patch(span.style);
+                        span
+                    }),
                 );
-            }
-        }
+
+                Line::from(spans)
+            })
+            .collect(),
+    };
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .scroll((diff.scroll, 0))
+            .block(Block::bordered().title("Diff")),
+        area,
+    );
+}
+
+// Single line input with the dimmed hint after the text.
+fn render_prompt(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    input: &Input,
+    hint: &str,
+) {
+    let width = area.width.max(3) - 3;
+    let scroll = input.visual_scroll(width as usize);
+
+    let mut spans = vec![Span::raw(input.value())];
+    if !hint.is_empty() {
+        spans.push(Span::styled(
+            format!("  → {hint}"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    frame.render_widget(
+        Paragraph::new(Line::from(spans))
+            .scroll((0, scroll as u16))
+            .block(Block::bordered().title(title)),
+        area,
+    );
+
+    let x = input.visual_cursor().max(scroll) - scroll + 1;
+    frame.set_cursor_position((area.x + x as u16, area.y + 1))
+}
+
+fn is_ctrl_r(key_event: &KeyEvent) -> bool {
+    key_event.code == KeyCode::Char('r')
+        && key_event.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+// The last (partially typed) word of the input.
+fn current_word(input: &str) -> &str {
+    input
+        .rsplit(|c: char| !(c.is_alphanumeric() || "_./-".contains(c)))
+        .next()
+        .unwrap_or_default()
+}
+
+fn suggest(index: &GitIndex, word: &str) -> Vec<String> {
+    if word.len() < MIN_CHARS_TO_SUGGEST {
+        return vec![];
+    }
+
+    // Words that look like a path are completed with the file paths.
+    if word.contains('/') || word.contains('.') {
+        return suggest_paths(&index.all_paths, word, MAX_SUGGESTIONS);
     }
+
+    suggest_identifiers(&index.identifiers, word, MAX_SUGGESTIONS)
+        .into_iter()
+        .map(|s| s.identifier)
+        .collect()
 }
 
 fn get_highlighted_line<'a>(
@@ -410,6 +1384,16 @@ fn get_highlighted_line<'a>(
         format!("{:>6}| ", line_number + 1),
         Style::default().fg(Color::LightYellow),
     )];
+    result.extend(get_highlighted_spans(line, positions));
+
+    Line::from(result)
+}
+
+fn get_highlighted_spans<'a>(
+    line: &'a str,
+    positions: &[(String, usize)],
+) -> Vec<Span<'a>> {
+    let mut result = vec![];
 
     let mut current = 0;
     for pos in positions {
@@ -431,23 +1415,7 @@ fn get_highlighted_line<'a>(
         result.push(Span::raw(truncate_long_line(&line[current..])));
     }
 
-    Line::from(result)
-}
-
-fn convert_search_result_to_lines(
-    result: &SingleCommitSearchResult,
-) -> Vec<Line<'_>> {
-    let mut lines = vec![];
-    for (line_num, line) in &result.lines {
-        let words = result.words_per_line.get(line_num);
-        lines.push(get_highlighted_line(
-            line,
-            *line_num,
-            words.unwrap_or(&Vec::new()),
-        ))
-    }
-
-    lines
+    result
 }
 
 fn truncate_long_line(line: &str) -> String {
@@ -482,3 +1450,296 @@ fn get_last_n_chars(line: &str, n: usize) -> &str {
 }
 
 const MAX_CHARS_TO_SHOW: usize = 80;
+
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const PREVIEW_SCROLL_LINES: i32 = 10;
+
+const MIN_CHARS_TO_SUGGEST: usize = 2;
+const MAX_SUGGESTIONS: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use crep_indexer::index::git_index::GitIndex;
+
+    use super::*;
+    use crate::test_util::commit_file;
+    use crate::test_util::index_repo;
+    use crate::test_util::init_repo;
+    use crate::test_util::run;
+
+    // a.txt is modified by the commits 0 and 2. b.txt is added by the commit 1
+    // and deleted by the commit 3.
+    fn build_index() -> (tempfile::TempDir, GitIndex) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+        commit_file(repo_path, "a.txt", "hello\n");
+        commit_file(repo_path, "b.txt", "say hello\n");
+        commit_file(repo_path, "a.txt", "other\nhello again\n");
+        run(repo_path, &["git", "rm", "b.txt"]);
+        run(repo_path, &["git", "commit", "-m", "delete"]);
+
+        let index = index_repo(repo_path);
+        (dir, index)
+    }
+
+    // Neither the history nor the saved searches are persisted.
+    fn build_app<'a>(index: &'a GitIndex, dir: &tempfile::TempDir) -> App<'a> {
+        let mut app =
+            App::new(Searcher::new(index, dir.path().to_str().unwrap()));
+        app.history = QueryHistory::default();
+        app.saved_searches = SavedSearches::default();
+        app
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+            .unwrap();
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    // The last search sent to the search thread.
+    fn last_search(app: &App) -> Option<(Query, SearchOption)> {
+        app.search_recv
+            .as_ref()
+            .unwrap()
+            .try_iter()
+            .filter_map(|message| match message {
+                SearchMessage::SearchRequest(query, option) => {
+                    Some((query, option))
+                }
+                SearchMessage::Terminate => None,
+            })
+            .last()
+    }
+
+    // Searches "hello" and selects the first hit, a.txt.
+    fn show_hits(app: &mut App) {
+        let mut hits = app
+            .searcher
+            .lock()
+            .unwrap()
+            .handle_query(&Query::Plain("hello".to_owned()), Default::default())
+            .unwrap();
+        hits.sort_by(|a, b| a.result.file_path.cmp(&b.result.file_path));
+
+        app.search_result = hits;
+        app.select(0);
+    }
+
+    fn preview_commit(app: &App) -> Option<usize> {
+        app.preview.as_ref().map(|preview| preview.commit_index)
+    }
+
+    #[test]
+    fn switch_between_input_and_control() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+        assert!(app.state == State::Input(QueryType::RawString));
+
+        type_text(&mut app, "hel");
+        assert_eq!(app.input.value(), "hel");
+        assert_eq!(
+            last_search(&app).map(|(query, _)| query),
+            Some(Query::Plain("hel".to_owned()))
+        );
+
+        // Enter keeps the query and records it.
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state == State::Control);
+        assert_eq!(app.input.value(), "hel");
+        assert_eq!(
+            app.history.get(0),
+            Some(&HistoryEntry {
+                query_type: QueryType::RawString,
+                query: "hel".to_owned(),
+            })
+        );
+
+        press(&mut app, KeyCode::Char('r'));
+        assert!(app.state == State::Input(QueryType::Regex));
+        press(&mut app, KeyCode::Esc);
+        assert!(app.state == State::Control);
+        // Esc goes back to the last input mode.
+        press(&mut app, KeyCode::Esc);
+        assert!(app.state == State::Input(QueryType::Regex));
+
+        // Up recalls the query with its type.
+        press(&mut app, KeyCode::Up);
+        assert!(app.state == State::Input(QueryType::RawString));
+        assert_eq!(app.input.value(), "hel");
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.state == State::Terminate);
+    }
+
+    #[test]
+    fn toggles_apply_to_the_search() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+
+        press(&mut app, KeyCode::Esc);
+        type_text(&mut app, "wmaw");
+        assert!(!app.whole_word);
+        assert!(app.multiline);
+        assert!(app.all_matches);
+
+        press(&mut app, KeyCode::Char('f'));
+        type_text(&mut app, "x");
+        let (query, option) = last_search(&app).unwrap();
+        assert_eq!(
+            query,
+            Query::Fuzzy {
+                term: "x".to_owned(),
+                max_edits: default_max_edits("x"),
+            }
+        );
+        assert!(!option.whole_word);
+        assert!(option.multiline);
+        assert!(option.all_matches);
+    }
+
+    #[test]
+    fn reverse_search_and_saved_searches() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+
+        for query in ["foo", "bar"] {
+            type_text(&mut app, query);
+            press(&mut app, KeyCode::Enter);
+            press(&mut app, KeyCode::Esc);
+            app.input.reset();
+        }
+
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Char('r'),
+            KeyModifiers::CONTROL,
+        )))
+        .unwrap();
+        assert!(app.state == State::ReverseSearch);
+        type_text(&mut app, "fo");
+        assert_eq!(app.reverse_match, Some(0));
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state == State::Input(QueryType::RawString));
+        assert_eq!(app.input.value(), "foo");
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('S'));
+        assert!(app.state == State::SaveSearch);
+        type_text(&mut app, "mine");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state == State::Control);
+        assert_eq!(
+            app.saved_searches
+                .searches
+                .iter()
+                .map(|search| (
+                    search.name.as_str(),
+                    search.entry.query.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![("mine", "foo")]
+        );
+
+        app.input.reset();
+        press(&mut app, KeyCode::Char('o'));
+        assert!(app.state == State::SavedSearches);
+        // Clamped to the only saved search.
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.saved_selected, 0);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state == State::Input(QueryType::RawString));
+        assert_eq!(app.input.value(), "foo");
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('o'));
+        press(&mut app, KeyCode::Char('d'));
+        assert!(app.saved_searches.searches.is_empty());
+    }
+
+    #[test]
+    fn select_hits_and_step_commits() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+        press(&mut app, KeyCode::Esc);
+
+        show_hits(&mut app);
+        assert_eq!(app.search_result.len(), 2);
+        assert_eq!(app.selected, 0);
+        assert_eq!(preview_commit(&app), Some(3));
+
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.selected, 1);
+        assert_eq!(preview_commit(&app), Some(1));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.selected, 1);
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(app.selected, 0);
+
+        // a.txt was modified by the commits 0 and 2.
+        press(&mut app, KeyCode::Char('['));
+        assert_eq!(preview_commit(&app), Some(2));
+        press(&mut app, KeyCode::Char('['));
+        assert_eq!(preview_commit(&app), Some(0));
+        let num_logs = app.log.len();
+        press(&mut app, KeyCode::Char('['));
+        assert_eq!(preview_commit(&app), Some(0));
+        assert_eq!(app.log.len(), num_logs + 1);
+        press(&mut app, KeyCode::Char(']'));
+        assert_eq!(preview_commit(&app), Some(2));
+    }
+
+    #[test]
+    fn commit_log_previews_the_selected_commit() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+        press(&mut app, KeyCode::Esc);
+        show_hits(&mut app);
+
+        press(&mut app, KeyCode::Char('['));
+        press(&mut app, KeyCode::Char('l'));
+        assert!(app.state == State::CommitLog);
+        let log = app.commit_log.as_ref().unwrap();
+        assert_eq!(log.selected_commit(), Some(2));
+
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.commit_log.as_ref().unwrap().selected_commit(), Some(0));
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state == State::Control);
+        assert!(app.commit_log.is_none());
+        assert_eq!(preview_commit(&app), Some(0));
+
+        press(&mut app, KeyCode::Char('l'));
+        press(&mut app, KeyCode::Esc);
+        assert!(app.state == State::Control);
+        assert!(app.commit_log.is_none());
+    }
+
+    #[test]
+    fn editor_opens_only_files_at_head() {
+        let (dir, index) = build_index();
+        let mut app = build_app(&index, &dir);
+        press(&mut app, KeyCode::Esc);
+        show_hits(&mut app);
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            app.editor_request.take(),
+            Some((dir.path().join("a.txt"), 2))
+        );
+
+        // b.txt is deleted at HEAD.
+        press(&mut app, KeyCode::Char('j'));
+        let num_logs = app.log.len();
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.editor_request, None);
+        assert_eq!(app.log.len(), num_logs + 1);
+    }
+}
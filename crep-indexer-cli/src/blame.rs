@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use chrono::DateTime;
+use crep_indexer::index::encoding::decode_to_string;
+use crep_indexer::index::file_filter::not_indexed_message;
+use crep_indexer::index::git_index::GitIndex;
+use git2::Oid;
+use git2::Repository;
+use serde::Serialize;
+
+// Consecutive lines of the file that were introduced by the same commit.
+#[derive(Debug, Serialize)]
+pub struct BlameRange {
+    pub commit_id: String,
+    pub author: String,
+    pub summary: String,
+    // Seconds since the epoch.
+    pub time: i64,
+    // 0-based and the end is exclusive.
+    pub line_start: usize,
+    pub line_end: usize,
+    // Line number of `line_start` in the file at the commit.
+    pub line_start_in_commit: usize,
+}
+
+pub struct Blame {
+    pub lines: Vec<String>,
+    pub ranges: Vec<BlameRange>,
+}
+
+// Blames the file at the indexed HEAD. Everything except the line contents and
+// the commit details comes from the index.
+pub fn blame(
+    index: &GitIndex,
+    repo_path: &str,
+    file_path: &str,
+) -> anyhow::Result<Blame> {
+    let file_id = index.file_name_to_id.get(file_path).ok_or_else(|| {
+        anyhow::anyhow!(not_indexed_message(
+            file_path,
+            index.skipped_files.get(file_path)
+        ))
+    })?;
+
+    let tracker = index
+        .file_id_to_diff_tracker
+        .get(file_id)
+        .filter(|tracker| !tracker.commit_indexes.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!("{file_path} does not exist at the indexed HEAD")
+        })?;
+
+    let repo = Repository::open(Path::new(repo_path))?;
+    let head = index
+        .commit_index_to_commit_id
+        .last()
+        .ok_or_else(|| anyhow::anyhow!("Nothing is indexed"))?;
+
+    let blob = repo
+        .find_commit(Oid::from_bytes(head)?)?
+        .tree()?
+        .get_path(Path::new(file_path))?
+        .to_object(&repo)?
+        .peel_to_blob()?;
+    let lines = decode_to_string(blob.content())
+        .lines()
+        .map(str::to_owned)
+        .collect();
+
+    let mut ranges = vec![];
+    for range in tracker.blame() {
+        let commit_id =
+            Oid::from_bytes(&index.commit_index_to_commit_id[range.commit_id])?;
+        let commit = repo.find_commit(commit_id)?;
+
+        ranges.push(BlameRange {
+            commit_id: commit_id.to_string(),
+            author: commit.author().name().unwrap_or_default().to_owned(),
+            summary: commit.summary().unwrap_or_default().to_owned(),
+            time: commit.time().seconds(),
+            line_start: range.line_start,
+            line_end: range.line_end,
+            line_start_in_commit: range.line_start_in_commit,
+        });
+    }
+
+    Ok(Blame { lines, ranges })
+}
+
+// Prints `git blame` like output of the file at the indexed HEAD.
+pub fn print_blame(
+    index: &GitIndex,
+    repo_path: &str,
+    file_path: &str,
+) -> anyhow::Result<()> {
+    let Blame { lines, ranges } = blame(index, repo_path, file_path)?;
+
+    for range in ranges {
+        let date = DateTime::from_timestamp_secs(range.time)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        for line_num in range.line_start..range.line_end {
+            println!(
+                "{} ({:<16} {} {:>5}) {}",
+                &range.commit_id[0..8],
+                range.author,
+                date,
+                line_num + 1,
+                lines.get(line_num).map_or("", String::as_str)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use std::io;
+use std::io::Write;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+// Copies the text with the OSC 52 escape sequence, so that it also works over
+// SSH (and in tmux with `set-clipboard on`) without a clipboard daemon.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::index::git_indexer::FileId;
+use crep_indexer::search::git_searcher::MatchedQuery;
+use crep_indexer::search::git_searcher::SearchOption;
+use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+
+use crate::searcher::CommitInfo;
+use crate::searcher::DiffLine;
+use crate::searcher::Searcher;
+
+pub struct CommitLogEntry {
+    pub commit_index: CommitIndex,
+    // None if the commit can't be read from the repository.
+    pub info: Option<CommitInfo>,
+}
+
+// Every commit that modified the file of a hit, the newest first.
+pub struct CommitLog {
+    pub file_id: FileId,
+    pub file_path: String,
+    pub query: MatchedQuery,
+    pub entries: Vec<CommitLogEntry>,
+    pub selected: usize,
+    pub diff: FileDiff,
+}
+
+// The change of the file at the selected commit, with every match of the query
+// highlighted.
+#[derive(Default)]
+pub struct FileDiff {
+    pub lines: Vec<DiffLine>,
+    pub words_per_line: BTreeMap<usize, Vec<(String, usize)>>,
+    pub error: Option<String>,
+    pub scroll: u16,
+}
+
+impl CommitLog {
+    // Opens on the `commit_index` if the file was modified there.
+    pub fn load(
+        searcher: &Searcher,
+        query: &MatchedQuery,
+        file_id: FileId,
+        file_path: &str,
+        commit_index: CommitIndex,
+    ) -> Self {
+        let entries = searcher
+            .index()
+            .file_id_to_document
+            .get(&file_id)
+            .map(|document| {
+                document
+                    .doc_modified_commits
+                    .iter()
+                    .rev()
+                    .map(|commit_index| CommitLogEntry {
+                        commit_index: commit_index as CommitIndex,
+                        info: searcher
+                            .get_commit_info(commit_index as usize)
+                            .ok(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let selected = entries
+            .iter()
+            .position(|entry| entry.commit_index == commit_index)
+            .unwrap_or_default();
+
+        let mut log = Self {
+            file_id,
+            file_path: file_path.to_owned(),
+            query: query.clone(),
+            entries,
+            selected,
+            diff: FileDiff::default(),
+        };
+        log.select(searcher, selected);
+
+        log
+    }
+
+    pub fn selected_commit(&self) -> Option<CommitIndex> {
+        self.entries
+            .get(self.selected)
+            .map(|entry| entry.commit_index)
+    }
+
+    pub fn select(&mut self, searcher: &Searcher, selected: usize) {
+        self.selected = selected.min(self.entries.len().saturating_sub(1));
+
+        self.diff = match self.selected_commit() {
+            Some(commit_index) => FileDiff::load(
+                searcher,
+                &self.query,
+                self.file_id,
+                commit_index,
+            ),
+            None => FileDiff {
+                error: Some("No commit modified the file".into()),
+                ..Default::default()
+            },
+        };
+    }
+}
+
+impl FileDiff {
+    fn load(
+        searcher: &Searcher,
+        query: &MatchedQuery,
+        file_id: FileId,
+        commit_index: CommitIndex,
+    ) -> Self {
+        let lines = match searcher.diff_file_at_commit(commit_index, file_id) {
+            Ok(lines) => lines,
+            Err(e) => {
+                return Self {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                };
+            }
+        };
+
+        let words_per_line = SingleCommitSearchResult::new_with_option(
+            query,
+            commit_index,
+            &lines
+                .iter()
+                .map(|line| line.content.as_str())
+                .collect::<Vec<_>>(),
+            &SearchOption {
+                all_matches: true,
+                before_context: Some(0),
+                after_context: Some(0),
+                ..Default::default()
+            },
+        )
+        .ok()
+        .flatten()
+        .map(|result| result.words_per_line)
+        .unwrap_or_default();
+
+        Self {
+            lines,
+            words_per_line,
+            error: None,
+            scroll: 0,
+        }
+    }
+
+    pub fn scroll_by(&mut self, delta: i32) {
+        let max_scroll = self.lines.len().saturating_sub(1) as i32;
+        self.scroll = (self.scroll as i32 + delta).clamp(0, max_scroll) as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crep_indexer::index::git_index::GitIndex;
+
+    use super::*;
+    use crate::test_util::commit_file;
+    use crate::test_util::index_repo;
+    use crate::test_util::init_repo;
+
+    fn query() -> MatchedQuery {
+        MatchedQuery::Words(vec!["hello".to_owned()])
+    }
+
+    fn commits(log: &CommitLog) -> Vec<CommitIndex> {
+        log.entries.iter().map(|entry| entry.commit_index).collect()
+    }
+
+    // a.txt is modified by the commits 0 and 2, b.txt by the commit 1.
+    fn build_index() -> (tempfile::TempDir, GitIndex) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+        commit_file(repo_path, "a.txt", "hello\n");
+        commit_file(repo_path, "b.txt", "abc\n");
+        commit_file(repo_path, "a.txt", "hello\nhello world\n");
+
+        let index = index_repo(repo_path);
+        (dir, index)
+    }
+
+    #[test]
+    fn load_newest_first_and_select_the_hit() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+        let file_id = index.file_name_to_id["a.txt"];
+
+        let log = CommitLog::load(&searcher, &query(), file_id, "a.txt", 0);
+        assert_eq!(commits(&log), vec![2, 0]);
+        assert_eq!(log.selected, 1);
+        assert_eq!(log.selected_commit(), Some(0));
+        assert!(log.entries.iter().all(|entry| entry.info.is_some()));
+        assert!(log.diff.error.is_none());
+
+        // Only the added line matches, not the headers of the diff.
+        let added = log
+            .diff
+            .lines
+            .iter()
+            .position(|line| line.origin == '+')
+            .unwrap();
+        assert_eq!(log.diff.lines[added].content, "hello");
+        assert_eq!(
+            log.diff.words_per_line,
+            BTreeMap::from([(added, vec![("hello".to_owned(), 0)])])
+        );
+
+        // The commit 1 did not modify the file.
+        let log = CommitLog::load(&searcher, &query(), file_id, "a.txt", 1);
+        assert_eq!(log.selected, 0);
+        assert_eq!(log.selected_commit(), Some(2));
+    }
+
+    #[test]
+    fn select_is_clamped() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+        let file_id = index.file_name_to_id["a.txt"];
+
+        let mut log = CommitLog::load(&searcher, &query(), file_id, "a.txt", 2);
+        assert_eq!(log.selected, 0);
+
+        log.select(&searcher, 10);
+        assert_eq!(log.selected, 1);
+        assert_eq!(log.selected_commit(), Some(0));
+    }
+
+    #[test]
+    fn select_on_empty_log() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+
+        // Not an indexed file.
+        let mut log = CommitLog::load(&searcher, &query(), 100, "c.txt", 0);
+        assert!(log.entries.is_empty());
+        assert_eq!(log.selected, 0);
+        assert_eq!(log.selected_commit(), None);
+        assert!(log.diff.error.is_some());
+
+        log.select(&searcher, 3);
+        assert_eq!(log.selected, 0);
+        assert_eq!(log.selected_commit(), None);
+
+        log.diff.scroll_by(5);
+        assert_eq!(log.diff.scroll, 0);
+    }
+}
@@ -0,0 +1,329 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::app::QueryType;
+
+// Older queries are dropped once the history grows past this.
+const MAX_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub query_type: QueryType,
+    pub query: String,
+}
+
+impl HistoryEntry {
+    // Stored as `<query type>\t<query>`.
+    fn parse(line: &str) -> Option<Self> {
+        let (query_type, query) = line.split_once('\t')?;
+
+        Some(Self {
+            query_type: QueryType::parse(query_type)?,
+            query: query.to_owned(),
+        })
+    }
+
+    fn format(&self) -> String {
+        format!("{}\t{}", self.query_type.as_str(), self.query)
+    }
+}
+
+// Queries submitted with `Enter`, the oldest first. Persisted under the user's
+// data dir so that they survive restarts; the default one is not persisted.
+#[derive(Default)]
+pub struct QueryHistory {
+    path: Option<PathBuf>,
+    entries: Vec<HistoryEntry>,
+
+    // Position of the entry recalled by up/down.
+    cursor: Option<usize>,
+}
+
+impl QueryHistory {
+    pub fn load() -> Self {
+        let path = data_file("history");
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content.lines().filter_map(HistoryEntry::parse).collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries,
+            cursor: None,
+        }
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        self.cursor = None;
+        if entry.query.trim().is_empty() || self.entries.last() == Some(&entry)
+        {
+            return Ok(());
+        }
+
+        self.entries.push(entry);
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.drain(..self.entries.len() - MAX_HISTORY);
+        }
+
+        save_lines(
+            self.path.as_ref(),
+            self.entries.iter().map(HistoryEntry::format),
+        )
+    }
+
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+
+    // Older entry than the recalled one.
+    pub fn previous(&mut self) -> Option<&HistoryEntry> {
+        let cursor = match self.cursor {
+            Some(0) => 0,
+            Some(cursor) => cursor - 1,
+            None => self.entries.len().checked_sub(1)?,
+        };
+
+        self.cursor = Some(cursor);
+        self.entries.get(cursor)
+    }
+
+    // Newer entry than the recalled one. None once it passes the newest one.
+    pub fn next(&mut self) -> Option<&HistoryEntry> {
+        let cursor = self.cursor? + 1;
+        if cursor >= self.entries.len() {
+            self.cursor = None;
+            return None;
+        }
+
+        self.cursor = Some(cursor);
+        self.entries.get(cursor)
+    }
+
+    // The newest entry that contains the text, searching from `before`
+    // (exclusive) towards the older ones.
+    pub fn reverse_search(
+        &self,
+        text: &str,
+        before: Option<usize>,
+    ) -> Option<(usize, &HistoryEntry)> {
+        let end = before.unwrap_or(self.entries.len()).min(self.entries.len());
+
+        self.entries[..end]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, entry)| entry.query.contains(text))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub name: String,
+    pub entry: HistoryEntry,
+}
+
+// Named queries, ordered by the name. The default one is not persisted.
+#[derive(Default)]
+pub struct SavedSearches {
+    path: Option<PathBuf>,
+    pub searches: Vec<SavedSearch>,
+}
+
+impl SavedSearches {
+    pub fn load() -> Self {
+        let path = data_file("saved_searches");
+        let searches = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| {
+                        // Stored as `<name>\t<history entry>`.
+                        let (name, entry) = line.split_once('\t')?;
+
+                        Some(SavedSearch {
+                            name: name.to_owned(),
+                            entry: HistoryEntry::parse(entry)?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { path, searches }
+    }
+
+    // Replaces the search with the same name.
+    pub fn save(&mut self, name: &str, entry: HistoryEntry) -> io::Result<()> {
+        // Tabs separate the fields of the file.
+        let name = name.trim().replace('\t', " ");
+
+        self.searches.retain(|search| search.name != name);
+        self.searches.push(SavedSearch { name, entry });
+        self.searches.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.persist()
+    }
+
+    pub fn remove(&mut self, index: usize) -> io::Result<()> {
+        if index < self.searches.len() {
+            self.searches.remove(index);
+        }
+
+        self.persist()
+    }
+
+    fn persist(&self) -> io::Result<()> {
+        save_lines(
+            self.path.as_ref(),
+            self.searches.iter().map(|search| {
+                format!("{}\t{}", search.name, search.entry.format())
+            }),
+        )
+    }
+}
+
+// None if the platform has no data dir; then nothing is persisted.
+fn data_file(name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("crep").join(name))
+}
+
+fn save_lines(
+    path: Option<&PathBuf>,
+    lines: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut content = lines.collect::<Vec<_>>().join("\n");
+    content.push('\n');
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str) -> HistoryEntry {
+        HistoryEntry {
+            query_type: QueryType::RawString,
+            query: query.to_owned(),
+        }
+    }
+
+    // Not persisted.
+    fn history(queries: &[&str]) -> QueryHistory {
+        let mut history = QueryHistory {
+            path: None,
+            entries: vec![],
+            cursor: None,
+        };
+        for query in queries {
+            history.push(entry(query)).unwrap();
+        }
+
+        history
+    }
+
+    fn queries(history: &QueryHistory) -> Vec<&str> {
+        history
+            .entries
+            .iter()
+            .map(|entry| entry.query.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn push_skips_empty_and_repeated_queries() {
+        let mut history = history(&["a", "a", "  ", "b", "a"]);
+        assert_eq!(queries(&history), vec!["a", "b", "a"]);
+
+        // Same query with the different type is a different entry.
+        history
+            .push(HistoryEntry {
+                query_type: QueryType::Regex,
+                query: "a".to_owned(),
+            })
+            .unwrap();
+        assert_eq!(queries(&history), vec!["a", "b", "a", "a"]);
+    }
+
+    #[test]
+    fn push_drops_the_oldest_entries() {
+        let mut history = history(&[]);
+        for i in 0..MAX_HISTORY + 10 {
+            history.push(entry(&i.to_string())).unwrap();
+        }
+
+        assert_eq!(history.entries.len(), MAX_HISTORY);
+        assert_eq!(history.entries[0], entry("10"));
+        assert_eq!(
+            history.entries.last(),
+            Some(&entry(&(MAX_HISTORY + 9).to_string()))
+        );
+    }
+
+    #[test]
+    fn previous_and_next() {
+        let mut history = history(&["a", "b", "c"]);
+
+        assert_eq!(history.next(), None);
+        assert_eq!(history.previous(), Some(&entry("c")));
+        assert_eq!(history.previous(), Some(&entry("b")));
+        assert_eq!(history.previous(), Some(&entry("a")));
+        // Stays at the oldest one.
+        assert_eq!(history.previous(), Some(&entry("a")));
+
+        assert_eq!(history.next(), Some(&entry("b")));
+        assert_eq!(history.next(), Some(&entry("c")));
+        // Passes the newest one and starts over.
+        assert_eq!(history.next(), None);
+        assert_eq!(history.next(), None);
+        assert_eq!(history.previous(), Some(&entry("c")));
+
+        // Pushing resets the cursor.
+        history.previous();
+        history.push(entry("d")).unwrap();
+        assert_eq!(history.previous(), Some(&entry("d")));
+    }
+
+    #[test]
+    fn previous_of_empty_history() {
+        let mut history = history(&[]);
+
+        assert_eq!(history.previous(), None);
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn reverse_search() {
+        let history = history(&["foo", "bar", "foobar", "baz"]);
+
+        assert_eq!(
+            history.reverse_search("foo", None),
+            Some((2, &entry("foobar")))
+        );
+        assert_eq!(
+            history.reverse_search("foo", Some(2)),
+            Some((0, &entry("foo")))
+        );
+        assert_eq!(history.reverse_search("foo", Some(0)), None);
+        assert_eq!(history.reverse_search("qux", None), None);
+        // Out of range starts from the newest one.
+        assert_eq!(
+            history.reverse_search("ba", Some(100)),
+            Some((3, &entry("baz")))
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Mutex;
+use std::thread;
+
+use crep_indexer::search::cancellation::CancellationToken;
+use log::error;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json::json;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+// Same as LSP's `RequestCancelled`.
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+// Both the newline delimited messages and the `Content-Length` framed ones
+// (as in LSP) are accepted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framing {
+    Line,
+    ContentLength,
+}
+
+// A request if it has the id, a notification otherwise. Responses (to the
+// requests sent by us) have no method.
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_params(message: impl ToString) -> Self {
+        Self::new(INVALID_PARAMS, message.to_string())
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, format!("Unknown method {method}"))
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(INTERNAL_ERROR, e.to_string())
+    }
+}
+
+pub trait Handler: Sync {
+    // `writer` sends the notifications while the request is running.
+    fn handle<W: Write + Send>(
+        &self,
+        method: &str,
+        id: &Value,
+        params: Value,
+        cancellation: &CancellationToken,
+        writer: &MessageWriter<W>,
+    ) -> Result<Value, RpcError>;
+}
+
+// Serves the requests from stdin until the `exit` notification or the end of
+// the input. Every request runs on its own thread, so that a long search does
+// not block the others and can be cancelled with `$/cancelRequest`.
+// Notifications other than these two are ignored.
+pub fn serve(handler: &impl Handler) -> anyhow::Result<()> {
+    serve_with(
+        handler,
+        &mut io::stdin().lock(),
+        &MessageWriter::new(io::stdout()),
+    )
+}
+
+fn serve_with<W: Write + Send>(
+    handler: &impl Handler,
+    reader: &mut impl BufRead,
+    writer: &MessageWriter<W>,
+) -> anyhow::Result<()> {
+    // Tokens of the running requests by their ids.
+    let running: Mutex<HashMap<String, CancellationToken>> =
+        Mutex::new(HashMap::new());
+
+    thread::scope(|scope| {
+        while let Some((body, framing)) = read_message(reader)? {
+            writer.set_framing(framing);
+
+            let message = match serde_json::from_str::<Value>(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    writer.respond(
+                        Value::Null,
+                        Err(RpcError::new(PARSE_ERROR, e.to_string())),
+                    )?;
+                    continue;
+                }
+            };
+            let Message { id, method, params } =
+                match serde_json::from_value::<Message>(message) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        writer.respond(
+                            Value::Null,
+                            Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                        )?;
+                        continue;
+                    }
+                };
+
+            // Responses to our requests; we never send one.
+            let Some(method) = method else {
+                continue;
+            };
+
+            match (method.as_str(), id) {
+                ("exit", _) => break,
+                ("$/cancelRequest", _) => {
+                    if let Some(cancellation) =
+                        running.lock().unwrap().get(&params["id"].to_string())
+                    {
+                        cancellation.cancel();
+                    }
+                }
+                (_, None) => {}
+                (_, Some(id)) => {
+                    let cancellation = CancellationToken::new();
+                    running
+                        .lock()
+                        .unwrap()
+                        .insert(id.to_string(), cancellation.clone());
+
+                    let running = &running;
+                    scope.spawn(move || {
+                        let result = handler.handle(
+                            &method,
+                            &id,
+                            params,
+                            &cancellation,
+                            writer,
+                        );
+                        running.lock().unwrap().remove(&id.to_string());
+
+                        let result = match cancellation.is_cancelled() {
+                            true => Err(RpcError::new(
+                                REQUEST_CANCELLED,
+                                "Request cancelled",
+                            )),
+                            false => result,
+                        };
+
+                        if let Err(e) = writer.respond(id, result) {
+                            error!("Unable to respond to {method}: {e}");
+                        }
+                    });
+                }
+            }
+        }
+
+        // Nobody waits for the running requests anymore.
+        for cancellation in running.lock().unwrap().values() {
+            cancellation.cancel();
+        }
+
+        Ok(())
+    })
+}
+
+pub fn parse_params<T: for<'de> Deserialize<'de>>(
+    params: Value,
+) -> Result<T, RpcError> {
+    // Omitted params are the same as the empty ones.
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+// Returns None at the end of the input.
+pub fn read_message(
+    reader: &mut impl BufRead,
+) -> io::Result<Option<(String, Framing)>> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let Some((name, value)) = trimmed.split_once(':') else {
+            return Ok(Some((trimmed.to_owned(), Framing::Line)));
+        };
+        if !name.eq_ignore_ascii_case("content-length") {
+            return Ok(Some((trimmed.to_owned(), Framing::Line)));
+        }
+
+        let length = value.trim().parse::<usize>().map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        })?;
+
+        // Skips the rest of the headers (e.g. `Content-Type`).
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        return String::from_utf8(body)
+            .map(|body| Some((body, Framing::ContentLength)))
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+            });
+    }
+}
+
+// Writes every message at once, so that the threads can share it. Messages
+// use the framing of the last message read.
+pub struct MessageWriter<W: Write> {
+    inner: Mutex<(W, Framing)>,
+}
+
+impl<W: Write> MessageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: Mutex::new((writer, Framing::Line)),
+        }
+    }
+
+    pub fn set_framing(&self, framing: Framing) {
+        self.inner.lock().unwrap().1 = framing;
+    }
+
+    pub fn send(&self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+
+        let mut inner = self.inner.lock().unwrap();
+        let (writer, framing) = &mut *inner;
+        match framing {
+            Framing::Line => writeln!(writer, "{body}")?,
+            Framing::ContentLength => {
+                write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn respond(
+        &self,
+        id: Value,
+        result: Result<Value, RpcError>,
+    ) -> io::Result<()> {
+        self.send(&match result {
+            Ok(result) => {
+                json!({ "jsonrpc": "2.0", "id": id, "result": result })
+            }
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": e.code, "message": e.message },
+            }),
+        })
+    }
+
+    pub fn notify(&self, method: &str, params: Value) -> io::Result<()> {
+        self.send(
+            &json!({ "jsonrpc": "2.0", "method": method, "params": params }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Read;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn read_all(input: &str) -> Vec<(String, Framing)> {
+        let mut reader = Cursor::new(input.as_bytes().to_vec());
+
+        let mut messages = vec![];
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    fn sent_messages(writer: MessageWriter<Vec<u8>>) -> Vec<Value> {
+        let (output, _) = writer.inner.into_inner().unwrap();
+
+        serde_json::Deserializer::from_slice(&output)
+            .into_iter::<Value>()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn read_line_delimited_messages() {
+        assert_eq!(
+            read_all("{\"id\":1}\n\n  {\"id\":2}  \r\n{\"id\":3}"),
+            vec![
+                ("{\"id\":1}".to_owned(), Framing::Line),
+                ("{\"id\":2}".to_owned(), Framing::Line),
+                ("{\"id\":3}".to_owned(), Framing::Line),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_content_length_messages() {
+        let body = "{\"a\":\n\"b\"}";
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{body}\
+             content-length:{}\r\n\r\n{body}",
+            body.len(),
+            body.len()
+        );
+
+        assert_eq!(
+            read_all(&input),
+            vec![
+                (body.to_owned(), Framing::ContentLength),
+                (body.to_owned(), Framing::ContentLength),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_mixed_framing() {
+        assert_eq!(
+            read_all("Content-Length: 2\r\n\r\n{}{\"id\":1}\n"),
+            vec![
+                ("{}".to_owned(), Framing::ContentLength),
+                ("{\"id\":1}".to_owned(), Framing::Line),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_invalid_content_length() {
+        let mut reader = Cursor::new(b"Content-Length: abc\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut reader).is_err());
+
+        // The input ends in the headers.
+        let mut reader = Cursor::new(
+            b"Content-Length: 2\r\nContent-Type: json\r\n".to_vec(),
+        );
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        // The input ends in the body.
+        let mut reader = Cursor::new(b"Content-Length: 10\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn write_with_the_framing() {
+        let writer = MessageWriter::new(vec![]);
+        writer.respond(json!(1), Ok(json!("ok"))).unwrap();
+        writer.set_framing(Framing::ContentLength);
+        writer
+            .respond(json!(2), Err(RpcError::method_not_found("foo")))
+            .unwrap();
+
+        let (output, _) = writer.inner.into_inner().unwrap();
+        let error = r#"{"error":{"code":-32601,"message":"Unknown method foo"},"id":2,"jsonrpc":"2.0"}"#;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "{}\nContent-Length: {}\r\n\r\n{error}",
+                r#"{"id":1,"jsonrpc":"2.0","result":"ok"}"#,
+                error.len()
+            )
+        );
+    }
+
+    // `wait` runs until it is cancelled and reports its id.
+    struct WaitHandler {
+        cancelled: Mutex<mpsc::Sender<Value>>,
+    }
+
+    impl Handler for WaitHandler {
+        fn handle<W: Write + Send>(
+            &self,
+            method: &str,
+            id: &Value,
+            params: Value,
+            cancellation: &CancellationToken,
+            _writer: &MessageWriter<W>,
+        ) -> Result<Value, RpcError> {
+            match method {
+                "wait" => {
+                    while !cancellation.is_cancelled() {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    self.cancelled.lock().unwrap().send(id.clone()).unwrap();
+                    Ok(Value::Null)
+                }
+                "echo" => Ok(params),
+                _ => Err(RpcError::method_not_found(method)),
+            }
+        }
+    }
+
+    // Does not end the input until a request reports that it was cancelled,
+    // since every running request is cancelled at the end of the input.
+    struct EndAfterCancelled {
+        input: Cursor<Vec<u8>>,
+        cancelled: Option<mpsc::Receiver<Value>>,
+    }
+
+    impl Read for EndAfterCancelled {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.fill_buf()?.read(buf)?;
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for EndAfterCancelled {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            if self.input.position() == self.input.get_ref().len() as u64
+                && let Some(cancelled) = self.cancelled.take()
+            {
+                assert_eq!(
+                    cancelled.recv_timeout(Duration::from_secs(10)),
+                    Ok(json!(1))
+                );
+            }
+            self.input.fill_buf()
+        }
+
+        fn consume(&mut self, amount: usize) {
+            self.input.consume(amount)
+        }
+    }
+
+    #[test]
+    fn cancel_request() {
+        let (send_cancelled, recv_cancelled) = mpsc::channel();
+        let handler = WaitHandler {
+            cancelled: Mutex::new(send_cancelled),
+        };
+
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"wait"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"echo","params":"hi"}"#,
+            // Unknown ids are ignored.
+            r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":3}}"#,
+            r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":1}}"#,
+            // The reader waits at the end of the input, after the last line.
+            "",
+        ]
+        .join("\n");
+        let mut reader = EndAfterCancelled {
+            input: Cursor::new(input.into_bytes()),
+            cancelled: Some(recv_cancelled),
+        };
+
+        let writer = MessageWriter::new(vec![]);
+        serve_with(&handler, &mut reader, &writer).unwrap();
+
+        let mut messages = sent_messages(writer);
+        messages.sort_by_key(|message| message["id"].as_i64());
+        assert_eq!(
+            messages,
+            vec![
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": {
+                        "code": REQUEST_CANCELLED,
+                        "message": "Request cancelled",
+                    },
+                }),
+                json!({ "jsonrpc": "2.0", "id": 2, "result": "hi" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let handler = WaitHandler {
+            cancelled: Mutex::new(mpsc::channel().0),
+        };
+
+        let mut reader = Cursor::new(
+            b"not json\n{\"id\":1,\"method\":3}\n{\"method\":\"exit\"}\n{\"id\":2,\"method\":\"echo\"}\n"
+                .to_vec(),
+        );
+        let writer = MessageWriter::new(vec![]);
+        serve_with(&handler, &mut reader, &writer).unwrap();
+
+        let messages = sent_messages(writer);
+        // Nothing is served after `exit`.
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(messages[1]["error"]["code"], json!(INVALID_REQUEST));
+    }
+}
@@ -0,0 +1,729 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crep_indexer::index::git_index::GitIndex;
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::search::cancellation::CancellationToken;
+use crep_indexer::search::git_searcher::Query;
+use crep_indexer::search::git_searcher::SearchOption;
+use crep_indexer::search::result::search_result::SearchResult;
+use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json::json;
+
+use crate::blame;
+use crate::jsonrpc::Handler;
+use crate::jsonrpc::MessageWriter;
+use crate::jsonrpc::RpcError;
+use crate::jsonrpc::parse_params;
+use crate::jsonrpc::serve;
+use crate::rpc::commit_sha;
+use crate::rpc::find_commit;
+use crate::rpc::find_file;
+use crate::rpc::parse_query_type;
+use crate::searcher::RepoPool;
+use crate::searcher::Searcher;
+
+#[derive(Deserialize)]
+struct WorkspaceSymbolParams {
+    query: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistorySearchParams {
+    query: String,
+    // `plain` (the default), `regex` or `fuzzy`.
+    query_type: Option<String>,
+    #[serde(default)]
+    whole_word: bool,
+    max_results: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct Position {
+    line: usize,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+// The lines of the selection, or the line of the cursor.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WhenIntroducedParams {
+    text_document: TextDocumentIdentifier,
+    range: Option<Range>,
+    position: Option<Position>,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentContentParams {
+    uri: String,
+}
+
+// The file in the working tree (`file://`), or the file at a commit served
+// as a virtual document (`crep://<commit>/<path>`).
+enum DocumentUri {
+    Head(String),
+    AtCommit(CommitIndex, String),
+}
+
+struct LspServer {
+    index: GitIndex,
+    repo_path: String,
+    // Absolute path of the repo, so that the paths map to `file://` URIs.
+    root: PathBuf,
+    // Opened once instead of per request.
+    repo_pool: RepoPool,
+}
+
+pub fn run_lsp(index: GitIndex, repo_path: &str) -> anyhow::Result<()> {
+    serve(&LspServer {
+        index,
+        repo_path: repo_path.to_owned(),
+        root: Path::new(repo_path).canonicalize()?,
+        repo_pool: RepoPool::new(repo_path),
+    })
+}
+
+impl Handler for LspServer {
+    fn handle<W: Write + Send>(
+        &self,
+        method: &str,
+        _id: &Value,
+        params: Value,
+        cancellation: &CancellationToken,
+        _writer: &MessageWriter<W>,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "workspaceSymbolProvider": true,
+                    "workspace": {
+                        "textDocumentContent": { "schemes": ["crep"] },
+                    },
+                },
+                "serverInfo": {
+                    "name": "crep",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "workspace/symbol" => {
+                self.workspace_symbol(params, cancellation.clone())
+            }
+            "workspace/textDocumentContent" => {
+                self.text_document_content(params)
+            }
+            "crep/historySearch" => {
+                self.history_search(params, cancellation.clone())
+            }
+            "crep/whenIntroduced" => self.when_introduced(params),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
+
+impl LspServer {
+    fn searcher(&self) -> Searcher<'_> {
+        Searcher::with_pool(
+            &self.index,
+            self.repo_pool.clone(),
+            &self.repo_path,
+        )
+    }
+
+    fn head(&self) -> Result<CommitIndex, RpcError> {
+        find_commit(&self.index, "HEAD")
+    }
+
+    // Definitions at HEAD of the identifiers that contain the query.
+    fn workspace_symbol(
+        &self,
+        params: Value,
+        cancellation: CancellationToken,
+    ) -> Result<Value, RpcError> {
+        let params: WorkspaceSymbolParams = parse_params(params)?;
+        let query = params.query.trim();
+        if query.is_empty() {
+            return Ok(json!([]));
+        }
+
+        let head = self.head()?;
+        let symbols = Mutex::new(BTreeSet::new());
+
+        self.searcher().stream_query(
+            &Query::Plain(query.to_owned()),
+            SearchOption {
+                all_matches: true,
+                before_context: Some(0),
+                after_context: Some(0),
+                cancellation: Some(cancellation),
+                ..Default::default()
+            },
+            MAX_SYMBOL_FILES,
+            |hit| {
+                let Some(result) = match_at(&hit.result, head) else {
+                    return;
+                };
+
+                for (line_num, words) in &result.words_per_line {
+                    let Some(line) = result.lines.get(line_num) else {
+                        continue;
+                    };
+
+                    for (_, col) in words {
+                        let (start, end) = identifier_at(line, *col);
+                        if let Some(kind) = definition_kind(&line[..start]) {
+                            symbols.lock().unwrap().insert((
+                                line[start..end].to_owned(),
+                                hit.result.file_path.clone(),
+                                *line_num,
+                                utf16_len(&line[..start]),
+                                utf16_len(&line[..end]),
+                                kind,
+                            ));
+                        }
+                    }
+                }
+            },
+        )?;
+
+        Ok(Value::Array(
+            symbols
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .take(MAX_SYMBOLS)
+                .map(|(name, path, line, start, end, kind)| {
+                    json!({
+                        "name": name,
+                        "kind": kind,
+                        "location": {
+                            "uri": self.file_uri(&path),
+                            "range": line_range(line, start, end),
+                        },
+                        "containerName": path,
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    // The first and the last commits where each file matched the query. The
+    // matches at HEAD point to the working tree, the older ones to the
+    // `crep://` documents.
+    fn history_search(
+        &self,
+        params: Value,
+        cancellation: CancellationToken,
+    ) -> Result<Value, RpcError> {
+        let params: HistorySearchParams = parse_params(params)?;
+        let query_type = parse_query_type(params.query_type.as_deref())?;
+
+        let head = self.head()?;
+        let hits = Mutex::new(vec![]);
+
+        self.searcher().stream_query(
+            &query_type.to_query(&params.query),
+            SearchOption {
+                whole_word: params.whole_word,
+                before_context: Some(0),
+                after_context: Some(0),
+                cancellation: Some(cancellation),
+                ..Default::default()
+            },
+            params.max_results.unwrap_or(MAX_SYMBOL_FILES),
+            |hit| {
+                let path = &hit.result.file_path;
+                let last_match = hit.result.last_match.as_ref();
+
+                hits.lock().unwrap().push(json!({
+                    "path": path,
+                    "firstMatch":
+                        self.match_location(path, &hit.result.first_match, head),
+                    "lastMatch": last_match
+                        .map(|last| self.match_location(path, last, head)),
+                }));
+            },
+        )?;
+
+        Ok(Value::Array(hits.into_inner().unwrap()))
+    }
+
+    // The commits that introduced the lines, blamed at HEAD.
+    fn when_introduced(&self, params: Value) -> Result<Value, RpcError> {
+        let params: WhenIntroducedParams = parse_params(params)?;
+
+        let path = match self.parse_uri(&params.text_document.uri)? {
+            DocumentUri::Head(path) => path,
+            DocumentUri::AtCommit(..) => {
+                return Err(RpcError::invalid_params(
+                    "Only the files at HEAD can be blamed",
+                ));
+            }
+        };
+        let (first_line, last_line) = match (params.range, params.position) {
+            (Some(range), _) => (range.start.line, range.end.line),
+            (None, Some(position)) => (position.line, position.line),
+            (None, None) => {
+                return Err(RpcError::invalid_params(
+                    "Either the range or the position is required",
+                ));
+            }
+        };
+
+        let blame = blame::blame(&self.index, &self.repo_path, &path)?;
+
+        Ok(Value::Array(
+            blame
+                .ranges
+                .iter()
+                .filter(|range| {
+                    range.line_start <= last_line && first_line < range.line_end
+                })
+                .map(|range| {
+                    let start = range.line_start.max(first_line);
+                    let end = range.line_end.min(last_line + 1);
+                    let line_in_commit =
+                        range.line_start_in_commit + start - range.line_start;
+
+                    json!({
+                        "range": {
+                            "start": { "line": start, "character": 0 },
+                            "end": { "line": end, "character": 0 },
+                        },
+                        "commit": range.commit_id,
+                        "author": range.author,
+                        "time": range.time,
+                        "summary": range.summary,
+                        "location": {
+                            "uri": commit_uri(&range.commit_id, &path),
+                            "range": line_range(line_in_commit, 0, 0),
+                        },
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    fn text_document_content(&self, params: Value) -> Result<Value, RpcError> {
+        let params: TextDocumentContentParams = parse_params(params)?;
+
+        let (commit_index, path) = match self.parse_uri(&params.uri)? {
+            DocumentUri::Head(path) => (self.head()?, path),
+            DocumentUri::AtCommit(commit_index, path) => (commit_index, path),
+        };
+        let file_id = find_file(&self.index, &path)?;
+
+        let text = self
+            .searcher()
+            .read_file_at_commit(commit_index, file_id)?
+            .ok_or_else(|| {
+                RpcError::invalid_params(format!(
+                    "{path} is not a file at the commit"
+                ))
+            })?;
+
+        Ok(json!({ "text": text }))
+    }
+
+    fn match_location(
+        &self,
+        path: &str,
+        result: &SingleCommitSearchResult,
+        head: CommitIndex,
+    ) -> Value {
+        let commit = commit_sha(&self.index, result.commit_id);
+        let uri = match result.commit_id == head {
+            true => self.file_uri(path),
+            false => commit_uri(&commit, path),
+        };
+
+        let (line_num, line) = result
+            .lines
+            .iter()
+            .next()
+            .map_or((0, ""), |(line_num, line)| (*line_num, line.as_str()));
+        let (start, end) = result
+            .words_per_line
+            .get(&line_num)
+            .and_then(|words| words.first())
+            .map_or((0, 0), |(word, col)| (*col, col + word.len()));
+
+        json!({
+            "commit": commit,
+            "atHead": result.commit_id == head,
+            "line": line,
+            "location": {
+                "uri": uri,
+                "range": line_range(
+                    line_num,
+                    line.get(..start).map_or(0, utf16_len),
+                    line.get(..end).map_or(0, utf16_len),
+                ),
+            },
+        })
+    }
+
+    fn file_uri(&self, path: &str) -> String {
+        format!(
+            "file://{}",
+            encode_path(&self.root.join(path).to_string_lossy())
+        )
+    }
+
+    fn parse_uri(&self, uri: &str) -> Result<DocumentUri, RpcError> {
+        if let Some(rest) = uri.strip_prefix("crep://") {
+            let (commit, path) = rest.split_once('/').ok_or_else(|| {
+                RpcError::invalid_params(format!("{uri} has no path"))
+            })?;
+
+            return Ok(DocumentUri::AtCommit(
+                find_commit(&self.index, commit)?,
+                decode_path(path),
+            ));
+        }
+
+        if let Some(path) = uri.strip_prefix("file://") {
+            let path = PathBuf::from(decode_path(path));
+            let path = path.strip_prefix(&self.root).map_err(|_| {
+                RpcError::invalid_params(format!("{uri} is not in the repo"))
+            })?;
+
+            return Ok(DocumentUri::Head(path.to_string_lossy().into_owned()));
+        }
+
+        Err(RpcError::invalid_params(format!("Unsupported URI {uri}")))
+    }
+}
+
+// The match at HEAD, if the file still matches there.
+fn match_at(
+    result: &SearchResult,
+    head: CommitIndex,
+) -> Option<&SingleCommitSearchResult> {
+    let last = result.last_match.as_ref().unwrap_or(&result.first_match);
+    (last.commit_id == head).then_some(last)
+}
+
+// Byte range of the identifier that contains the column.
+fn identifier_at(line: &str, col: usize) -> (usize, usize) {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+    let start = line[..col]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier(*c))
+        .last()
+        .map_or(col, |(index, _)| index);
+    let end = line[col..]
+        .char_indices()
+        .find(|(_, c)| !is_identifier(*c))
+        .map_or(line.len(), |(index, _)| col + index);
+
+    (start, end)
+}
+
+// LSP `SymbolKind` of the identifier after the text, if the text ends with a
+// keyword that defines one (e.g. `pub fn`, `class`). Only a guess, but it
+// works for most of the languages without parsing them.
+fn definition_kind(before: &str) -> Option<u32> {
+    let keyword = before
+        .trim_end_matches(|c: char| c.is_whitespace() || c == '!')
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()?;
+
+    match keyword {
+        "mod" | "module" => Some(2),
+        "namespace" => Some(3),
+        "package" => Some(4),
+        "class" => Some(5),
+        "enum" => Some(10),
+        "trait" | "interface" | "protocol" => Some(11),
+        "fn" | "def" | "func" | "function" | "macro_rules" => Some(12),
+        "const" | "static" => Some(14),
+        "struct" | "union" => Some(23),
+        _ => None,
+    }
+}
+
+fn line_range(line: usize, start: usize, end: usize) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+// LSP counts the characters in UTF-16 code units.
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+fn commit_uri(commit: &str, path: &str) -> String {
+    format!("crep://{commit}/{}", encode_path(path))
+}
+
+// Percent-encodes everything but the unreserved characters and `/`.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| hex::decode(hex).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.extend(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Number of the files searched for the symbols (and the history matches,
+// unless the request sets `maxResults`).
+const MAX_SYMBOL_FILES: usize = 100;
+const MAX_SYMBOLS: usize = 200;
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::test_util::commit_file;
+    use crate::test_util::index_repo;
+    use crate::test_util::init_repo;
+
+    // Commits the files one by one and serves the indexed repo.
+    fn build_server(
+        files: &[(&str, &str)],
+    ) -> (TempDir, LspServer, Vec<String>) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+        let commits = files
+            .iter()
+            .map(|(name, content)| commit_file(repo_path, name, content))
+            .collect();
+
+        let repo_path_str = repo_path.to_str().unwrap();
+        let server = LspServer {
+            index: index_repo(repo_path),
+            repo_path: repo_path_str.to_owned(),
+            root: repo_path.canonicalize().unwrap(),
+            repo_pool: RepoPool::new(repo_path_str),
+        };
+
+        (dir, server, commits)
+    }
+
+    #[test]
+    fn encode_and_decode_path() {
+        assert_eq!(encode_path("a b/100%/é.rs"), "a%20b/100%25/%C3%A9.rs");
+
+        for path in [
+            "src/main.rs",
+            "dir with space/100%/ünï©ode 名前.rs",
+            "a%2Fb?#.txt",
+        ] {
+            assert_eq!(decode_path(&encode_path(path)), path);
+        }
+
+        // Broken escapes are kept as is.
+        assert_eq!(decode_path("100%"), "100%");
+        assert_eq!(decode_path("%zz%4"), "%zz%4");
+        assert_eq!(decode_path("%2f"), "/");
+    }
+
+    #[test]
+    fn identifier_at_multi_byte_line() {
+        let line = "let größe = über_größe + 1;";
+
+        let first = line.find("größe").unwrap();
+        assert_eq!(identifier_at(line, first), (first, first + "größe".len()));
+
+        // The column in the middle of the identifier.
+        let start = line.find("über").unwrap();
+        let col = line.find("_größe").unwrap() + 1;
+        assert_eq!(
+            identifier_at(line, col),
+            (start, start + "über_größe".len())
+        );
+
+        assert_eq!(identifier_at("a + b", 2), (2, 2));
+        assert_eq!(identifier_at("abc", 3), (0, 3));
+    }
+
+    #[test]
+    fn definition_kind_from_keyword() {
+        assert_eq!(definition_kind("pub fn "), Some(12));
+        assert_eq!(definition_kind("    class "), Some(5));
+        assert_eq!(definition_kind("macro_rules! "), Some(12));
+        assert_eq!(definition_kind("pub(crate) struct "), Some(23));
+        assert_eq!(definition_kind("let x = "), None);
+        assert_eq!(definition_kind(""), None);
+    }
+
+    #[test]
+    fn workspace_symbol_in_utf16_columns() {
+        let (_dir, server, _) = build_server(&[(
+            "src/lib.rs",
+            "/* 😀 */ fn parse_query() {}\nlet x = parse_query();\n",
+        )]);
+
+        let symbols = server
+            .workspace_symbol(
+                json!({ "query": "parse" }),
+                CancellationToken::new(),
+            )
+            .unwrap();
+
+        // The emoji is two UTF-16 code units, and the call is not a definition.
+        assert_eq!(
+            symbols,
+            json!([{
+                "name": "parse_query",
+                "kind": 12,
+                "location": {
+                    "uri": server.file_uri("src/lib.rs"),
+                    "range": line_range(0, 12, 23),
+                },
+                "containerName": "src/lib.rs",
+            }])
+        );
+
+        assert_eq!(
+            server
+                .workspace_symbol(
+                    json!({ "query": " " }),
+                    CancellationToken::new()
+                )
+                .unwrap(),
+            json!([])
+        );
+    }
+
+    #[test]
+    fn parse_file_and_commit_uris() {
+        let (_dir, server, commits) = build_server(&[("a.txt", "abc\n")]);
+
+        let path = "dir with space/ü%.rs";
+        assert!(matches!(
+            server.parse_uri(&server.file_uri(path)),
+            Ok(DocumentUri::Head(head_path)) if head_path == path
+        ));
+        assert!(matches!(
+            server.parse_uri(&commit_uri(&commits[0], path)),
+            Ok(DocumentUri::AtCommit(0, commit_path)) if commit_path == path
+        ));
+
+        assert!(server.parse_uri("file:///elsewhere/a.txt").is_err());
+        assert!(server.parse_uri(&format!("crep://{}", commits[0])).is_err());
+        assert!(server.parse_uri("https://example.com/a.txt").is_err());
+    }
+
+    #[test]
+    fn when_introduced_clips_blame_ranges() {
+        let (_dir, server, commits) = build_server(&[
+            ("a.txt", "a\nb\nc\n"),
+            ("a.txt", "a\nb\nc\nd\ne\n"),
+        ]);
+        let uri = server.file_uri("a.txt");
+
+        // (start, end, commit, line in the commit) of every range.
+        let introduced = |params: Value| {
+            let ranges = server.when_introduced(params).unwrap();
+            ranges
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|range| {
+                    (
+                        range["range"]["start"]["line"].as_u64().unwrap(),
+                        range["range"]["end"]["line"].as_u64().unwrap(),
+                        range["commit"].as_str().unwrap().to_owned(),
+                        range["location"]["range"]["start"]["line"]
+                            .as_u64()
+                            .unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            introduced(json!({
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": 2, "character": 0 },
+                    "end": { "line": 3, "character": 1 },
+                },
+            })),
+            vec![(2, 3, commits[0].clone(), 2), (3, 4, commits[1].clone(), 3)]
+        );
+        assert_eq!(
+            introduced(json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": 4, "character": 0 },
+            })),
+            vec![(4, 5, commits[1].clone(), 4)]
+        );
+
+        assert!(
+            server
+                .when_introduced(json!({ "textDocument": { "uri": uri } }))
+                .is_err()
+        );
+        assert!(
+            server
+                .when_introduced(json!({
+                    "textDocument": { "uri": commit_uri(&commits[0], "a.txt") },
+                    "position": { "line": 0 },
+                }))
+                .is_err()
+        );
+    }
+}
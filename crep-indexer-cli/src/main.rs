@@ -1,22 +1,40 @@
 mod app;
+mod blame;
+mod clipboard;
+mod commit_log;
+mod history;
+mod jsonrpc;
 mod logger;
+mod lsp;
+mod preview;
 mod raw_searcher;
+mod rpc;
 mod searcher;
+#[cfg(test)]
+mod test_util;
 
 use std::io::{self};
 use std::path::Path;
 
 use app::App;
+use blame::print_blame;
 use clap::Parser;
+use clap::Subcommand;
+use crep_indexer::index::file_filter::FileFilterConfig;
 use crep_indexer::index::git_index::GitIndex;
 use crep_indexer::index::git_index_serialization::GitIndexSerialization;
 use crep_indexer::index::git_index_serialization::GitIndexSerializationRef;
 use crep_indexer::index::git_indexer::GitIndexer;
 use crep_indexer::index::git_indexer::GitIndexerConfig;
+use crep_indexer::search::git_searcher::Query;
+use crep_indexer::search::summary::OutputMode;
 
 use log::LevelFilter;
 use logger::init_file_logger;
+use lsp::run_lsp;
 use raw_searcher::handle_query;
+use raw_searcher::run_search;
+use rpc::run_rpc;
 use searcher::Searcher;
 
 #[derive(Parser, Debug)]
@@ -47,6 +65,63 @@ struct Args {
 
     #[arg(short)]
     continue_index: bool,
+
+    /// Gitignore style pattern of the files to index. Can be repeated.
+    #[arg(long = "include")]
+    include_patterns: Vec<String>,
+
+    /// Gitignore style pattern of the files not to index. Can be repeated.
+    #[arg(long = "exclude")]
+    exclude_patterns: Vec<String>,
+
+    /// Skips the files larger than this (in bytes).
+    #[arg(long)]
+    max_file_size: Option<u64>,
+
+    /// Skips the files that have a longer line than this (in bytes).
+    #[arg(long)]
+    max_line_length: Option<usize>,
+
+    /// Skips the lockfiles, the generated code and the minified files.
+    #[arg(long)]
+    skip_generated: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints the commit that introduced every line of the file at the
+    /// indexed HEAD.
+    Blame {
+        /// Path of the file relative to the repo root.
+        file: String,
+    },
+    /// Serves JSON-RPC over stdin/stdout for the editor integrations.
+    Rpc,
+    /// Serves the Language Server Protocol over stdin/stdout.
+    Lsp,
+    /// Runs a single query and prints the results.
+    Search {
+        query: String,
+
+        /// Treats the query as a regex.
+        #[arg(short, long)]
+        regex: bool,
+
+        /// Prints the number of the matching files and commits.
+        #[arg(short, long, conflicts_with = "files_with_matches")]
+        count: bool,
+
+        /// Prints the paths of the matching files.
+        #[arg(short = 'l', long)]
+        files_with_matches: bool,
+
+        /// Checks the candidates from the index against the file contents.
+        #[arg(long)]
+        verify: bool,
+    },
 }
 
 fn main() -> io::Result<()> {
@@ -58,13 +133,52 @@ fn main() -> io::Result<()> {
         env_logger::init();
     }
 
-    let index = build_index(&args);
+    let indexer = build_indexer(&args);
     if args.save_only {
         return Ok(());
     }
 
+    if let Some(Command::Rpc) = &args.command {
+        return run_rpc(indexer, &args.path, args.save_path.clone())
+            .map_err(io::Error::other);
+    }
+
+    let index: GitIndex = indexer.into();
+
+    if let Some(Command::Lsp) = &args.command {
+        return run_lsp(index, &args.path).map_err(io::Error::other);
+    }
+
+    if let Some(Command::Blame { file }) = &args.command {
+        print_blame(&index, &args.path, file).map_err(io::Error::other)?;
+        return Ok(());
+    }
+
     let mut searcher = Searcher::new(&index, &args.path);
 
+    if let Some(Command::Search {
+        query,
+        regex,
+        count,
+        files_with_matches,
+        verify,
+    }) = &args.command
+    {
+        let query = match regex {
+            true => Query::Regex(query.to_owned()),
+            false => Query::Plain(query.to_owned()),
+        };
+        let output = match (count, files_with_matches) {
+            (true, _) => OutputMode::Count,
+            (_, true) => OutputMode::FilesWithMatches,
+            _ => OutputMode::Matches,
+        };
+
+        run_search(&mut searcher, &query, output, *verify)
+            .map_err(io::Error::other)?;
+        return Ok(());
+    }
+
     if args.debug {
         handle_query(&mut searcher).unwrap();
         Ok(())
@@ -79,7 +193,7 @@ fn main() -> io::Result<()> {
     }
 }
 
-fn build_index(args: &Args) -> GitIndex {
+fn build_indexer(args: &Args) -> GitIndexer {
     let config = GitIndexerConfig {
         show_index_progress: true,
         main_branch_name: args
@@ -88,6 +202,13 @@ fn build_index(args: &Args) -> GitIndex {
             .unwrap_or("main")
             .to_owned(),
         ignore_utf8_error: true,
+        file_filter: FileFilterConfig {
+            include_patterns: args.include_patterns.clone(),
+            exclude_patterns: args.exclude_patterns.clone(),
+            max_file_size: args.max_file_size,
+            max_line_length: args.max_line_length,
+            skip_generated: args.skip_generated,
+        },
     };
 
     let repo = git2::Repository::open(Path::new(&args.path)).unwrap();
@@ -115,7 +236,5 @@ fn build_index(args: &Args) -> GitIndex {
         serialized.save(std::path::Path::new(&save_path)).unwrap();
     }
 
-    let index: GitIndex = indexer.into();
-
-    index
+    indexer
 }
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::index::git_indexer::FileId;
+use crep_indexer::search::git_searcher::MatchedQuery;
+use crep_indexer::search::git_searcher::SearchOption;
+use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+
+use crate::searcher::Searcher;
+
+// Number of lines shown above the first match when the preview opens.
+const LINES_ABOVE_MATCH: usize = 5;
+
+// The whole file at a commit, with every match of the query highlighted.
+pub struct Preview {
+    pub file_id: FileId,
+    pub file_path: String,
+    pub commit_index: CommitIndex,
+    pub lines: Vec<String>,
+    pub words_per_line: BTreeMap<usize, Vec<(String, usize)>>,
+
+    // Set when the file can't be read at the commit (e.g. it was deleted).
+    pub error: Option<String>,
+    pub scroll: u16,
+}
+
+impl Preview {
+    pub fn load(
+        searcher: &Searcher,
+        query: &MatchedQuery,
+        file_id: FileId,
+        file_path: &str,
+        commit_index: CommitIndex,
+    ) -> Self {
+        let (lines, error) =
+            match searcher.read_file_at_commit(commit_index, file_id) {
+                Ok(Some(content)) => {
+                    (content.lines().map(str::to_owned).collect(), None)
+                }
+                Ok(None) => (vec![], Some("Not a file at this commit".into())),
+                Err(e) => (vec![], Some(e.to_string())),
+            };
+
+        let words_per_line = SingleCommitSearchResult::new_with_option(
+            query,
+            commit_index,
+            &lines.iter().map(String::as_str).collect::<Vec<_>>(),
+            &SearchOption {
+                all_matches: true,
+                before_context: Some(0),
+                after_context: Some(0),
+                ..Default::default()
+            },
+        )
+        .ok()
+        .flatten()
+        .map(|result| result.words_per_line)
+        .unwrap_or_default();
+
+        let scroll = words_per_line
+            .keys()
+            .next()
+            .map_or(0, |line| line.saturating_sub(LINES_ABOVE_MATCH));
+
+        Self {
+            file_id,
+            file_path: file_path.to_owned(),
+            commit_index,
+            lines,
+            words_per_line,
+            error,
+            scroll: scroll as u16,
+        }
+    }
+
+    // 1-based line number of the first match.
+    pub fn first_match_line(&self) -> Option<usize> {
+        self.words_per_line.keys().next().map(|line| line + 1)
+    }
+
+    pub fn scroll_by(&mut self, delta: i32) {
+        let max_scroll = self.lines.len().saturating_sub(1) as i32;
+        self.scroll = (self.scroll as i32 + delta).clamp(0, max_scroll) as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crep_indexer::index::git_index::GitIndex;
+
+    use super::*;
+    use crate::test_util::commit_file;
+    use crate::test_util::index_repo;
+    use crate::test_util::init_repo;
+
+    fn query() -> MatchedQuery {
+        MatchedQuery::Words(vec!["needle".to_owned()])
+    }
+
+    // 20 lines with the needle at the line.
+    fn lines_with_needle_at(needle_line: usize) -> String {
+        (0..20)
+            .map(|line| match line == needle_line {
+                true => "needle\n".to_owned(),
+                false => format!("line {line}\n"),
+            })
+            .collect()
+    }
+
+    // far.txt and near.txt are added by the commits 0 and 1, empty.txt by the
+    // commit 2.
+    fn build_index() -> (tempfile::TempDir, GitIndex) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+        commit_file(repo_path, "far.txt", &lines_with_needle_at(12));
+        commit_file(repo_path, "near.txt", &lines_with_needle_at(2));
+        commit_file(repo_path, "empty.txt", "");
+
+        let index = index_repo(repo_path);
+        (dir, index)
+    }
+
+    fn load(searcher: &Searcher, path: &str, commit_index: usize) -> Preview {
+        let file_id = searcher.index().file_name_to_id[path];
+        Preview::load(searcher, &query(), file_id, path, commit_index)
+    }
+
+    #[test]
+    fn load_scrolls_above_the_first_match() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+
+        let preview = load(&searcher, "far.txt", 2);
+        assert!(preview.error.is_none());
+        assert_eq!(preview.lines.len(), 20);
+        assert_eq!(preview.first_match_line(), Some(13));
+        assert_eq!(preview.scroll, 7);
+
+        // Saturates at the top of the file.
+        let preview = load(&searcher, "near.txt", 2);
+        assert_eq!(preview.first_match_line(), Some(3));
+        assert_eq!(preview.scroll, 0);
+    }
+
+    #[test]
+    fn scroll_by_is_clamped() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+
+        let mut preview = load(&searcher, "far.txt", 2);
+        preview.scroll_by(100);
+        assert_eq!(preview.scroll, 19);
+        preview.scroll_by(-3);
+        assert_eq!(preview.scroll, 16);
+        preview.scroll_by(-100);
+        assert_eq!(preview.scroll, 0);
+    }
+
+    #[test]
+    fn empty_and_missing_files() {
+        let (dir, index) = build_index();
+        let searcher = Searcher::new(&index, dir.path().to_str().unwrap());
+
+        let mut preview = load(&searcher, "empty.txt", 2);
+        assert!(preview.error.is_none());
+        assert!(preview.lines.is_empty());
+        assert_eq!(preview.first_match_line(), None);
+        assert_eq!(preview.scroll, 0);
+        preview.scroll_by(5);
+        assert_eq!(preview.scroll, 0);
+
+        // near.txt is added after the commit 0.
+        let mut preview = load(&searcher, "near.txt", 0);
+        assert!(preview.error.is_some());
+        assert!(preview.lines.is_empty());
+        preview.scroll_by(5);
+        assert_eq!(preview.scroll, 0);
+    }
+}
@@ -1,7 +1,12 @@
 use color_eyre::owo_colors::OwoColorize;
+use crep_indexer::search::fuzzy::default_max_edits;
 use crep_indexer::search::git_searcher::Query;
+use crep_indexer::search::git_searcher::SearchOption;
 use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+use crep_indexer::search::summary::MatchCount;
+use crep_indexer::search::summary::OutputMode;
 
+use crate::searcher::Hit;
 use crate::searcher::Searcher;
 
 pub fn handle_query(searcher: &mut Searcher) -> anyhow::Result<()> {
@@ -13,59 +18,104 @@ pub fn handle_query(searcher: &mut Searcher) -> anyhow::Result<()> {
 
         let query = string_to_query(query);
 
-        let results = searcher.handle_query(&query).unwrap_or_else(|e| {
-            println!("Error: {:?}", e);
-            vec![]
-        });
-
-        let mut lines: Vec<String> = vec![];
-
-        for result in results {
-            lines.push(format!("File: {}", result.file_path));
-
-            match &result.last_match {
-                Some(last) => {
-                    let first_commit_info = searcher
-                        .get_commit_info(result.first_match.commit_id)?;
-                    let last_commit_info =
-                        searcher.get_commit_info(last.commit_id)?;
-
-                    lines.push(format!(
-                        "First seen at commit {} ... last seen at {}",
-                        first_commit_info.display_simple(),
-                        last_commit_info.display_simple(),
-                    ));
-
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        &result.first_match,
-                    ));
-                    lines.extend_from_slice(&[
-                        "".to_owned(),
-                        "---------------------------------------".to_owned(),
-                        "".to_owned(),
-                    ]);
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        last,
-                    ));
-                }
-                None => {
-                    let first_commit_info = searcher
-                        .get_commit_info(result.first_match.commit_id)?;
-                    lines.push(format!(
-                        "Seen at commit {}, never seen afterwards",
-                        first_commit_info.display_simple(),
-                    ));
-                    lines.extend_from_slice(&convert_search_result_to_lines(
-                        &result.first_match,
-                    ));
+        let results = searcher
+            .handle_query(&query, SearchOption::default())
+            .unwrap_or_else(|e| {
+                println!("Error: {:?}", e);
+                vec![]
+            });
+
+        print_search_results(searcher, results)?;
+    }
+}
+
+// Runs a single query and prints the results in the given output mode.
+pub fn run_search(
+    searcher: &mut Searcher,
+    query: &Query,
+    output: OutputMode,
+    verify: bool,
+) -> anyhow::Result<()> {
+    match output {
+        OutputMode::Matches => {
+            let results =
+                searcher.handle_query(query, SearchOption::default())?;
+            print_search_results(searcher, results)
+        }
+        OutputMode::FilesWithMatches => {
+            for file in searcher.files_with_matches(query, verify)? {
+                match file.at_head {
+                    true => println!("{}", file.file_path),
+                    false => println!("{} (deleted)", file.file_path),
                 }
-            };
+            }
 
-            lines.push("".to_owned());
+            Ok(())
         }
+        OutputMode::Count => {
+            let count =
+                MatchCount::new(&searcher.files_with_matches(query, verify)?);
+            println!(
+                "{} files ({} at HEAD), {} commits",
+                count.num_files, count.num_files_at_head, count.num_commits
+            );
+
+            Ok(())
+        }
+    }
+}
 
-        println!("{}", lines.join("\n"));
+fn print_search_results(
+    searcher: &Searcher,
+    hits: Vec<Hit>,
+) -> anyhow::Result<()> {
+    let mut lines: Vec<String> = vec![];
+
+    for Hit { result, .. } in hits {
+        lines.push(format!("File: {}", result.file_path));
+
+        match &result.last_match {
+            Some(last) => {
+                let first_commit_info =
+                    searcher.get_commit_info(result.first_match.commit_id)?;
+                let last_commit_info =
+                    searcher.get_commit_info(last.commit_id)?;
+
+                lines.push(format!(
+                    "First seen at commit {} ... last seen at {}",
+                    first_commit_info.display_simple(),
+                    last_commit_info.display_simple(),
+                ));
+
+                lines.extend_from_slice(&convert_search_result_to_lines(
+                    &result.first_match,
+                ));
+                lines.extend_from_slice(&[
+                    "".to_owned(),
+                    "---------------------------------------".to_owned(),
+                    "".to_owned(),
+                ]);
+                lines.extend_from_slice(&convert_search_result_to_lines(last));
+            }
+            None => {
+                let first_commit_info =
+                    searcher.get_commit_info(result.first_match.commit_id)?;
+                lines.push(format!(
+                    "Seen at commit {}, never seen afterwards",
+                    first_commit_info.display_simple(),
+                ));
+                lines.extend_from_slice(&convert_search_result_to_lines(
+                    &result.first_match,
+                ));
+            }
+        };
+
+        lines.push("".to_owned());
     }
+
+    println!("{}", lines.join("\n"));
+
+    Ok(())
 }
 
 fn convert_search_result_to_lines(
@@ -89,6 +139,11 @@ fn string_to_query(query: String) -> Query {
         Query::Plain(query.trim().chars().skip(2).collect())
     } else if query.starts_with("r:") {
         Query::Regex(query.trim().chars().skip(2).collect())
+    } else if query.starts_with("f:") {
+        let term = query.trim().chars().skip(2).collect::<String>();
+        let max_edits = default_max_edits(&term);
+
+        Query::Fuzzy { term, max_edits }
     } else {
         Query::Regex(query.trim().to_owned())
     }
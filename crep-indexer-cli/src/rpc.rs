@@ -0,0 +1,366 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crep_indexer::index::file_filter::not_indexed_message;
+use crep_indexer::index::git_index::GitIndex;
+use crep_indexer::index::git_index_serialization::GitIndexSerializationRef;
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::index::git_indexer::FileId;
+use crep_indexer::index::git_indexer::GitIndexer;
+use crep_indexer::search::cancellation::CancellationToken;
+use crep_indexer::search::git_searcher::SearchOption;
+use log::error;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json::json;
+
+use crate::app::QueryType;
+use crate::blame;
+use crate::jsonrpc::Handler;
+use crate::jsonrpc::MessageWriter;
+use crate::jsonrpc::RpcError;
+use crate::jsonrpc::parse_params;
+use crate::jsonrpc::serve;
+use crate::searcher::Hit;
+use crate::searcher::RepoPool;
+use crate::searcher::Searcher;
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    // `plain` (the default), `regex` or `fuzzy`.
+    query_type: Option<String>,
+    #[serde(default)]
+    whole_word: bool,
+    #[serde(default)]
+    multiline: bool,
+    #[serde(default)]
+    all_matches: bool,
+    before_context: Option<usize>,
+    after_context: Option<usize>,
+    max_results: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct FileParams {
+    path: String,
+    // Full or abbreviated SHA of an indexed commit. Defaults to the indexed
+    // HEAD.
+    commit: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PathParams {
+    path: String,
+}
+
+struct RpcServer {
+    repo_path: String,
+    save_path: Option<String>,
+
+    indexer: Mutex<GitIndexer>,
+    // Replaced by `reindex`. The running requests keep the snapshot they
+    // started with.
+    index: RwLock<Arc<GitIndex>>,
+    // Opened once instead of per request, and reopened by `reindex`.
+    repo_pool: RwLock<RepoPool>,
+}
+
+pub fn run_rpc(
+    indexer: GitIndexer,
+    repo_path: &str,
+    save_path: Option<String>,
+) -> anyhow::Result<()> {
+    serve(&RpcServer {
+        repo_path: repo_path.to_owned(),
+        save_path,
+        index: RwLock::new(Arc::new(indexer.clone().into())),
+        indexer: Mutex::new(indexer),
+        repo_pool: RwLock::new(RepoPool::new(repo_path)),
+    })
+}
+
+impl Handler for RpcServer {
+    fn handle<W: Write + Send>(
+        &self,
+        method: &str,
+        id: &Value,
+        params: Value,
+        cancellation: &CancellationToken,
+        writer: &MessageWriter<W>,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "search" => self.search(params, cancellation.clone()),
+            "searchStream" => {
+                self.search_stream(id, params, cancellation.clone(), writer)
+            }
+            "fileAtCommit" => self.file_at_commit(params),
+            "blame" => self.blame(params),
+            "history" => self.history(params),
+            "reindex" => self.reindex().map_err(RpcError::from),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
+
+impl RpcServer {
+    fn snapshot(&self) -> Arc<GitIndex> {
+        self.index.read().unwrap().clone()
+    }
+
+    fn searcher<'a>(&self, index: &'a GitIndex) -> Searcher<'a> {
+        let pool = self.repo_pool.read().unwrap().clone();
+        Searcher::with_pool(index, pool, &self.repo_path)
+    }
+
+    fn search(
+        &self,
+        params: Value,
+        cancellation: CancellationToken,
+    ) -> Result<Value, RpcError> {
+        let hits = Mutex::new(vec![]);
+        let truncated =
+            self.run_search(params, cancellation, |index, hit| {
+                hits.lock().unwrap().push(hit_to_json(index, &hit))
+            })?;
+
+        let hits = hits.into_inner().unwrap();
+        Ok(json!({
+            "truncated": truncated,
+            "hits": hits,
+        }))
+    }
+
+    // Sends every hit as the `searchResult` notification as soon as it is
+    // found. The response only has the summary.
+    fn search_stream<W: Write + Send>(
+        &self,
+        id: &Value,
+        params: Value,
+        cancellation: CancellationToken,
+        writer: &MessageWriter<W>,
+    ) -> Result<Value, RpcError> {
+        let num_hits = AtomicUsize::new(0);
+        let truncated =
+            self.run_search(params, cancellation, |index, hit| {
+                num_hits.fetch_add(1, Ordering::Relaxed);
+
+                let params =
+                    json!({ "id": id, "hit": hit_to_json(index, &hit) });
+                if let Err(e) = writer.notify("searchResult", params) {
+                    error!("Unable to send the search result: {e}");
+                }
+            })?;
+
+        let num_hits = num_hits.into_inner();
+        Ok(json!({
+            "truncated": truncated,
+            "num_hits": num_hits,
+        }))
+    }
+
+    // Returns true if some hits were left out.
+    fn run_search(
+        &self,
+        params: Value,
+        cancellation: CancellationToken,
+        on_hit: impl Fn(&GitIndex, Hit) + Sync + Send,
+    ) -> Result<bool, RpcError> {
+        let params: SearchParams = parse_params(params)?;
+        let query_type = parse_query_type(params.query_type.as_deref())?;
+        let max_results = params.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+        let index = self.snapshot();
+        let truncated = self.searcher(&index).stream_query(
+            &query_type.to_query(&params.query),
+            SearchOption {
+                whole_word: params.whole_word,
+                multiline: params.multiline,
+                all_matches: params.all_matches,
+                before_context: params.before_context,
+                after_context: params.after_context,
+                cancellation: Some(cancellation),
+                ..Default::default()
+            },
+            max_results,
+            |hit| on_hit(&index, hit),
+        )?;
+
+        Ok(truncated)
+    }
+
+    fn file_at_commit(&self, params: Value) -> Result<Value, RpcError> {
+        let params: FileParams = parse_params(params)?;
+
+        let index = self.snapshot();
+        let file_id = find_file(&index, &params.path)?;
+        let commit_index = match &params.commit {
+            Some(commit) => find_commit(&index, commit)?,
+            None => find_commit(&index, "HEAD")?,
+        };
+
+        let content = self
+            .searcher(&index)
+            .read_file_at_commit(commit_index, file_id)?
+            .ok_or_else(|| {
+                RpcError::invalid_params(format!(
+                    "{} is not a file at the commit",
+                    params.path
+                ))
+            })?;
+
+        Ok(json!({
+            "path": params.path,
+            "commit": commit_sha(&index, commit_index),
+            "content": content,
+        }))
+    }
+
+    // Blame of the file at the indexed HEAD.
+    fn blame(&self, params: Value) -> Result<Value, RpcError> {
+        let params: PathParams = parse_params(params)?;
+
+        let index = self.snapshot();
+        let blame::Blame { lines, ranges } =
+            blame::blame(&index, &self.repo_path, &params.path)?;
+
+        Ok(json!({
+            "path": params.path,
+            "lines": lines,
+            "ranges": ranges,
+        }))
+    }
+
+    // Every commit that modified the file, the newest first.
+    fn history(&self, params: Value) -> Result<Value, RpcError> {
+        let params: PathParams = parse_params(params)?;
+
+        let index = self.snapshot();
+        let file_id = find_file(&index, &params.path)?;
+        let searcher = self.searcher(&index);
+
+        let mut commits = vec![];
+        if let Some(document) = index.file_id_to_document.get(&file_id) {
+            for commit_index in document.doc_modified_commits.iter().rev() {
+                let info = searcher.get_commit_info(commit_index as usize)?;
+                commits.push(json!({
+                    "commit": info.commit_id,
+                    "author": info.author,
+                    "time": info.commit_time.timestamp(),
+                    "summary": info.summary,
+                }));
+            }
+        }
+
+        Ok(json!({
+            "path": params.path,
+            "commits": commits,
+        }))
+    }
+
+    // Indexes the commits added since the last index and swaps the snapshot.
+    fn reindex(&self) -> anyhow::Result<Value> {
+        let mut indexer = self.indexer.lock().unwrap();
+        let prev_num_commits = indexer.commit_index_to_commit_id.len();
+
+        let mut new_indexer = indexer.clone();
+        new_indexer.index_history(git2::Repository::open(&self.repo_path)?)?;
+
+        let num_commits = new_indexer.commit_index_to_commit_id.len();
+        if num_commits > prev_num_commits {
+            if let Some(save_path) = &self.save_path {
+                GitIndexSerializationRef::from(&new_indexer)
+                    .save(Path::new(save_path))?;
+            }
+
+            *self.index.write().unwrap() = Arc::new(new_indexer.clone().into());
+            *self.repo_pool.write().unwrap() = RepoPool::new(&self.repo_path);
+            *indexer = new_indexer;
+        }
+
+        let index = self.snapshot();
+        Ok(json!({
+            "head": index
+                .commit_index_to_commit_id
+                .last()
+                .map(hex::encode),
+            "num_commits": num_commits,
+            "num_new_commits": num_commits - prev_num_commits,
+        }))
+    }
+}
+
+// `plain` unless set.
+pub fn parse_query_type(
+    query_type: Option<&str>,
+) -> Result<QueryType, RpcError> {
+    match query_type {
+        Some(query_type) => QueryType::parse(query_type).ok_or_else(|| {
+            RpcError::invalid_params(format!("Unknown query type {query_type}"))
+        }),
+        None => Ok(QueryType::RawString),
+    }
+}
+
+// The search result with the SHAs of the commits it was found at.
+fn hit_to_json(index: &GitIndex, hit: &Hit) -> Value {
+    let mut value = json!(hit.result);
+    value["first_match_commit"] =
+        json!(commit_sha(index, hit.result.first_match.commit_id));
+    value["last_match_commit"] = json!(
+        hit.result
+            .last_match
+            .as_ref()
+            .map(|last| commit_sha(index, last.commit_id))
+    );
+
+    value
+}
+
+pub fn commit_sha(index: &GitIndex, commit_index: CommitIndex) -> String {
+    hex::encode(index.commit_index_to_commit_id[commit_index])
+}
+
+pub fn find_file(index: &GitIndex, path: &str) -> Result<FileId, RpcError> {
+    index.file_name_to_id.get(path).copied().ok_or_else(|| {
+        RpcError::invalid_params(not_indexed_message(
+            path,
+            index.skipped_files.get(path),
+        ))
+    })
+}
+
+// `HEAD` or the (unambiguous) prefix of the SHA.
+pub fn find_commit(
+    index: &GitIndex,
+    commit: &str,
+) -> Result<CommitIndex, RpcError> {
+    let num_commits = index.commit_index_to_commit_id.len();
+    if commit == "HEAD" && num_commits > 0 {
+        return Ok(num_commits - 1);
+    }
+
+    let prefix = commit.to_ascii_lowercase();
+    let mut found = index
+        .commit_index_to_commit_id
+        .iter()
+        .enumerate()
+        .filter(|(_, commit_id)| hex::encode(commit_id).starts_with(&prefix))
+        .map(|(commit_index, _)| commit_index);
+
+    match (found.next(), found.next()) {
+        (Some(commit_index), None) if !prefix.is_empty() => Ok(commit_index),
+        (Some(_), Some(_)) if !prefix.is_empty() => {
+            Err(RpcError::invalid_params(format!("{commit} is ambiguous")))
+        }
+        _ => Err(RpcError::invalid_params(format!("{commit} is not indexed"))),
+    }
+}
+
+// Number of the files returned when the request does not set `max_results`.
+const DEFAULT_MAX_RESULTS: usize = 100;
@@ -2,21 +2,30 @@ use std::fmt;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::time::Instant;
 
 use chrono::DateTime;
 use chrono::Utc;
+use crep_indexer::index::encoding::decode_to_string;
 use crep_indexer::index::git_index::GitIndex;
 use crep_indexer::index::git_indexer::CommitIndex;
 use crep_indexer::index::git_indexer::FileId;
 use crep_indexer::search::git_searcher::GitSearcher;
+use crep_indexer::search::git_searcher::MatchedQuery;
 use crep_indexer::search::git_searcher::Query;
 use crep_indexer::search::git_searcher::SearchOption;
 use crep_indexer::search::result::search_result::RepoReader;
 use crep_indexer::search::result::search_result::SearchResult;
+use crep_indexer::search::summary::FileWithMatches;
+use crep_indexer::search::summary::collect_files_with_matches;
+use git2::DiffFormat;
+use git2::DiffOptions;
 use git2::Oid;
 use git2::Repository;
 use log::debug;
+use log::error;
 use log::info;
 use rayon::prelude::*;
 
@@ -24,16 +33,30 @@ pub struct Searcher<'a> {
     pool: RepoPool,
     index: &'a GitIndex,
     searcher: GitSearcher<'a>,
+    repo_path: String,
 }
 
-struct RepoPool {
+// The search result with the query that matched the file, so that the other
+// commits of the file can be highlighted the same way.
+#[derive(Debug)]
+pub struct Hit {
+    pub result: SearchResult,
+    pub query: MatchedQuery,
+}
+
+// One repository per rayon thread. Opening them is not cheap, so the
+// long-running servers keep the pool and hand a clone to each searcher.
+#[derive(Clone)]
+pub struct RepoPool {
     repos: Vec<Arc<Mutex<Repository>>>,
 }
 
 impl RepoPool {
-    fn new(num_threads: usize, path: &str) -> Self {
+    pub fn new(path: &str) -> Self {
+        assert!(rayon::current_num_threads() > 0);
+
         let mut repos = vec![];
-        for _ in 0..num_threads {
+        for _ in 0..rayon::current_num_threads() {
             repos.push(Arc::new(Mutex::new(
                 Repository::open(Path::new(path)).unwrap(),
             )));
@@ -45,70 +68,113 @@ impl RepoPool {
 
 impl<'a> Searcher<'a> {
     pub fn new(index: &'a GitIndex, path: &str) -> Self {
-        assert!(rayon::current_num_threads() > 0);
+        Self::with_pool(index, RepoPool::new(path), path)
+    }
 
+    pub fn with_pool(index: &'a GitIndex, pool: RepoPool, path: &str) -> Self {
         Self {
-            pool: RepoPool::new(rayon::current_num_threads(), path),
+            pool,
             index,
             searcher: GitSearcher::new(index.into()),
+            repo_path: path.to_owned(),
         }
     }
 
+    pub fn index(&self) -> &'a GitIndex {
+        self.index
+    }
+
+    pub fn repo_path(&self) -> &str {
+        &self.repo_path
+    }
+
+    // None if the path is not a file at the commit.
+    pub fn read_file_at_commit(
+        &self,
+        commit_index: CommitIndex,
+        file_id: FileId,
+    ) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .reader_for_current_thread()
+            .read_file_at_commit(commit_index, file_id)?
+            .map(|(_, content)| content))
+    }
+
+    // Returns whatever was found so far if the option's cancellation token is
+    // cancelled while searching.
     pub fn handle_query(
         &mut self,
         query: &Query,
-    ) -> anyhow::Result<Vec<SearchResult>> {
+        option: SearchOption,
+    ) -> anyhow::Result<Vec<Hit>> {
+        let hits = Mutex::new(vec![]);
+        self.stream_query(query, option, MAX_HITS, |hit| {
+            hits.lock().unwrap().push(hit)
+        })?;
+
+        Ok(hits.into_inner().unwrap())
+    }
+
+    // Calls `on_hit` as soon as each file is read, in no particular order.
+    // Stops after `max_hits`, or once the option's cancellation token is
+    // cancelled.
+    //
+    // Returns true if some hits were left out, i.e. more than `max_hits` files
+    // matched or the search was interrupted.
+    pub fn stream_query(
+        &mut self,
+        query: &Query,
+        option: SearchOption,
+        max_hits: usize,
+        on_hit: impl Fn(Hit) + Sync + Send,
+    ) -> anyhow::Result<bool> {
         let raw_result_start = Instant::now();
 
-        let raw_results = self.searcher.search(
-            query,
-            Some(SearchOption {
-                max_num_to_find: None,
-            }),
-        );
+        let outcome = self
+            .searcher
+            .search(query, Some(option.clone()))
+            .map_err(anyhow::Error::msg)?;
+        let raw_results = outcome.results;
 
         info!(
             "Raw result end: {}",
             Instant::now().duration_since(raw_result_start).as_millis()
         );
 
-        if raw_results.is_err() {
-            anyhow::bail!("{raw_results:?}")
-        }
-
-        let raw_results = raw_results.unwrap();
-        if raw_results.is_empty() {
-            return Ok(vec![]);
-        }
-
         let to_search_result_start = Instant::now();
-        let results = raw_results
+        let num_hits = AtomicUsize::new(0);
+        raw_results
             .par_iter()
             .map_init(
-                || ThreadSafeRepoReader {
-                    repo: self
-                        .pool
-                        .repos
-                        .get(rayon::current_thread_index().unwrap_or_default())
-                        .unwrap()
-                        .clone(),
-                    file_id_to_path: &self.index.file_id_to_path,
-                    commit_index_to_commit_id: &self
-                        .index
-                        .commit_index_to_commit_id,
-                },
+                || self.reader_for_current_thread(),
                 |reader, result| {
-                    debug!(
-                        "Checking {result:?} at {}",
-                        self.index.file_id_to_path[result.file_id as usize]
-                    );
+                    let file_path =
+                        &self.index.file_id_to_path[result.file_id as usize];
+                    debug!("Checking {result:?} at {file_path}");
 
-                    SearchResult::new(reader, result).unwrap()
+                    match SearchResult::new_with_option(reader, result, &option)
+                    {
+                        Ok(search_result) => {
+                            search_result.map(|search_result| Hit {
+                                result: search_result,
+                                query: result.query.clone(),
+                            })
+                        }
+                        Err(e) => {
+                            error!("Unable to read {file_path}: {e}");
+                            None
+                        }
+                    }
                 },
             )
-            .filter(|res| res.is_some())
-            .take_any(100)
-            .collect::<Vec<_>>();
+            .flatten()
+            // One more than asked, to tell whether there were more hits.
+            .take_any(max_hits.saturating_add(1))
+            .for_each(|hit| {
+                if num_hits.fetch_add(1, Ordering::Relaxed) < max_hits {
+                    on_hit(hit);
+                }
+            });
 
         info!(
             "Search result end: {}",
@@ -117,7 +183,59 @@ impl<'a> Searcher<'a> {
                 .as_millis()
         );
 
-        Ok(results.into_iter().map(|s| s.unwrap()).collect())
+        Ok(outcome.truncated
+            || num_hits.into_inner() > max_hits
+            || option.is_interrupted())
+    }
+
+    // Files that contain the query, answered from the index. `verify` checks
+    // every candidate commit against the file contents, which is exact but
+    // reads the blobs.
+    pub fn files_with_matches(
+        &mut self,
+        query: &Query,
+        verify: bool,
+    ) -> anyhow::Result<Vec<FileWithMatches>> {
+        let raw_results = self
+            .searcher
+            .search(query, None)
+            .map_err(anyhow::Error::msg)?
+            .results;
+
+        let raw_results = match verify {
+            true => raw_results
+                .par_iter()
+                .map_init(
+                    || self.reader_for_current_thread(),
+                    |reader, result| {
+                        SearchResult::verify(
+                            reader,
+                            result,
+                            &SearchOption::default(),
+                        )
+                    },
+                )
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect(),
+            false => raw_results,
+        };
+
+        Ok(collect_files_with_matches(&self.index.into(), &raw_results))
+    }
+
+    fn reader_for_current_thread(&self) -> ThreadSafeRepoReader<'a> {
+        ThreadSafeRepoReader {
+            repo: self
+                .pool
+                .repos
+                .get(rayon::current_thread_index().unwrap_or_default())
+                .unwrap()
+                .clone(),
+            file_id_to_path: &self.index.file_id_to_path,
+            commit_index_to_commit_id: &self.index.commit_index_to_commit_id,
+        }
     }
 
     pub fn get_commit_info(
@@ -137,14 +255,69 @@ impl<'a> Searcher<'a> {
                 .ok_or_else(|| anyhow::anyhow!("invalid commit timestamp"))?,
             is_commit_head: self.index.commit_index_to_commit_id.len() - 1
                 == commit_index,
+            author: commit.author().name().unwrap_or_default().to_owned(),
+            summary: commit.summary().unwrap_or_default().to_owned(),
         })
     }
+
+    // Patch of the file against the first parent of the commit.
+    pub fn diff_file_at_commit(
+        &self,
+        commit_index: CommitIndex,
+        file_id: FileId,
+    ) -> anyhow::Result<Vec<DiffLine>> {
+        let file_path = &self.index.file_id_to_path[file_id];
+        let commit_id = Oid::from_bytes(
+            &self.index.commit_index_to_commit_id[commit_index],
+        )?;
+
+        let repo = self.pool.repos.first().unwrap().lock().unwrap();
+        let commit = repo.find_commit(commit_id)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            // The root commit adds every file.
+            Err(_) => None,
+        };
+
+        let mut option = DiffOptions::new();
+        option.pathspec(file_path).disable_pathspec_match(true);
+
+        let diff = repo.diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(&mut option),
+        )?;
+
+        let mut lines = vec![];
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            let content = String::from_utf8_lossy(line.content());
+            // The file header spans multiple lines.
+            for content in content.lines() {
+                lines.push(DiffLine {
+                    origin: line.origin(),
+                    content: content.to_owned(),
+                });
+            }
+            true
+        })?;
+
+        Ok(lines)
+    }
+}
+
+// `origin` is `+`, `-` or ` ` for the content, `F` for the file header and
+// `H` for the hunk header.
+pub struct DiffLine {
+    pub origin: char,
+    pub content: String,
 }
 
 pub struct CommitInfo {
     pub commit_id: String,
     pub commit_time: DateTime<Utc>,
     pub is_commit_head: bool,
+    pub author: String,
+    pub summary: String,
 }
 
 impl CommitInfo {
@@ -197,10 +370,13 @@ impl<'i> RepoReader for ThreadSafeRepoReader<'i> {
         if let Some(blob) = object.as_blob() {
             Ok(Some((
                 file_path.to_owned(),
-                String::from_utf8_lossy(blob.content()).to_string(),
+                decode_to_string(blob.content()),
             )))
         } else {
             Ok(None)
         }
     }
 }
+
+// Number of the files shown for a query.
+const MAX_HITS: usize = 100;
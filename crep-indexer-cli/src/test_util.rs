@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use crep_indexer::index::git_index::GitIndex;
+use crep_indexer::index::git_indexer::GitIndexer;
+use crep_indexer::index::git_indexer::GitIndexerConfig;
+
+pub fn run(cwd: &Path, args: &[&str]) {
+    std::process::Command::new(args[0])
+        .args(&args[1..])
+        .current_dir(cwd)
+        .output()
+        .expect("spawn ok");
+}
+
+pub fn init_repo(repo_path: &Path) {
+    run(repo_path, &["git", "init"]);
+    run(
+        repo_path,
+        &["git", "config", "--local", "user.email", "jaebum@test.com"],
+    );
+    run(
+        repo_path,
+        &["git", "config", "--local", "user.name", "Jaebum"],
+    );
+}
+
+// Returns the id of the new commit.
+pub fn commit_file(repo_path: &Path, name: &str, content: &str) -> String {
+    let path = repo_path.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+    run(repo_path, &["git", "add", "."]);
+    run(repo_path, &["git", "commit", "-m", name]);
+
+    git2::Repository::open(repo_path)
+        .unwrap()
+        .head()
+        .unwrap()
+        .peel_to_commit()
+        .unwrap()
+        .id()
+        .to_string()
+}
+
+pub fn index_repo(repo_path: &Path) -> GitIndex {
+    let mut indexer = GitIndexer::new(GitIndexerConfig {
+        show_index_progress: false,
+        main_branch_name: "main".to_owned(),
+        ignore_utf8_error: false,
+        ..Default::default()
+    });
+    indexer
+        .index_history(git2::Repository::open(repo_path).unwrap())
+        .unwrap();
+
+    indexer.into()
+}
@@ -20,6 +20,18 @@ pub struct LineDeleteResult {
     pub start_and_end: (usize, usize),
 }
 
+// Lines of the current file that came from the same commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameRange {
+    // [line_start, line_end) in the current file.
+    pub line_start: usize,
+    pub line_end: usize,
+
+    pub commit_id: CommitIndex,
+    // Line number of `line_start` in the file at `commit_id`.
+    pub line_start_in_commit: usize,
+}
+
 impl FileDiffTracker {
     pub fn new(init_commit: CommitIndex, total_line: usize) -> Self {
         Self {
@@ -258,6 +270,55 @@ impl FileDiffTracker {
         line_delete_result
     }
 
+    // Returns the origin of every line of the current file. Adjacent chunks
+    // that continue the same commit's lines are merged.
+    pub fn blame(&self) -> Vec<BlameRange> {
+        let mut ranges: Vec<BlameRange> = vec![];
+
+        for (chunk_index, (commit_id, line_start_in_commit)) in
+            self.commit_indexes.iter().enumerate()
+        {
+            let line_start = self.get_chunk_start(chunk_index);
+            let line_end = self.commit_line_end[chunk_index];
+            if line_start >= line_end {
+                continue;
+            }
+
+            if let Some(last) = ranges.last_mut()
+                && last.commit_id == *commit_id
+                && last.line_start_in_commit + (last.line_end - last.line_start)
+                    == *line_start_in_commit
+            {
+                last.line_end = line_end;
+                continue;
+            }
+
+            ranges.push(BlameRange {
+                line_start,
+                line_end,
+                commit_id: *commit_id,
+                line_start_in_commit: *line_start_in_commit,
+            });
+        }
+
+        ranges
+    }
+
+    // (commit that introduced the line, line number in that commit)
+    pub fn blame_line(&self, line: usize) -> Option<(CommitIndex, usize)> {
+        let chunk_index = self.find_chunk_index_by_line_num(line);
+        if chunk_index >= self.commit_line_end.len() {
+            return None;
+        }
+
+        let (commit_id, line_start_in_commit) =
+            self.commit_indexes[chunk_index];
+        Some((
+            commit_id,
+            line_start_in_commit + line - self.get_chunk_start(chunk_index),
+        ))
+    }
+
     pub fn delete_all(&mut self) {
         self.commit_line_end.clear();
         self.commit_indexes.clear();
@@ -724,4 +785,42 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_blame() {
+        let tracker = FileDiffTracker {
+            commit_line_end: vec![5, 8, 14, 21],
+            commit_indexes: vec![(1, 0), (2, 5), (2, 8), (3, 10)],
+        };
+
+        assert_eq!(
+            tracker.blame(),
+            vec![
+                BlameRange {
+                    line_start: 0,
+                    line_end: 5,
+                    commit_id: 1,
+                    line_start_in_commit: 0,
+                },
+                // (2, 5) and (2, 8) are the continuous lines of commit 2.
+                BlameRange {
+                    line_start: 5,
+                    line_end: 14,
+                    commit_id: 2,
+                    line_start_in_commit: 5,
+                },
+                BlameRange {
+                    line_start: 14,
+                    line_end: 21,
+                    commit_id: 3,
+                    line_start_in_commit: 10,
+                },
+            ]
+        );
+
+        assert_eq!(tracker.blame_line(0), Some((1, 0)));
+        assert_eq!(tracker.blame_line(6), Some((2, 6)));
+        assert_eq!(tracker.blame_line(15), Some((3, 11)));
+        assert_eq!(tracker.blame_line(21), None);
+    }
 }
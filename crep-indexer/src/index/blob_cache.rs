@@ -0,0 +1,125 @@
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use lru::LruCache;
+use trigram_hash::trigram_hash::TrigramKey;
+use trigram_hash::trigram_hash::split_lines_to_tokens;
+
+use super::identifier::IdentifierTokens;
+use super::identifier::split_lines_to_identifier_tokens;
+
+// Max number of the blobs whose tokens are kept. The least recently used one
+// is evicted first.
+const MAX_CACHED_BLOBS: usize = 4096;
+
+// Larger blobs are tokenized every time; keeping their tokens costs more than
+// tokenizing them again.
+const MAX_CACHED_BLOB_LINES: usize = 10_000;
+
+// Tokens of the whole blob, with the line numbers starting at 0.
+#[derive(Debug, Default, PartialEq)]
+pub struct BlobTokens {
+    pub words: AHashMap<TrigramKey, Vec<usize>>,
+    pub identifiers: IdentifierTokens,
+}
+
+impl BlobTokens {
+    fn new(lines: &[String]) -> Self {
+        Self {
+            words: split_lines_to_tokens(lines, /*line_start_index=*/ 0),
+            identifiers: split_lines_to_identifier_tokens(
+                lines, /*line_start_index=*/ 0,
+            ),
+        }
+    }
+}
+
+// Tokens of the blobs seen so far, keyed by the blob id. The same blob shows up
+// again and again in the history (copied or vendored files, renames, reverts),
+// and its tokens never change.
+#[derive(Clone)]
+pub struct BlobTokenCache {
+    blob_id_to_tokens: LruCache<[u8; 20], Arc<BlobTokens>>,
+
+    pub num_hits: usize,
+    pub num_misses: usize,
+}
+
+impl Default for BlobTokenCache {
+    fn default() -> Self {
+        Self {
+            blob_id_to_tokens: LruCache::new(
+                NonZeroUsize::new(MAX_CACHED_BLOBS).unwrap(),
+            ),
+            num_hits: 0,
+            num_misses: 0,
+        }
+    }
+}
+
+impl BlobTokenCache {
+    pub fn get_or_tokenize(
+        &mut self,
+        blob_id: [u8; 20],
+        lines: &[String],
+    ) -> Arc<BlobTokens> {
+        if let Some(tokens) = self.blob_id_to_tokens.get(&blob_id) {
+            self.num_hits += 1;
+            return tokens.clone();
+        }
+
+        self.num_misses += 1;
+        let tokens = Arc::new(BlobTokens::new(lines));
+        if lines.len() > MAX_CACHED_BLOB_LINES {
+            return tokens;
+        }
+
+        self.blob_id_to_tokens.put(blob_id, tokens.clone());
+
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &str) -> Vec<String> {
+        content.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn reuse_tokens_of_known_blob() {
+        let mut cache = BlobTokenCache::default();
+
+        let first = cache.get_or_tokenize([1; 20], &lines("fn foo()\nbar"));
+        let second = cache.get_or_tokenize([1; 20], &lines("fn foo()\nbar"));
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(*first, BlobTokens::new(&lines("fn foo()\nbar")));
+        assert_eq!((cache.num_hits, cache.num_misses), (1, 1));
+    }
+
+    #[test]
+    fn evict_least_recently_used_blob() {
+        let mut cache = BlobTokenCache::default();
+
+        let blob_id = |i: usize| {
+            let mut blob_id = [0; 20];
+            blob_id[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            blob_id
+        };
+
+        for i in 0..MAX_CACHED_BLOBS {
+            cache.get_or_tokenize(blob_id(i), &lines("a"));
+        }
+        // Keeps the first blob.
+        cache.get_or_tokenize(blob_id(0), &lines("a"));
+        cache.get_or_tokenize(blob_id(MAX_CACHED_BLOBS), &lines("a"));
+
+        assert_eq!(cache.blob_id_to_tokens.len(), MAX_CACHED_BLOBS);
+        assert!(cache.blob_id_to_tokens.contains(&blob_id(0)));
+        assert!(!cache.blob_id_to_tokens.contains(&blob_id(1)));
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use aho_corasick::AhoCorasick;
 use anyhow::Result;
 
+#[derive(Clone)]
 pub struct Utf8FileChecker {
     marks: AhoCorasick,
     ext_to_magic_numbers: HashMap<&'static str, &'static [Signature]>,
@@ -3,11 +3,13 @@ use ahash::AHashSet;
 use fst::Set;
 use serde::Deserialize;
 use serde::Serialize;
+use std::hash::Hash;
 use trigram_hash::trigram_hash::TrigramKey;
 
 use roaring::RoaringBitmap;
 
 use super::git_indexer::CommitIndex;
+use super::identifier::IdentifierTokens;
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct WordKey {
@@ -32,16 +34,29 @@ pub struct Document {
     #[serde(with = "crate::util::serde::fst::fst_set_to_vec::option")]
     pub all_words: Option<Set<Vec<u8>>>,
 
+    // Identifier level tokens, tracked the same way as the trigrams.
+    pub tokens: TokenIndex,
+
     pub doc_modified_commits: RoaringBitmap,
 
     pub is_deleted: bool,
 }
 
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenIndex {
+    // Whole identifiers, e.g. `getUserName`.
+    pub identifiers: AHashMap<String, WordIndex>,
+
+    // Lowercased parts of the identifiers, e.g. `get`, `user` and `name`.
+    pub parts: AHashMap<String, WordIndex>,
+}
+
 impl Document {
     pub fn new() -> Self {
         Self {
             words: AHashMap::new(),
             all_words: None,
+            tokens: TokenIndex::default(),
             doc_modified_commits: RoaringBitmap::new(),
             is_deleted: false,
         }
@@ -50,20 +65,30 @@ impl Document {
     pub fn add_words(
         &mut self,
         commit_index: CommitIndex,
-        words: AHashMap<TrigramKey, Vec<usize>>,
+        words: &AHashMap<TrigramKey, Vec<usize>>,
     ) {
-        for (word, lines) in words {
-            let word_index = self.words.entry(word).or_default();
-
-            for line in lines {
-                word_index.word_history.insert(WordKey {
-                    commit_id: commit_index,
-                    line,
-                });
-            }
+        add_to_word_indexes(&mut self.words, commit_index, words);
+        self.doc_modified_commits.insert(commit_index as u32);
 
-            word_index.commit_inclutivity.insert(commit_index as u32);
-        }
+        // The document is reopened if it was deleted or turned binary before.
+        self.is_deleted = false;
+    }
+
+    pub fn add_tokens(
+        &mut self,
+        commit_index: CommitIndex,
+        tokens: &IdentifierTokens,
+    ) {
+        add_to_word_indexes(
+            &mut self.tokens.identifiers,
+            commit_index,
+            &tokens.identifiers,
+        );
+        add_to_word_indexes(
+            &mut self.tokens.parts,
+            commit_index,
+            &tokens.parts,
+        );
         self.doc_modified_commits.insert(commit_index as u32);
     }
 
@@ -72,28 +97,32 @@ impl Document {
         commit_index: CommitIndex,
         words: &[(TrigramKey, Vec<WordKey>)],
     ) {
-        for (word, word_keys) in words {
-            debug_assert!(!word_keys.is_empty());
-
-            let word_index = self.words.get_mut(word);
-            if let Some(word_index) = word_index {
-                for word_key in word_keys {
-                    word_index.word_history.remove(word_key);
-                }
-            }
-        }
-
-        let modified_words: AHashSet<TrigramKey> =
-            words.iter().map(|(word, _)| *word).collect();
-        for word in modified_words {
-            self.update_commit_inclutivity_after_removal(commit_index, word);
-        }
+        remove_from_word_indexes(&mut self.words, commit_index, words);
+        self.doc_modified_commits.insert(commit_index as u32);
+    }
 
+    pub fn remove_tokens(
+        &mut self,
+        commit_index: CommitIndex,
+        identifiers: &[(String, Vec<WordKey>)],
+        parts: &[(String, Vec<WordKey>)],
+    ) {
+        remove_from_word_indexes(
+            &mut self.tokens.identifiers,
+            commit_index,
+            identifiers,
+        );
+        remove_from_word_indexes(&mut self.tokens.parts, commit_index, parts);
         self.doc_modified_commits.insert(commit_index as u32);
     }
 
     pub fn remove_document(&mut self, commit_index: CommitIndex) {
-        for word_index in self.words.values_mut() {
+        for word_index in self
+            .words
+            .values_mut()
+            .chain(self.tokens.identifiers.values_mut())
+            .chain(self.tokens.parts.values_mut())
+        {
             if !word_index.word_history.is_empty() {
                 let last_enabled_commit = word_index.commit_inclutivity.max();
                 word_index.commit_inclutivity.insert_range(
@@ -109,39 +138,13 @@ impl Document {
         self.is_deleted = true
     }
 
-    fn update_commit_inclutivity_after_removal(
-        &mut self,
-        commit_index: CommitIndex,
-        word: TrigramKey,
-    ) {
-        let word_index = self.words.get_mut(&word);
-        if word_index.is_none() {
-            return;
-        }
-
-        let word_index = word_index.unwrap();
-        if word_index.word_history.is_empty() {
-            // Then commit_index - 1 is the last time that the document contained the word.
-            match word_index.commit_inclutivity.max() {
-                Some(last_enabled_bit) => {
-                    word_index.commit_inclutivity.insert_range(
-                        last_enabled_bit..((commit_index - 1) as u32),
-                    );
-                    word_index.commit_inclutivity.optimize();
-                }
-                None => {
-                    word_index
-                        .commit_inclutivity
-                        .insert((commit_index - 1) as u32);
-                }
-            }
-
-            word_index.commit_inclutivity.optimize();
-        }
-    }
-
     pub fn finalize(&mut self, commit_index: CommitIndex) {
-        for index in self.words.values_mut() {
+        for index in self
+            .words
+            .values_mut()
+            .chain(self.tokens.identifiers.values_mut())
+            .chain(self.tokens.parts.values_mut())
+        {
             if index.commit_inclutivity.contains(commit_index as u32) {
                 continue;
             }
@@ -169,6 +172,90 @@ impl Document {
     }
 }
 
+fn add_to_word_indexes<K: Eq + Hash + Clone>(
+    indexes: &mut AHashMap<K, WordIndex>,
+    commit_index: CommitIndex,
+    words: &AHashMap<K, Vec<usize>>,
+) {
+    for (word, lines) in words {
+        // Only clone the key when the word is new to the document.
+        match indexes.get_mut(word) {
+            Some(word_index) => {
+                add_to_word_index(word_index, commit_index, lines);
+            }
+            None => {
+                let mut word_index = WordIndex::default();
+                add_to_word_index(&mut word_index, commit_index, lines);
+                indexes.insert(word.clone(), word_index);
+            }
+        }
+    }
+}
+
+fn add_to_word_index(
+    word_index: &mut WordIndex,
+    commit_index: CommitIndex,
+    lines: &[usize],
+) {
+    for &line in lines {
+        word_index.word_history.insert(WordKey {
+            commit_id: commit_index,
+            line,
+        });
+    }
+
+    word_index.commit_inclutivity.insert(commit_index as u32);
+}
+
+fn remove_from_word_indexes<K: Eq + Hash>(
+    indexes: &mut AHashMap<K, WordIndex>,
+    commit_index: CommitIndex,
+    words: &[(K, Vec<WordKey>)],
+) {
+    for (word, word_keys) in words {
+        debug_assert!(!word_keys.is_empty());
+
+        let word_index = indexes.get_mut(word);
+        if let Some(word_index) = word_index {
+            for word_key in word_keys {
+                word_index.word_history.remove(word_key);
+            }
+        }
+    }
+
+    let modified_words: AHashSet<&K> =
+        words.iter().map(|(word, _)| word).collect();
+    for word in modified_words {
+        if let Some(word_index) = indexes.get_mut(word) {
+            update_commit_inclutivity_after_removal(word_index, commit_index);
+        }
+    }
+}
+
+fn update_commit_inclutivity_after_removal(
+    word_index: &mut WordIndex,
+    commit_index: CommitIndex,
+) {
+    if word_index.word_history.is_empty() {
+        // Then commit_index - 1 is the last time that the document contained the word.
+        match word_index.commit_inclutivity.max() {
+            Some(last_enabled_bit) => {
+                word_index.commit_inclutivity.insert_range(
+                    last_enabled_bit..((commit_index - 1) as u32),
+                );
+                word_index.commit_inclutivity.optimize();
+            }
+            None => {
+                word_index
+                    .commit_inclutivity
+                    .insert((commit_index - 1) as u32);
+            }
+        }
+
+        word_index.commit_inclutivity.optimize();
+    }
+}
+
 #[cfg(test)]
 impl PartialEq for Document {
     fn eq(&self, other: &Self) -> bool {
@@ -184,6 +271,10 @@ impl PartialEq for Document {
             return false;
         }
 
+        if self.tokens != other.tokens {
+            return false;
+        }
+
         match (&self.all_words, &other.all_words) {
             (Some(left), Some(right)) => {
                 left.stream().into_strs().unwrap()
@@ -210,7 +301,7 @@ mod document_test {
 
         let mut document = Document::new();
 
-        document.add_words(1, words);
+        document.add_words(1, &words);
         assert_eq!(
             document,
             Document {
@@ -250,7 +341,8 @@ mod document_test {
                 ]),
                 all_words: None,
                 doc_modified_commits: RoaringBitmap::from([1]),
-                is_deleted: false
+                is_deleted: false,
+                tokens: TokenIndex::default(),
             }
         );
     }
@@ -294,6 +386,7 @@ mod document_test {
                 ),
                 doc_modified_commits: RoaringBitmap::from_iter([1, 3, 5, 8]),
                 is_deleted: false,
+                tokens: TokenIndex::default(),
             };
 
         let encoded =
@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+use std::cmp::min;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+// Only the beginning of the file is checked for UTF-16 without the BOM and for
+// the binary content.
+const NUM_BYTES_TO_CHECK: usize = 1024 * 8;
+
+// A few bytes of binary data can have the zeros in the UTF-16 positions by
+// chance.
+const MIN_UTF16_CODE_UNITS: usize = 4;
+
+// Original encoding of the indexed text. Everything is transcoded to UTF-8
+// before it is tokenized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    // Anything that is not valid UTF-8 (e.g. Windows-1252) is read as
+    // ISO-8859-1, which maps every byte to a char.
+    Latin1,
+}
+
+// Returns None if the content looks binary.
+pub fn detect_encoding(content: &[u8]) -> Option<TextEncoding> {
+    if content.starts_with(UTF8_BOM) {
+        return Some(TextEncoding::Utf8Bom);
+    }
+    if content.starts_with(UTF16_LE_BOM) {
+        return Some(TextEncoding::Utf16Le);
+    }
+    if content.starts_with(UTF16_BE_BOM) {
+        return Some(TextEncoding::Utf16Be);
+    }
+
+    let head = &content[0..min(NUM_BYTES_TO_CHECK, content.len())];
+    if let Some(encoding) = detect_utf16_without_bom(head) {
+        return Some(encoding);
+    }
+
+    if is_binary(head) {
+        return None;
+    }
+
+    match std::str::from_utf8(content) {
+        Ok(_) => Some(TextEncoding::Utf8),
+        Err(_) => Some(TextEncoding::Latin1),
+    }
+}
+
+// Same as git: the content is binary if it has the NUL byte, or if more than
+// 1/128 of it is the control characters.
+fn is_binary(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return true;
+    }
+
+    let num_non_printable = head
+        .iter()
+        .filter(|b| {
+            (**b < 0x20 && !b"\t\n\r\x08\x0c\x1b".contains(b)) || **b == 0x7f
+        })
+        .count();
+
+    num_non_printable * 128 > head.len()
+}
+
+// The BOM is dropped. Invalid sequences are replaced with U+FFFD.
+pub fn decode(content: &[u8], encoding: TextEncoding) -> Cow<'_, str> {
+    match encoding {
+        TextEncoding::Utf8 => String::from_utf8_lossy(content),
+        TextEncoding::Utf8Bom => String::from_utf8_lossy(
+            content.strip_prefix(UTF8_BOM).unwrap_or(content),
+        ),
+        TextEncoding::Utf16Le => Cow::Owned(decode_utf16(
+            content.strip_prefix(UTF16_LE_BOM).unwrap_or(content),
+            u16::from_le_bytes,
+        )),
+        TextEncoding::Utf16Be => Cow::Owned(decode_utf16(
+            content.strip_prefix(UTF16_BE_BOM).unwrap_or(content),
+            u16::from_be_bytes,
+        )),
+        TextEncoding::Latin1 => {
+            Cow::Owned(content.iter().map(|b| *b as char).collect())
+        }
+    }
+}
+
+// Decodes the content the same way as the indexer does, so that the lines and
+// the columns of the matches line up. Binary content is decoded lossily.
+pub fn decode_to_string(content: &[u8]) -> String {
+    decode(
+        content,
+        detect_encoding(content).unwrap_or(TextEncoding::Utf8),
+    )
+    .into_owned()
+}
+
+fn decode_utf16(content: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+    char::decode_utf16(
+        content
+            .chunks_exact(2)
+            .map(|pair| to_u16([pair[0], pair[1]])),
+    )
+    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    .collect()
+}
+
+// Mostly ASCII text in UTF-16 has the zero byte in every other position. The
+// decoded text must also look like text, as NUL-padded binary data has the
+// same zeros.
+fn detect_utf16_without_bom(head: &[u8]) -> Option<TextEncoding> {
+    let num_pairs = head.len() / 2;
+    if num_pairs < MIN_UTF16_CODE_UNITS {
+        return None;
+    }
+
+    let zeros_at = |parity: usize| {
+        head.iter()
+            .skip(parity)
+            .step_by(2)
+            .take(num_pairs)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+
+    let (encoding, to_u16): (_, fn([u8; 2]) -> u16) =
+        if odd_zeros * 10 >= num_pairs * 9 && even_zeros == 0 {
+            (TextEncoding::Utf16Le, u16::from_le_bytes)
+        } else if even_zeros * 10 >= num_pairs * 9 && odd_zeros == 0 {
+            (TextEncoding::Utf16Be, u16::from_be_bytes)
+        } else {
+            return None;
+        };
+
+    let text = decode_utf16(&head[..num_pairs * 2], to_u16);
+    let mut num_chars = 0;
+    let mut num_non_printable = 0;
+    for c in text.chars() {
+        num_chars += 1;
+        if matches!(c, '\t' | '\r' | '\n') {
+            continue;
+        }
+        if c < ' ' {
+            return None;
+        }
+        if c.is_control() || c == char::REPLACEMENT_CHARACTER {
+            num_non_printable += 1;
+        }
+    }
+
+    match num_non_printable * 128 > num_chars {
+        true => None,
+        false => Some(encoding),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16_le(text: &str, bom: bool) -> Vec<u8> {
+        let mut bytes = match bom {
+            true => UTF16_LE_BOM.to_vec(),
+            false => vec![],
+        };
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    #[test]
+    fn detect_and_decode() {
+        let cases: Vec<(Vec<u8>, TextEncoding)> = vec![
+            ("héllo\n".as_bytes().to_vec(), TextEncoding::Utf8),
+            (
+                [UTF8_BOM, "héllo\n".as_bytes()].concat(),
+                TextEncoding::Utf8Bom,
+            ),
+            (utf16_le("héllo\n", true), TextEncoding::Utf16Le),
+            (utf16_le("héllo\n", false), TextEncoding::Utf16Le),
+            (
+                [
+                    UTF16_BE_BOM.to_vec(),
+                    "héllo\n"
+                        .encode_utf16()
+                        .flat_map(u16::to_be_bytes)
+                        .collect(),
+                ]
+                .concat(),
+                TextEncoding::Utf16Be,
+            ),
+            (b"h\xe9llo\n".to_vec(), TextEncoding::Latin1),
+        ];
+
+        for (content, encoding) in cases {
+            assert_eq!(detect_encoding(&content), Some(encoding));
+            assert_eq!(decode(&content, encoding), "héllo\n");
+            assert_eq!(decode_to_string(&content), "héllo\n");
+        }
+    }
+
+    #[test]
+    fn binary_content() {
+        assert_eq!(detect_encoding(b"\x89PNG\r\n\x1a\n\x00\x00\xff"), None);
+        // Without the NUL byte.
+        assert_eq!(detect_encoding(b"\x8b\x01\x02abc\x03\x04\xfa"), None);
+    }
+
+    #[test]
+    fn short_control_bytes_are_binary() {
+        assert_eq!(detect_encoding(b"\x00\x01\x02"), None);
+        assert_eq!(detect_encoding(b"\x00\x01\x00\x02\x00\x03"), None);
+        assert_eq!(detect_encoding(b"\x01\x02\x03"), None);
+    }
+
+    #[test]
+    fn nul_padded_binary_is_not_utf16() {
+        // The zeros are in the UTF-16 BE positions, but the code units are
+        // control characters.
+        let content = (0..64u8).flat_map(|i| [0, i % 0x20]).collect::<Vec<_>>();
+        assert_eq!(detect_encoding(&content), None);
+
+        // Same for the LE positions.
+        let content = (0..64u8)
+            .flat_map(|i| [i % 0x20 + 1, 0])
+            .collect::<Vec<_>>();
+        assert_eq!(detect_encoding(&content), None);
+
+        // Short UTF-16 text is still detected.
+        assert_eq!(
+            detect_encoding(&utf16_le("abcd", false)),
+            Some(TextEncoding::Utf16Le)
+        );
+    }
+}
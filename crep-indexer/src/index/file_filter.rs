@@ -0,0 +1,329 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Result;
+use ignore::gitignore::Gitignore;
+use ignore::gitignore::GitignoreBuilder;
+use serde::Deserialize;
+use serde::Serialize;
+
+// Rules deciding which files are indexed. Every file is indexed by default.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct FileFilterConfig {
+    // Gitignore style patterns. If set, only the matching files are indexed.
+    pub include_patterns: Vec<String>,
+    // Gitignore style patterns of the files that are never indexed.
+    pub exclude_patterns: Vec<String>,
+
+    // In bytes.
+    pub max_file_size: Option<u64>,
+    // In bytes. Files having any longer line are skipped.
+    pub max_line_length: Option<usize>,
+
+    // Skips the lockfiles, the generated code and the minified files.
+    pub skip_generated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkipReason {
+    Excluded,
+    NotIncluded,
+    TooLarge { size: u64 },
+    LineTooLong { length: usize },
+    Generated,
+    Minified,
+    // `binary` or `-diff` in .gitattributes.
+    Binary,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::Excluded => write!(f, "matches an exclude pattern"),
+            SkipReason::NotIncluded => {
+                write!(f, "does not match any include pattern")
+            }
+            SkipReason::TooLarge { size } => {
+                write!(f, "file is too large ({size} bytes)")
+            }
+            SkipReason::LineTooLong { length } => {
+                write!(f, "line is too long ({length} bytes)")
+            }
+            SkipReason::Generated => write!(f, "generated file"),
+            SkipReason::Minified => write!(f, "minified file"),
+            SkipReason::Binary => write!(f, "binary in .gitattributes"),
+        }
+    }
+}
+
+// Error message for the file that is not in the index.
+pub fn not_indexed_message(path: &str, reason: Option<&SkipReason>) -> String {
+    match reason {
+        Some(reason) => format!("{path} is not indexed: {reason}"),
+        None => format!("{path} is not indexed"),
+    }
+}
+
+#[derive(Clone)]
+pub struct FileFilter {
+    include: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+
+    max_file_size: Option<u64>,
+    max_line_length: Option<usize>,
+    skip_generated: bool,
+}
+
+// Names of the files that are always generated.
+static GENERATED_FILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "yarn.lock",
+];
+
+static GENERATED_FILE_SUFFIXES: &[&str] = &[
+    ".min.css",
+    ".min.js",
+    ".pb.cc",
+    ".pb.go",
+    ".pb.h",
+    "_pb2.py",
+    "_pb2_grpc.py",
+];
+
+// Generators put one of these at the top of the file.
+static GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "Code generated by",
+    "DO NOT EDIT",
+    "Generated by the protocol buffer compiler",
+];
+
+// Number of the lines at the top of the file searched for the markers.
+const NUM_MARKER_LINES: usize = 5;
+
+// Minified files are mostly a few very long lines.
+const MINIFIED_MIN_SIZE: usize = 1024;
+const MINIFIED_AVG_LINE_LENGTH: usize = 200;
+
+impl FileFilter {
+    pub fn new(config: &FileFilterConfig) -> Result<Self> {
+        Ok(Self {
+            include: build_matcher(&config.include_patterns)?,
+            exclude: build_matcher(&config.exclude_patterns)?,
+            max_file_size: config.max_file_size,
+            max_line_length: config.max_line_length,
+            skip_generated: config.skip_generated,
+        })
+    }
+
+    // Checks the rules that only need the path of the file.
+    pub fn check_path(&self, path: &str) -> Option<SkipReason> {
+        if let Some(exclude) = &self.exclude
+            && is_match(exclude, path)
+        {
+            return Some(SkipReason::Excluded);
+        }
+
+        if let Some(include) = &self.include
+            && !is_match(include, path)
+        {
+            return Some(SkipReason::NotIncluded);
+        }
+
+        if self.skip_generated && is_generated_file_name(path) {
+            return Some(SkipReason::Generated);
+        }
+
+        None
+    }
+
+    pub fn check_size(&self, size: u64) -> Option<SkipReason> {
+        match self.max_file_size {
+            Some(max_file_size) if size > max_file_size => {
+                Some(SkipReason::TooLarge { size })
+            }
+            _ => None,
+        }
+    }
+
+    // True if `check_content` has anything to check.
+    pub fn needs_content(&self) -> bool {
+        self.max_line_length.is_some() || self.skip_generated
+    }
+
+    pub fn check_content(&self, content: &[u8]) -> Option<SkipReason> {
+        if let Some(max_line_length) = self.max_line_length
+            && let Some(length) = content
+                .split(|c| *c == b'\n')
+                .map(<[u8]>::len)
+                .find(|length| *length > max_line_length)
+        {
+            return Some(SkipReason::LineTooLong { length });
+        }
+
+        if !self.skip_generated {
+            return None;
+        }
+
+        let has_marker = content
+            .split(|c| *c == b'\n')
+            .take(NUM_MARKER_LINES)
+            .map(String::from_utf8_lossy)
+            .any(|line| {
+                GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+            });
+        if has_marker {
+            return Some(SkipReason::Generated);
+        }
+
+        let num_lines = content.split(|c| *c == b'\n').count();
+        if content.len() >= MINIFIED_MIN_SIZE
+            && content.len() / num_lines > MINIFIED_AVG_LINE_LENGTH
+        {
+            return Some(SkipReason::Minified);
+        }
+
+        None
+    }
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self::new(&FileFilterConfig::default()).unwrap()
+    }
+}
+
+fn build_matcher(patterns: &[String]) -> Result<Option<Gitignore>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+
+    Ok(Some(builder.build()?))
+}
+
+// The pattern matches the file or any of its directories, as in .gitignore.
+fn is_match(matcher: &Gitignore, path: &str) -> bool {
+    matcher
+        .matched_path_or_any_parents(path, /*is_dir=*/ false)
+        .is_ignore()
+}
+
+fn is_generated_file_name(path: &str) -> bool {
+    let file_name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path);
+
+    GENERATED_FILE_NAMES.contains(&file_name)
+        || GENERATED_FILE_SUFFIXES
+            .iter()
+            .any(|suffix| file_name.ends_with(suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: FileFilterConfig) -> FileFilter {
+        FileFilter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn include_and_exclude_patterns() {
+        let filter = filter(FileFilterConfig {
+            include_patterns: vec!["src/".to_owned(), "*.md".to_owned()],
+            exclude_patterns: vec![
+                "vendor/".to_owned(),
+                "*.json".to_owned(),
+                "!keep.json".to_owned(),
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(filter.check_path("src/main.rs"), None);
+        assert_eq!(filter.check_path("docs/README.md"), None);
+        assert_eq!(filter.check_path("src/keep.json"), None);
+        assert_eq!(
+            filter.check_path("src/vendor/lib.rs"),
+            Some(SkipReason::Excluded)
+        );
+        assert_eq!(
+            filter.check_path("src/data.json"),
+            Some(SkipReason::Excluded)
+        );
+        assert_eq!(
+            filter.check_path("build.rs"),
+            Some(SkipReason::NotIncluded)
+        );
+    }
+
+    #[test]
+    fn size_and_line_length() {
+        let filter = filter(FileFilterConfig {
+            max_file_size: Some(10),
+            max_line_length: Some(3),
+            ..Default::default()
+        });
+
+        assert_eq!(filter.check_size(10), None);
+        assert_eq!(
+            filter.check_size(11),
+            Some(SkipReason::TooLarge { size: 11 })
+        );
+        assert_eq!(filter.check_content(b"abc\nde\n"), None);
+        assert_eq!(
+            filter.check_content(b"abc\ndefg\n"),
+            Some(SkipReason::LineTooLong { length: 4 })
+        );
+    }
+
+    #[test]
+    fn generated_files() {
+        let filter = filter(FileFilterConfig {
+            skip_generated: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            filter.check_path("Cargo.lock"),
+            Some(SkipReason::Generated)
+        );
+        assert_eq!(
+            filter.check_path("web/app.min.js"),
+            Some(SkipReason::Generated)
+        );
+        assert_eq!(filter.check_path("web/app.js"), None);
+
+        assert_eq!(
+            filter
+                .check_content(b"// Code generated by protoc. DO NOT EDIT.\n"),
+            Some(SkipReason::Generated)
+        );
+        assert_eq!(
+            filter.check_content("x".repeat(2048).as_bytes()),
+            Some(SkipReason::Minified)
+        );
+        assert_eq!(filter.check_content(b"fn main() {}\n"), None);
+    }
+
+    #[test]
+    fn index_everything_by_default() {
+        let filter = FileFilter::default();
+
+        assert!(!filter.needs_content());
+        assert_eq!(filter.check_path("Cargo.lock"), None);
+        assert_eq!(filter.check_size(u64::MAX), None);
+    }
+}
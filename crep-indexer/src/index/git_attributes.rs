@@ -0,0 +1,259 @@
+use anyhow::Result;
+use git2::ObjectType;
+use git2::Repository;
+use git2::Tree;
+use git2::TreeWalkMode;
+use git2::TreeWalkResult;
+use globset::GlobBuilder;
+use globset::GlobMatcher;
+use roaring::RoaringBitmap;
+use serde::Deserialize;
+use serde::Serialize;
+
+const GIT_ATTRIBUTES_FILE_NAME: &str = ".gitattributes";
+
+// Attributes of a file that matter to the indexer.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FileAttributes {
+    // `binary` or `-diff`.
+    pub binary: bool,
+    // `linguist-vendored`.
+    pub vendored: bool,
+    // `linguist-generated`.
+    pub generated: bool,
+}
+
+// Files that are indexed, but flagged by .gitattributes at HEAD. Searches rank
+// them after the other files.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileKinds {
+    pub vendored: RoaringBitmap,
+    pub generated: RoaringBitmap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Attribute {
+    Diff,
+    LinguistVendored,
+    LinguistGenerated,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    // Directory of the .gitattributes, e.g. `src/` (empty at the root).
+    dir: String,
+    matcher: GlobMatcher,
+    // Patterns without a slash match the file name at any depth.
+    match_file_name: bool,
+    // None is `!attr` (unspecified).
+    attributes: Vec<(Attribute, Option<bool>)>,
+}
+
+// Rules of every .gitattributes in a tree.
+#[derive(Debug, Default, Clone)]
+pub struct GitAttributes {
+    // Shallower files first; later rules override the earlier ones as in git.
+    rules: Vec<Rule>,
+}
+
+impl GitAttributes {
+    pub fn from_tree(repo: &Repository, tree: &Tree) -> Result<Self> {
+        let mut files = vec![];
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(ObjectType::Blob)
+                && entry.name() == Some(GIT_ATTRIBUTES_FILE_NAME)
+            {
+                files.push((root.to_owned(), entry.id()));
+            }
+
+            TreeWalkResult::Ok
+        })?;
+
+        files.sort_by_key(|(root, _)| root.matches('/').count());
+
+        let mut rules = vec![];
+        for (dir, blob_id) in files {
+            let blob = repo.find_blob(blob_id)?;
+            rules.extend(parse_rules(
+                &dir,
+                &String::from_utf8_lossy(blob.content()),
+            ));
+        }
+
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn attributes(&self, path: &str) -> FileAttributes {
+        let mut diff = None;
+        let mut vendored = None;
+        let mut generated = None;
+
+        for rule in &self.rules {
+            let Some(relative_path) = path.strip_prefix(&rule.dir) else {
+                continue;
+            };
+
+            let target = match rule.match_file_name {
+                true => relative_path.rsplit('/').next().unwrap_or_default(),
+                false => relative_path,
+            };
+            if !rule.matcher.is_match(target) {
+                continue;
+            }
+
+            for (attribute, value) in &rule.attributes {
+                match attribute {
+                    Attribute::Diff => diff = *value,
+                    Attribute::LinguistVendored => vendored = *value,
+                    Attribute::LinguistGenerated => generated = *value,
+                }
+            }
+        }
+
+        FileAttributes {
+            binary: diff == Some(false),
+            vendored: vendored.unwrap_or(false),
+            generated: generated.unwrap_or(false),
+        }
+    }
+}
+
+pub fn is_git_attributes_file(path: &str) -> bool {
+    path.rsplit('/').next() == Some(GIT_ATTRIBUTES_FILE_NAME)
+}
+
+fn parse_rules(dir: &str, content: &str) -> Vec<Rule> {
+    content
+        .lines()
+        .filter_map(|line| parse_rule(dir, line))
+        .collect()
+}
+
+fn parse_rule(dir: &str, line: &str) -> Option<Rule> {
+    let line = line.trim();
+    // Macro definitions are ignored; `binary` is the only builtin one.
+    if line.is_empty() || line.starts_with('#') || line.starts_with("[attr]") {
+        return None;
+    }
+
+    let mut tokens = line.split_whitespace();
+    let pattern = tokens.next()?;
+
+    // Directory patterns never match the files in .gitattributes.
+    if pattern.ends_with('/') {
+        return None;
+    }
+
+    let attributes = tokens.filter_map(parse_attribute).collect::<Vec<_>>();
+    if attributes.is_empty() {
+        return None;
+    }
+
+    let match_file_name = !pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    let matcher = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .ok()?
+        .compile_matcher();
+
+    Some(Rule {
+        dir: dir.to_owned(),
+        matcher,
+        match_file_name,
+        attributes,
+    })
+}
+
+fn parse_attribute(token: &str) -> Option<(Attribute, Option<bool>)> {
+    if token == "binary" {
+        return Some((Attribute::Diff, Some(false)));
+    }
+
+    let (name, value) = if let Some(name) = token.strip_prefix('-') {
+        (name, Some(false))
+    } else if let Some(name) = token.strip_prefix('!') {
+        (name, None)
+    } else if let Some((name, value)) = token.split_once('=') {
+        // `diff=<driver>` is still a text diff.
+        (name, Some(name == "diff" || value != "false"))
+    } else {
+        (token, Some(true))
+    };
+
+    let attribute = match name {
+        "diff" => Attribute::Diff,
+        "linguist-vendored" => Attribute::LinguistVendored,
+        "linguist-generated" => Attribute::LinguistGenerated,
+        _ => return None,
+    };
+
+    Some((attribute, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(files: &[(&str, &str)]) -> GitAttributes {
+        GitAttributes {
+            rules: files
+                .iter()
+                .flat_map(|(dir, content)| parse_rules(dir, content))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn binary_and_diff() {
+        let attributes = attributes(&[(
+            "",
+            "# Comment\n*.png binary\n*.dat -diff\n*.rs diff=rust\n",
+        )]);
+
+        assert!(attributes.attributes("img/logo.png").binary);
+        assert!(attributes.attributes("data.dat").binary);
+        assert!(!attributes.attributes("src/main.rs").binary);
+        assert_eq!(
+            attributes.attributes("README.md"),
+            FileAttributes::default()
+        );
+    }
+
+    #[test]
+    fn linguist_attributes() {
+        let attributes = attributes(&[(
+            "",
+            "vendor/** linguist-vendored\n\
+             vendor/ours/** -linguist-vendored\n\
+             *.pb.go linguist-generated=true\n\
+             api.pb.go linguist-generated=false\n",
+        )]);
+
+        assert!(attributes.attributes("vendor/lib/a.c").vendored);
+        assert!(!attributes.attributes("vendor/ours/a.c").vendored);
+        assert!(!attributes.attributes("src/vendor/a.c").vendored);
+        assert!(attributes.attributes("proto/foo.pb.go").generated);
+        assert!(!attributes.attributes("proto/api.pb.go").generated);
+    }
+
+    #[test]
+    fn nested_files_override_parent() {
+        let attributes = attributes(&[
+            ("", "*.js linguist-generated\n"),
+            (
+                "web/",
+                "*.js !linguist-generated\n/app.js linguist-vendored\n",
+            ),
+        ]);
+
+        assert!(attributes.attributes("gen.js").generated);
+        assert!(!attributes.attributes("web/src/main.js").generated);
+        assert!(attributes.attributes("web/app.js").vendored);
+        assert!(!attributes.attributes("web/src/app.js").vendored);
+    }
+}
@@ -1,37 +1,66 @@
 use ahash::AHashMap;
+use fst::Map;
 use fst::Set;
 use roaring::RoaringBitmap;
 use trigram_hash::trigram_hash::TrigramKey;
 
+use crate::git::diff::FileDiffTracker;
 use crate::index::git_indexer::CommitIndex;
 
 use super::document::Document;
+use super::file_filter::SkipReason;
+use super::git_attributes::FileKinds;
 use super::git_indexer::FileId;
 use super::git_indexer::GitIndexer;
+use super::identifier::build_identifier_map;
+use super::identifier::build_path_set;
 
 #[derive(Debug)]
 pub struct GitIndex {
     pub commit_index_to_commit_id: Vec<[u8; 20]>,
     pub commit_id_to_commit_index: AHashMap<[u8; 20], CommitIndex>,
 
+    pub file_name_to_id: AHashMap<String, FileId>,
     pub file_id_to_path: Vec<String>,
+    pub file_id_to_diff_tracker: AHashMap<FileId, FileDiffTracker>,
 
     pub file_id_to_document: AHashMap<FileId, Document>,
     pub word_to_file_id_ever_contained: AHashMap<TrigramKey, RoaringBitmap>,
+    pub identifier_part_to_file_id_ever_contained:
+        AHashMap<String, RoaringBitmap>,
 
     pub all_words: Set<Vec<u8>>,
+
+    // Identifiers at HEAD mapped to their number of occurrences.
+    pub identifiers: Map<Vec<u8>>,
+    // Paths of the files at HEAD.
+    pub all_paths: Set<Vec<u8>>,
+
+    // Files that are not indexed due to the file filter rules.
+    pub skipped_files: AHashMap<String, SkipReason>,
+    pub file_kinds: FileKinds,
 }
 
 pub struct GitIndexRef<'a> {
     pub commit_index_to_commit_id: &'a Vec<[u8; 20]>,
     pub commit_id_to_commit_index: &'a AHashMap<[u8; 20], CommitIndex>,
 
+    pub file_name_to_id: &'a AHashMap<String, FileId>,
     pub file_id_to_path: &'a Vec<String>,
+    pub file_id_to_diff_tracker: &'a AHashMap<FileId, FileDiffTracker>,
 
     pub file_id_to_document: &'a AHashMap<FileId, Document>,
     pub word_to_file_id_ever_contained: &'a AHashMap<TrigramKey, RoaringBitmap>,
+    pub identifier_part_to_file_id_ever_contained:
+        &'a AHashMap<String, RoaringBitmap>,
 
     pub all_words: &'a Set<Vec<u8>>,
+
+    pub identifiers: &'a Map<Vec<u8>>,
+    pub all_paths: &'a Set<Vec<u8>>,
+
+    pub skipped_files: &'a AHashMap<String, SkipReason>,
+    pub file_kinds: &'a FileKinds,
 }
 
 impl<'a> From<&'a GitIndex> for GitIndexRef<'a> {
@@ -39,10 +68,18 @@ impl<'a> From<&'a GitIndex> for GitIndexRef<'a> {
         Self {
             commit_index_to_commit_id: &v.commit_index_to_commit_id,
             commit_id_to_commit_index: &v.commit_id_to_commit_index,
+            file_name_to_id: &v.file_name_to_id,
             file_id_to_path: &v.file_id_to_path,
+            file_id_to_diff_tracker: &v.file_id_to_diff_tracker,
             file_id_to_document: &v.file_id_to_document,
             word_to_file_id_ever_contained: &v.word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: &v
+                .identifier_part_to_file_id_ever_contained,
             all_words: &v.all_words,
+            identifiers: &v.identifiers,
+            all_paths: &v.all_paths,
+            skipped_files: &v.skipped_files,
+            file_kinds: &v.file_kinds,
         }
     }
 }
@@ -57,6 +94,11 @@ impl From<GitIndexer> for GitIndex {
         keys.sort();
 
         let all_words = Set::from_iter(keys).unwrap();
+        let identifiers = build_identifier_map(&indexer.identifier_counts);
+        let all_paths = build_path_set(
+            &indexer.file_id_to_path,
+            &indexer.file_id_to_document,
+        );
 
         /*
                 let not_deleted_files_head = RoaringBitmap::from_iter(
@@ -73,11 +115,19 @@ impl From<GitIndexer> for GitIndex {
         Self {
             commit_index_to_commit_id: indexer.commit_index_to_commit_id,
             commit_id_to_commit_index: indexer.commit_id_to_commit_index,
+            file_name_to_id: indexer.file_name_to_id,
             file_id_to_path: indexer.file_id_to_path,
+            file_id_to_diff_tracker: indexer.file_id_to_diff_tracker,
             file_id_to_document: indexer.file_id_to_document,
             word_to_file_id_ever_contained: indexer
                 .word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: indexer
+                .identifier_part_to_file_id_ever_contained,
             all_words,
+            identifiers,
+            all_paths,
+            skipped_files: indexer.skipped_files,
+            file_kinds: indexer.file_kinds,
         }
     }
 }
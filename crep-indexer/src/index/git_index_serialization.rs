@@ -3,6 +3,7 @@ use std::io;
 use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::Read;
+use std::io::Write;
 use std::path::Path;
 
 use ahash::AHashMap;
@@ -18,9 +19,18 @@ use trigram_hash::trigram_hash::TrigramKey;
 
 use crate::git::diff::FileDiffTracker;
 use crate::index::document::Document;
+use crate::index::encoding::TextEncoding;
+use crate::index::file_filter::SkipReason;
+use crate::index::git_attributes::FileKinds;
 use crate::index::git_indexer::FileId;
 use crate::index::git_indexer::GitIndexer;
 
+// Written before the encoded index. bincode has no schema, so an index saved
+// with different structs fails to decode with an opaque error or decodes into
+// garbage. Bump the version whenever a serialized struct changes.
+const INDEX_MAGIC: &[u8; 8] = b"CREPIDX\0";
+const INDEX_FORMAT_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 pub struct GitIndexSerialization {
     pub commit_index_to_commit_id: Vec<[u8; 20]>,
@@ -30,8 +40,14 @@ pub struct GitIndexSerialization {
     pub file_id_to_diff_tracker: AHashMap<FileId, FileDiffTracker>,
 
     pub word_to_file_id_ever_contained: AHashMap<TrigramKey, RoaringBitmap>,
+    pub identifier_part_to_file_id_ever_contained:
+        AHashMap<String, RoaringBitmap>,
+    pub identifier_counts: AHashMap<String, u64>,
 
     pub ignored_non_utf8_file_path_set: AHashSet<String>,
+    pub skipped_files: AHashMap<String, SkipReason>,
+    pub file_kinds: FileKinds,
+    pub file_id_to_encoding: AHashMap<FileId, TextEncoding>,
 }
 
 // Borrowed view over a [`GitIndexer`] used purely for serialization.
@@ -44,8 +60,14 @@ pub struct GitIndexSerializationRef<'a> {
     pub file_id_to_diff_tracker: &'a AHashMap<FileId, FileDiffTracker>,
 
     pub word_to_file_id_ever_contained: &'a AHashMap<TrigramKey, RoaringBitmap>,
+    pub identifier_part_to_file_id_ever_contained:
+        &'a AHashMap<String, RoaringBitmap>,
+    pub identifier_counts: &'a AHashMap<String, u64>,
 
     pub ignored_non_utf8_file_path_set: &'a AHashSet<String>,
+    pub skipped_files: &'a AHashMap<String, SkipReason>,
+    pub file_kinds: &'a FileKinds,
+    pub file_id_to_encoding: &'a AHashMap<FileId, TextEncoding>,
 }
 
 fn encode_to_file<T: Serialize>(
@@ -55,6 +77,8 @@ fn encode_to_file<T: Serialize>(
     let file = File::create(file_path)?;
 
     let mut writer = BufWriter::new(file);
+    writer.write_all(INDEX_MAGIC)?;
+    writer.write_all(&INDEX_FORMAT_VERSION.to_le_bytes())?;
     bserde::encode_into_std_write(
         value,
         &mut writer,
@@ -88,6 +112,7 @@ impl GitIndexSerialization {
             bytes_read: 0,
             pending_bytes: 0,
         };
+        check_format_version(&mut reader, file_path)?;
 
         let decoded = bserde::decode_from_std_read(
             &mut reader,
@@ -98,6 +123,36 @@ impl GitIndexSerialization {
     }
 }
 
+fn check_format_version(
+    reader: &mut impl Read,
+    file_path: &Path,
+) -> anyhow::Result<()> {
+    let mut header = [0; INDEX_MAGIC.len() + 4];
+    let version = match reader.read_exact(&mut header) {
+        Ok(()) if header.starts_with(INDEX_MAGIC) => Some(u32::from_le_bytes(
+            header[INDEX_MAGIC.len()..].try_into().unwrap(),
+        )),
+        Ok(()) => None,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    match version {
+        Some(INDEX_FORMAT_VERSION) => Ok(()),
+        Some(version) => Err(anyhow::anyhow!(
+            "{} has the index format {version}, but this build reads the \
+             format {INDEX_FORMAT_VERSION}. The index format changed, re-index \
+             the repository.",
+            file_path.display()
+        )),
+        None => Err(anyhow::anyhow!(
+            "{} was saved without the index format version. The index format \
+             changed, re-index the repository.",
+            file_path.display()
+        )),
+    }
+}
+
 impl<'a> From<&'a GitIndexer> for GitIndexSerializationRef<'a> {
     fn from(index: &'a GitIndexer) -> Self {
         Self {
@@ -107,8 +162,14 @@ impl<'a> From<&'a GitIndexer> for GitIndexSerializationRef<'a> {
             file_id_to_diff_tracker: &index.file_id_to_diff_tracker,
             word_to_file_id_ever_contained: &index
                 .word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: &index
+                .identifier_part_to_file_id_ever_contained,
+            identifier_counts: &index.identifier_counts,
             ignored_non_utf8_file_path_set: &index
                 .ignored_non_utf8_file_path_set,
+            skipped_files: &index.skipped_files,
+            file_kinds: &index.file_kinds,
+            file_id_to_encoding: &index.file_id_to_encoding,
         }
     }
 }
@@ -122,8 +183,14 @@ impl<'a> From<&'a GitIndexSerialization> for GitIndexSerializationRef<'a> {
             file_id_to_diff_tracker: &index.file_id_to_diff_tracker,
             word_to_file_id_ever_contained: &index
                 .word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: &index
+                .identifier_part_to_file_id_ever_contained,
+            identifier_counts: &index.identifier_counts,
             ignored_non_utf8_file_path_set: &index
                 .ignored_non_utf8_file_path_set,
+            skipped_files: &index.skipped_files,
+            file_kinds: &index.file_kinds,
+            file_id_to_encoding: &index.file_id_to_encoding,
         }
     }
 }
@@ -165,13 +232,13 @@ mod tests {
         let mut document_a = Document::new();
         document_a.add_words(
             1,
-            AHashMap::from_iter(vec![
+            &AHashMap::from_iter(vec![
                 ("abc".into(), vec![1, 2, 3]),
                 ("bcd".into(), vec![3, 4]),
             ]),
         );
         document_a
-            .add_words(2, AHashMap::from_iter(vec![("bcd".into(), vec![5])]));
+            .add_words(2, &AHashMap::from_iter(vec![("bcd".into(), vec![5])]));
         document_a.remove_words(
             3,
             &[(
@@ -212,9 +279,25 @@ mod tests {
                 "abc".into(),
                 ever_contained,
             )]),
+            identifier_part_to_file_id_ever_contained: AHashMap::from_iter(
+                vec![("abc".to_owned(), RoaringBitmap::from([1]))],
+            ),
+            identifier_counts: AHashMap::from_iter(vec![("abc".to_owned(), 2)]),
             ignored_non_utf8_file_path_set: AHashSet::from_iter(vec![
                 "/deleted".to_owned(),
             ]),
+            skipped_files: AHashMap::from_iter(vec![(
+                "/vendor/lib.rs".to_owned(),
+                SkipReason::Excluded,
+            )]),
+            file_kinds: FileKinds {
+                vendored: RoaringBitmap::from([1]),
+                generated: RoaringBitmap::new(),
+            },
+            file_id_to_encoding: AHashMap::from_iter(vec![(
+                1,
+                TextEncoding::Utf16Le,
+            )]),
         }
     }
 
@@ -226,8 +309,14 @@ mod tests {
                 && self.file_id_to_diff_tracker == other.file_id_to_diff_tracker
                 && self.word_to_file_id_ever_contained
                     == other.word_to_file_id_ever_contained
+                && self.identifier_part_to_file_id_ever_contained
+                    == other.identifier_part_to_file_id_ever_contained
+                && self.identifier_counts == other.identifier_counts
                 && self.ignored_non_utf8_file_path_set
                     == other.ignored_non_utf8_file_path_set
+                && self.skipped_files == other.skipped_files
+                && self.file_kinds == other.file_kinds
+                && self.file_id_to_encoding == other.file_id_to_encoding
         }
     }
 
@@ -265,6 +354,54 @@ mod tests {
         assert!(index == loaded);
     }
 
+    #[test]
+    fn test_load_without_format_version_errors() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file_path = dir.path().join("index.bin");
+
+        // Saved before the header was added.
+        let encoded =
+            serde::encode_to_vec(sample_index(), bincode::config::standard())
+                .unwrap();
+        std::fs::write(&file_path, encoded).unwrap();
+
+        let error = GitIndexSerialization::load(&file_path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("re-index"), "{error}");
+
+        std::fs::write(&file_path, b"").unwrap();
+        assert!(GitIndexSerialization::load(&file_path).is_err());
+    }
+
+    #[test]
+    fn test_load_other_format_version_errors() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file_path = dir.path().join("index.bin");
+
+        GitIndexSerializationRef::from(&sample_index())
+            .save(&file_path)
+            .unwrap();
+        let mut content = std::fs::read(&file_path).unwrap();
+        content[INDEX_MAGIC.len()..INDEX_MAGIC.len() + 4]
+            .copy_from_slice(&(INDEX_FORMAT_VERSION + 1).to_le_bytes());
+        std::fs::write(&file_path, content).unwrap();
+
+        let error = GitIndexSerialization::load(&file_path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.contains(&format!(
+                "the index format {}",
+                INDEX_FORMAT_VERSION + 1
+            )),
+            "{error}"
+        );
+        assert!(error.contains("re-index"), "{error}");
+    }
+
     #[test]
     fn test_load_missing_file_errors() {
         let dir = tempfile::tempdir().expect("tempdir");
@@ -5,11 +5,13 @@ use crate::index::git_index_serialization::GitIndexSerialization;
 use ahash::AHashMap;
 use ahash::AHashSet;
 use anyhow::Result;
+use git2::Blob;
 use git2::Delta;
 use git2::DiffDelta;
 use git2::DiffFlags;
 use git2::ObjectType;
 use git2::Oid;
+use git2::Patch;
 use git2::Repository;
 use git2::Sort;
 use git2::Tree;
@@ -25,13 +27,28 @@ use std::time::Instant;
 use trigram_hash::trigram_hash::TrigramKey;
 use trigram_hash::trigram_hash::split_lines_to_tokens;
 
+use super::blob_cache::BlobTokenCache;
 use super::check_binary::Utf8FileChecker;
 use super::document::Document;
 use super::document::WordKey;
+use super::encoding::TextEncoding;
+use super::encoding::decode;
+use super::encoding::detect_encoding;
+use super::file_filter::FileFilter;
+use super::file_filter::FileFilterConfig;
+use super::file_filter::SkipReason;
+use super::git_attributes::FileKinds;
+use super::git_attributes::GitAttributes;
+use super::git_attributes::is_git_attributes_file;
+use super::identifier::IdentifierTokens;
+use super::identifier::add_identifiers;
+use super::identifier::remove_identifiers;
+use super::identifier::split_lines_to_identifier_tokens;
 
 pub type CommitIndex = usize;
 pub type FileId = usize;
 
+#[derive(Clone)]
 pub struct GitIndexer {
     pub config: GitIndexerConfig,
 
@@ -47,15 +64,50 @@ pub struct GitIndexer {
     // RoaringBitmap is set if the corresponding file id contains the word.
     pub word_to_file_id_ever_contained: AHashMap<TrigramKey, RoaringBitmap>,
 
+    // Same as above, for the lowercased identifier parts.
+    pub identifier_part_to_file_id_ever_contained:
+        AHashMap<String, RoaringBitmap>,
+
+    // Number of occurrences of each identifier at HEAD.
+    pub identifier_counts: AHashMap<String, u64>,
+
     utf8_file_checker: Utf8FileChecker,
 
     pub ignored_non_utf8_file_path_set: AHashSet<String>,
+
+    // Files that are not indexed due to the file filter rules.
+    pub skipped_files: AHashMap<String, SkipReason>,
+
+    // Vendored and generated files according to .gitattributes at HEAD.
+    pub file_kinds: FileKinds,
+
+    // Original encoding of the files that are not in UTF-8 (at their last
+    // change). They are transcoded to UTF-8 when indexed.
+    pub file_id_to_encoding: AHashMap<FileId, TextEncoding>,
+
+    // .gitattributes of the commit being indexed.
+    git_attributes: GitAttributes,
+
+    // Built from the config when the indexing starts.
+    file_filter: FileFilter,
+
+    // Not saved; only speeds up the indexing.
+    blob_token_cache: BlobTokenCache,
 }
 
 #[derive(Debug)]
 struct CurrentGitDiffFile {
     current_file_id: FileId,
     status: Delta,
+    path: String,
+    // Blobs of the removed and the added file.
+    old_blob_id: Oid,
+    new_blob_id: Oid,
+    old_encoding: TextEncoding,
+    // Hunks are built from the decoded contents rather than by libgit2.
+    transcoded: bool,
+    // libgit2 gave a line that is not in UTF-8.
+    needs_decoding: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -63,6 +115,7 @@ pub struct GitIndexerConfig {
     pub show_index_progress: bool,
     pub main_branch_name: String,
     pub ignore_utf8_error: bool,
+    pub file_filter: FileFilterConfig,
 }
 
 impl GitIndexer {
@@ -77,7 +130,15 @@ impl GitIndexer {
             file_id_to_diff_tracker: AHashMap::new(),
             file_id_to_document: AHashMap::new(),
             word_to_file_id_ever_contained: AHashMap::new(),
+            identifier_part_to_file_id_ever_contained: AHashMap::new(),
+            identifier_counts: AHashMap::new(),
             ignored_non_utf8_file_path_set: AHashSet::new(),
+            skipped_files: AHashMap::new(),
+            file_kinds: FileKinds::default(),
+            file_id_to_encoding: AHashMap::new(),
+            git_attributes: GitAttributes::default(),
+            file_filter: FileFilter::default(),
+            blob_token_cache: BlobTokenCache::default(),
         }
     }
 
@@ -110,8 +171,17 @@ impl GitIndexer {
             file_id_to_document: index.file_id_to_document,
             word_to_file_id_ever_contained: index
                 .word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: index
+                .identifier_part_to_file_id_ever_contained,
+            identifier_counts: index.identifier_counts,
             ignored_non_utf8_file_path_set: index
                 .ignored_non_utf8_file_path_set,
+            skipped_files: index.skipped_files,
+            file_kinds: index.file_kinds,
+            file_id_to_encoding: index.file_id_to_encoding,
+            git_attributes: GitAttributes::default(),
+            file_filter: FileFilter::default(),
+            blob_token_cache: BlobTokenCache::default(),
         }
     }
 
@@ -132,6 +202,8 @@ impl GitIndexer {
     }
 
     pub fn index_history(&mut self, repo: Repository) -> Result<()> {
+        self.file_filter = FileFilter::new(&self.config.file_filter)?;
+
         let mut revwalk = repo.revwalk()?;
 
         revwalk.push_head()?;
@@ -170,6 +242,10 @@ impl GitIndexer {
             last_tree = Some(repo.find_commit(last_indexed_commit)?.tree()?);
         }
 
+        if let Some(last_tree) = &last_tree {
+            self.git_attributes = GitAttributes::from_tree(&repo, last_tree)?;
+        }
+
         for old_result in revwalk {
             let old = old_result?;
             let commit = repo.find_commit(old)?;
@@ -199,11 +275,14 @@ impl GitIndexer {
                 self.index_diff(&tree, prev_tree, &repo, &commit_index)?;
                 last_tree = Some(tree);
             } else {
+                self.git_attributes = GitAttributes::from_tree(&repo, &tree)?;
                 last_tree = Some(tree);
                 self.index_tree(&0, last_tree.as_ref().unwrap(), &repo)?;
             }
         }
 
+        self.update_file_kinds();
+
         if let Some(bar) = &bar {
             bar.finish();
         }
@@ -257,6 +336,16 @@ impl GitIndexer {
             Some(&mut opts),
         )?;
 
+        // The files added in this commit follow its .gitattributes.
+        let attributes_changed = diff.deltas().any(|delta| {
+            get_file_names_from_diff_delta(&delta)
+                .iter()
+                .any(|path| is_git_attributes_file(path))
+        });
+        if attributes_changed {
+            self.git_attributes = GitAttributes::from_tree(repo, current_tree)?;
+        }
+
         let current_diff_file: RefCell<Option<CurrentGitDiffFile>> =
             RefCell::new(None);
 
@@ -264,156 +353,102 @@ impl GitIndexer {
 
         let mut for_each_start_times = vec![];
 
-        diff.foreach(
+        // Error that stopped the diff in the callbacks.
+        let mut callback_error = None;
+
+        let result = diff.foreach(
             &mut |delta, _| {
                 for_each_start_times.push(Instant::now());
 
-                // Ignore binary files.
-                if delta.flags().contains(DiffFlags::BINARY) {
-                    // If the binary diffs are detected, 
-                    for path in get_file_names_from_diff_delta(&delta) {
-                        trace!("Ignored: {}", path);
-                        self.ignored_non_utf8_file_path_set.insert(path);
-                    }
-
-                    return true;
-                }
-
-                let mut file_delta = file_delta.borrow_mut();
-                let mut current_diff_file = current_diff_file.borrow_mut();
-
-                if !file_delta.is_empty() {
-                    self.index_git_delta(
-                        &current_diff_file,
-                        &file_delta,
-                        commit_index,
-                    )
-                    .unwrap();
-                    file_delta.clear();
-                }
-
-                match delta.status() {
-                    Delta::Modified => {
-                        if let Some(path) = delta.old_file().path() {
-                            let path = path.to_str().unwrap();
-
-                            if self.ignored_non_utf8_file_path_set.contains(path) {
-                                *current_diff_file = None;
-                                return true;
-                            }
-
-                            let file_id = self.get_file_id_insert_if_missing(path);
-                            *current_diff_file = Some(CurrentGitDiffFile {
-                                current_file_id: file_id,
-                                status: delta.status(),
-                            });
-                        }
-                    }
-                    Delta::Deleted => {
-                        if let Some(path) = delta.old_file().path() {
-                            let path = path.to_str().unwrap();
-                            if self.ignored_non_utf8_file_path_set.contains(path) {
-                                *current_diff_file = None;
-                                return true;
-                            }
-
-                            let file_id = self.get_file_id_insert_if_missing(
-                                path,
-                            );
-
-                            *current_diff_file = Some(CurrentGitDiffFile {
-                                current_file_id: file_id,
-                                status: delta.status(),
-                            });
-                        }
-                    }
-                    Delta::Added => {
-                        if let Some(path) = delta.new_file().path() {
-                            let path = path.to_str().unwrap();
-                            if self.ignored_non_utf8_file_path_set.contains(path) {
-                                *current_diff_file = None;
-                                return true;
-                            }
-
-                            let file_id = self.get_file_id_insert_if_missing(
-                                path,
-                            );
-
-                            *current_diff_file = Some(CurrentGitDiffFile {
-                                current_file_id: file_id,
-                                status: delta.status(),
-                            });
-                        }
-                    }
-                    _ => {
-                        panic!("Unknown {:?}", delta)
+                match self.begin_diff_file(
+                    repo,
+                    &delta,
+                    *commit_index,
+                    &mut current_diff_file.borrow_mut(),
+                    &mut file_delta.borrow_mut(),
+                ) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        callback_error = Some(e);
+                        false
                     }
                 }
-
-                true
             },
             None,
             Some(&mut |_delta, hunk| {
                 let current_diff_file = current_diff_file.borrow();
-                if current_diff_file.is_none() {
+                if current_diff_file
+                    .as_ref()
+                    .is_none_or(|file| file.transcoded)
+                {
                     return true;
                 }
 
                 file_delta.borrow_mut().push(GitDelta {
-                    prev_line_start_num: hunk.old_start(),
-                    prev_line_count: hunk.old_lines(),
-                    new_line_start_num: hunk.new_start(),
-                    new_line_count: hunk.new_lines(),
-                    added_lines: Vec::with_capacity(hunk.new_lines() as usize),
-                    deleted_lines: Vec::with_capacity(hunk.old_lines() as usize),
-                });
+                        prev_line_start_num: hunk.old_start(),
+                        prev_line_count: hunk.old_lines(),
+                        new_line_start_num: hunk.new_start(),
+                        new_line_count: hunk.new_lines(),
+                        added_lines: Vec::with_capacity(
+                            hunk.new_lines() as usize
+                        ),
+                        deleted_lines: Vec::with_capacity(
+                            hunk.old_lines() as usize
+                        ),
+                    });
 
                 true
             }),
             Some(&mut |_, _, line| {
-                let current_diff_file = current_diff_file.borrow();
-                if current_diff_file.is_none() {
+                let mut current_diff_file = current_diff_file.borrow_mut();
+                let Some(file) =
+                    current_diff_file.as_mut().filter(|file| !file.transcoded)
+                else {
                     return true;
-                }
+                };
 
-                // No need to handle Delte::Removed case.
-                if let Some(current_diff_file) = current_diff_file.as_ref()
-                    && !(current_diff_file.status == Delta::Modified
-                        || current_diff_file.status == Delta::Added) {
+                // The file is decoded once its diff is done.
+                let Ok(content) = std::str::from_utf8(line.content()) else {
+                    file.needs_decoding = true;
                     return true;
+                };
+                if line.new_lineno() == Some(1)
+                    && content.starts_with('\u{feff}')
+                {
+                    file.needs_decoding = true;
                 }
 
+                let content = content.strip_suffix('\n').unwrap_or(content);
                 let mut file_delta = file_delta.borrow_mut();
                 if line.origin() == '+' {
-                    let line = std::str::from_utf8(line.content())
-                            .unwrap_or("<invalid utf8>");
-                    file_delta.last_mut().unwrap().added_lines.push(
-                       line.strip_suffix('\n')
-                            .unwrap_or(line)
-                            .to_owned(),
-                    );
+                    file_delta
+                        .last_mut()
+                        .unwrap()
+                        .added_lines
+                        .push(content.to_owned());
                 } else if line.origin() == '-' {
-                    let line = std::str::from_utf8(line.content())
-                            .unwrap_or("<invalid utf8>");
-                    file_delta.last_mut().unwrap().deleted_lines.push(
-                        line.strip_suffix('\n').unwrap_or(line)
-                            .to_owned(),
-                    );
+                    file_delta
+                        .last_mut()
+                        .unwrap()
+                        .deleted_lines
+                        .push(content.to_owned());
                 }
 
                 true
             }),
-        )?;
+        );
 
-        let file_delta = file_delta.borrow_mut();
-        if !file_delta.is_empty() {
-            self.index_git_delta(
-                &current_diff_file.borrow_mut(),
-                &file_delta,
-                commit_index,
-            )
-            .unwrap();
+        if let Some(e) = callback_error {
+            return Err(e);
         }
+        result?;
+
+        self.index_current_diff_file(
+            repo,
+            &mut current_diff_file.borrow_mut(),
+            &mut file_delta.borrow_mut(),
+            *commit_index,
+        )?;
 
         let git_delta_index_done = Instant::now();
         debug!(
@@ -443,47 +478,49 @@ impl GitIndexer {
             }
 
             if let Some(name) = entry.name() {
+                let file_name = &format!("{root}{name}");
                 let object_id = entry.id();
-                let blob = match repo.find_blob(object_id) {
-                    Ok(blob) => blob,
-                    Err(_) => return TreeWalkResult::Ok,
-                };
-
-                let file_ext = Path::new(name)
-                    .extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap();
 
-                if !self
-                    .utf8_file_checker
-                    .is_utf8_document(blob.content(), file_ext)
+                let mut blob = None;
+                if let Some(reason) =
+                    self.check_new_file(repo, file_name, object_id, &mut blob)
                 {
+                    trace!("Skipped: {file_name} ({reason})");
+                    self.skipped_files.insert(file_name.clone(), reason);
                     return TreeWalkResult::Ok;
                 }
 
-                let content = std::str::from_utf8(blob.content());
-
-                if content.is_err() {
-                    if self.config.ignore_utf8_error {
-                        self.ignored_non_utf8_file_path_set
-                            .insert(format!("{root}{name}"));
+                let blob = match load_blob(repo, &mut blob, object_id) {
+                    Ok(blob) => blob,
+                    Err(_) => return TreeWalkResult::Ok,
+                };
 
-                        return TreeWalkResult::Ok;
-                    } else {
+                let Some(encoding) =
+                    self.text_encoding(Some(Path::new(name)), blob.content())
+                else {
+                    // Only the content that is not a text at all is an error.
+                    if !self.config.ignore_utf8_error
+                        && detect_encoding(blob.content()).is_none()
+                    {
                         panic!("Non UTF-8 file found at {root}{name}");
                     }
-                }
 
-                let content = content.unwrap();
-                let file_name = &format!("{root}{name}");
+                    // Recorded, so that the file is indexed once it turns
+                    // into a text.
+                    self.ignored_non_utf8_file_path_set
+                        .insert(format!("{root}{name}"));
+
+                    return TreeWalkResult::Ok;
+                };
+
+                let content = decode(blob.content(), encoding);
 
                 let file_id = self.get_file_id_insert_if_missing(file_name);
-                self.add_new_lines(
+                self.set_encoding(file_id, encoding);
+                self.add_new_file(
                     *commit_index,
                     file_id,
-                    /*prev_line_start=*/ 0,
-                    /*new_line_start=*/ 0,
+                    object_id,
                     &content
                         .lines()
                         .map(|line| line.to_string())
@@ -497,6 +534,347 @@ impl GitIndexer {
         Ok(())
     }
 
+    // Returns why the newly added file should not be indexed. Files that are
+    // indexed already keep being tracked even if they break the rules later.
+    //
+    // The blob is read into `blob` only if its content has to be checked.
+    fn check_new_file<'r>(
+        &self,
+        repo: &'r Repository,
+        path: &str,
+        blob_id: Oid,
+        blob: &mut Option<Blob<'r>>,
+    ) -> Option<SkipReason> {
+        if let Some(reason) = self.file_filter.check_path(path) {
+            return Some(reason);
+        }
+
+        if self.git_attributes.attributes(path).binary {
+            return Some(SkipReason::Binary);
+        }
+
+        let (size, _) = repo.odb().ok()?.read_header(blob_id).ok()?;
+        if let Some(reason) = self.file_filter.check_size(size as u64) {
+            return Some(reason);
+        }
+
+        if !self.file_filter.needs_content() {
+            return None;
+        }
+
+        let blob = load_blob(repo, blob, blob_id).ok()?;
+        self.file_filter.check_content(blob.content())
+    }
+
+    // Starts the diff of the file in the delta, after indexing the hunks of
+    // the previous file. libgit2 sends the hunks of the file to `file_delta`
+    // unless `current_diff_file` is left empty.
+    //
+    // Files that libgit2 diffs as a text in UTF-8 are not read here; they are
+    // decoded only if libgit2 gives a line that is not in UTF-8.
+    fn begin_diff_file(
+        &mut self,
+        repo: &Repository,
+        delta: &DiffDelta,
+        commit_index: CommitIndex,
+        current_diff_file: &mut Option<CurrentGitDiffFile>,
+        file_delta: &mut Vec<GitDelta>,
+    ) -> Result<()> {
+        self.index_current_diff_file(
+            repo,
+            current_diff_file,
+            file_delta,
+            commit_index,
+        )?;
+        *current_diff_file = None;
+        file_delta.clear();
+
+        let status = delta.status();
+        let path = match status {
+            Delta::Added => delta.new_file().path(),
+            Delta::Modified | Delta::Deleted => delta.old_file().path(),
+            _ => panic!("Unknown {:?}", delta),
+        };
+        let Some(path) = path.and_then(|path| path.to_str()) else {
+            return Ok(());
+        };
+
+        let new_blob_id = delta.new_file().id();
+        let mut new_blob = None;
+        let was_binary = self.ignored_non_utf8_file_path_set.contains(path);
+
+        match status {
+            Delta::Added => {
+                // The rules are checked again, since the file may have been
+                // skipped when it was added before.
+                self.skipped_files.remove(path);
+                self.ignored_non_utf8_file_path_set.remove(path);
+                if let Some(reason) =
+                    self.check_new_file(repo, path, new_blob_id, &mut new_blob)
+                {
+                    trace!("Skipped: {path} ({reason})");
+                    self.skipped_files.insert(path.to_owned(), reason);
+                    return Ok(());
+                }
+            }
+            _ if self.skipped_files.contains_key(path) => return Ok(()),
+            Delta::Deleted if was_binary => {
+                self.ignored_non_utf8_file_path_set.remove(path);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let is_binary_diff = delta.flags().contains(DiffFlags::BINARY);
+        let old_encoding = self.old_encoding(delta);
+
+        let new_encoding = match status {
+            Delta::Deleted => old_encoding,
+            _ if !is_binary_diff
+                && !was_binary
+                && old_encoding == TextEncoding::Utf8 =>
+            {
+                TextEncoding::Utf8
+            }
+            _ => {
+                let blob = load_blob(repo, &mut new_blob, new_blob_id)?;
+                match self.text_encoding(Some(Path::new(path)), blob.content())
+                {
+                    Some(encoding) => encoding,
+                    None if status == Delta::Added => {
+                        trace!("Ignored: {path}");
+                        self.ignored_non_utf8_file_path_set
+                            .insert(path.to_owned());
+                        return Ok(());
+                    }
+                    None if was_binary => return Ok(()),
+                    None => match self.file_name_to_id.get(path).copied() {
+                        Some(file_id) => {
+                            return self.close_binary_file(
+                                repo,
+                                path,
+                                file_id,
+                                delta.old_file().id(),
+                                commit_index,
+                            );
+                        }
+                        None => {
+                            self.ignored_non_utf8_file_path_set
+                                .insert(path.to_owned());
+                            return Ok(());
+                        }
+                    },
+                }
+            }
+        };
+
+        // The file that turns back into a text is indexed from its whole blob
+        // as if it was added now.
+        if was_binary {
+            trace!("Binary to text: {path}");
+            self.ignored_non_utf8_file_path_set.remove(path);
+
+            if let Some(reason) =
+                self.check_new_file(repo, path, new_blob_id, &mut new_blob)
+            {
+                trace!("Skipped: {path} ({reason})");
+                self.skipped_files.insert(path.to_owned(), reason);
+                return Ok(());
+            }
+
+            let blob = load_blob(repo, &mut new_blob, new_blob_id)?;
+            let lines = decode(blob.content(), new_encoding)
+                .lines()
+                .map(|line| line.to_owned())
+                .collect::<Vec<String>>();
+
+            let file_id = self.get_file_id_insert_if_missing(path);
+            self.set_encoding(file_id, new_encoding);
+            self.add_new_file(commit_index, file_id, new_blob_id, &lines);
+            return Ok(());
+        }
+
+        let file_id = self.get_file_id_insert_if_missing(path);
+        let mut file = CurrentGitDiffFile {
+            current_file_id: file_id,
+            status,
+            path: path.to_owned(),
+            old_blob_id: delta.old_file().id(),
+            new_blob_id,
+            old_encoding,
+            transcoded: false,
+            needs_decoding: false,
+        };
+
+        // The diff of libgit2 is on the raw bytes, so the decoded contents are
+        // diffed instead. libgit2 may also flag a text as binary (e.g. a
+        // control character in it).
+        if is_binary_diff
+            || old_encoding != TextEncoding::Utf8
+            || new_encoding != TextEncoding::Utf8
+        {
+            file.transcoded = true;
+            file_delta.extend(diff_transcoded(
+                repo,
+                file.old_blob_id,
+                old_encoding,
+                new_blob_id,
+                new_encoding,
+            )?);
+        }
+
+        if status != Delta::Deleted {
+            self.set_encoding(file_id, new_encoding);
+        }
+
+        *current_diff_file = Some(file);
+
+        Ok(())
+    }
+
+    // Indexes the hunks of the file whose diff is done.
+    fn index_current_diff_file(
+        &mut self,
+        repo: &Repository,
+        current_diff_file: &mut Option<CurrentGitDiffFile>,
+        file_delta: &mut Vec<GitDelta>,
+        commit_index: CommitIndex,
+    ) -> Result<()> {
+        let Some(file) = current_diff_file.as_mut() else {
+            return Ok(());
+        };
+
+        // libgit2 gave a line that is not in UTF-8, so the whole file is
+        // decoded and diffed again.
+        if file.needs_decoding {
+            file.needs_decoding = false;
+
+            let blob = repo.find_blob(file.new_blob_id)?;
+            let Some(encoding) =
+                self.text_encoding(Some(Path::new(&file.path)), blob.content())
+            else {
+                file_delta.clear();
+
+                let file = current_diff_file.take().unwrap();
+                if file.status == Delta::Added {
+                    trace!("Ignored: {}", file.path);
+                    self.ignored_non_utf8_file_path_set.insert(file.path);
+                    return Ok(());
+                }
+
+                return self.close_binary_file(
+                    repo,
+                    &file.path,
+                    file.current_file_id,
+                    file.old_blob_id,
+                    commit_index,
+                );
+            };
+
+            file.transcoded = true;
+            *file_delta = diff_transcoded(
+                repo,
+                file.old_blob_id,
+                file.old_encoding,
+                file.new_blob_id,
+                encoding,
+            )?;
+            self.set_encoding(file.current_file_id, encoding);
+        }
+
+        if file_delta.is_empty() {
+            return Ok(());
+        }
+
+        self.index_git_delta(current_diff_file, file_delta, &commit_index)
+            .map_err(anyhow::Error::msg)
+    }
+
+    // Closes the document of the file that turns binary, as if it was deleted.
+    fn close_binary_file(
+        &mut self,
+        repo: &Repository,
+        path: &str,
+        file_id: FileId,
+        old_blob_id: Oid,
+        commit_index: CommitIndex,
+    ) -> Result<()> {
+        trace!("Text to binary: {path}");
+        self.ignored_non_utf8_file_path_set.insert(path.to_owned());
+
+        let old_encoding = self
+            .file_id_to_encoding
+            .remove(&file_id)
+            .unwrap_or(TextEncoding::Utf8);
+        let blob = repo.find_blob(old_blob_id)?;
+        let lines = decode(blob.content(), old_encoding)
+            .lines()
+            .map(|line| line.to_owned())
+            .collect::<Vec<String>>();
+
+        self.delete_entire_file(commit_index, file_id, &lines);
+
+        Ok(())
+    }
+
+    fn old_encoding(&self, delta: &DiffDelta) -> TextEncoding {
+        delta
+            .old_file()
+            .path()
+            .and_then(|path| self.file_name_to_id.get(path.to_str()?))
+            .and_then(|file_id| self.file_id_to_encoding.get(file_id))
+            .copied()
+            .unwrap_or(TextEncoding::Utf8)
+    }
+
+    // Returns None if the content is binary.
+    fn text_encoding(
+        &self,
+        path: Option<&Path>,
+        content: &[u8],
+    ) -> Option<TextEncoding> {
+        let encoding = detect_encoding(content)?;
+
+        // Files with the BOM are transcoded, so only the others are checked.
+        let file_ext = path
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        if matches!(encoding, TextEncoding::Utf8 | TextEncoding::Latin1)
+            && !self.utf8_file_checker.is_utf8_document(content, file_ext)
+        {
+            return None;
+        }
+
+        Some(encoding)
+    }
+
+    // Only the files that are not in UTF-8 are recorded.
+    fn set_encoding(&mut self, file_id: FileId, encoding: TextEncoding) {
+        match encoding {
+            TextEncoding::Utf8 => self.file_id_to_encoding.remove(&file_id),
+            _ => self.file_id_to_encoding.insert(file_id, encoding),
+        };
+    }
+
+    // Flags the files by the .gitattributes at HEAD.
+    fn update_file_kinds(&mut self) {
+        self.file_kinds = FileKinds::default();
+        if self.git_attributes.is_empty() {
+            return;
+        }
+
+        for (file_id, path) in self.file_id_to_path.iter().enumerate() {
+            let attributes = self.git_attributes.attributes(path);
+            if attributes.vendored {
+                self.file_kinds.vendored.insert(file_id as u32);
+            }
+            if attributes.generated {
+                self.file_kinds.generated.insert(file_id as u32);
+            }
+        }
+    }
+
     fn index_git_delta(
         &mut self,
         file: &Option<CurrentGitDiffFile>,
@@ -509,6 +887,7 @@ impl GitIndexer {
 
         let file_id = file.as_ref().unwrap().current_file_id;
         let status = file.as_ref().unwrap().status;
+        let new_blob_id = file.as_ref().unwrap().new_blob_id;
 
         match status {
             Delta::Modified => {
@@ -584,11 +963,10 @@ impl GitIndexer {
                     ));
                 }
 
-                self.add_new_lines(
+                self.add_new_file(
                     *commit_index,
                     file_id,
-                    /*prev_line_start_num=*/ 0,
-                    0,
+                    new_blob_id,
                     &hunks[0].added_lines,
                 );
             }
@@ -605,7 +983,11 @@ impl GitIndexer {
                     ));
                 }
 
-                self.delete_entire_file(*commit_index, file_id);
+                self.delete_entire_file(
+                    *commit_index,
+                    file_id,
+                    &hunks[0].deleted_lines,
+                );
             }
             _ => {}
         }
@@ -613,6 +995,29 @@ impl GitIndexer {
         Ok(())
     }
 
+    // Add every line of the blob to the (empty) file. Tokens of the blobs that
+    // were seen before are reused.
+    fn add_new_file(
+        &mut self,
+        commit_index: CommitIndex,
+        file_id: FileId,
+        blob_id: Oid,
+        lines: &[String],
+    ) {
+        let blob_id = blob_id.as_bytes().try_into().unwrap();
+        let blob_tokens = self.blob_token_cache.get_or_tokenize(blob_id, lines);
+
+        self.add_lines_with_tokens(
+            commit_index,
+            file_id,
+            /*prev_line_start=*/ 0,
+            /*new_line_start=*/ 0,
+            lines,
+            &blob_tokens.words,
+            &blob_tokens.identifiers,
+        );
+    }
+
     // Add a new line at "prev_line_start".
     //
     // New lines are copied from (new_line_start, new_line_count) from the new file.
@@ -623,6 +1028,32 @@ impl GitIndexer {
         prev_line_start: usize,
         new_line_start: usize,
         lines: &[String],
+    ) {
+        let tokens = split_lines_to_tokens(lines, new_line_start);
+        let identifier_tokens =
+            split_lines_to_identifier_tokens(lines, new_line_start);
+
+        self.add_lines_with_tokens(
+            commit_index,
+            file_id,
+            prev_line_start,
+            new_line_start,
+            lines,
+            &tokens,
+            &identifier_tokens,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_lines_with_tokens(
+        &mut self,
+        commit_index: CommitIndex,
+        file_id: FileId,
+        prev_line_start: usize,
+        new_line_start: usize,
+        lines: &[String],
+        tokens: &AHashMap<TrigramKey, Vec<usize>>,
+        identifier_tokens: &IdentifierTokens,
     ) {
         let diff_tracker = self.file_id_to_diff_tracker.get_mut(&file_id);
         if let Some(tracker) = diff_tracker {
@@ -638,9 +1069,9 @@ impl GitIndexer {
             );
         }
 
-        // Now index those new lines.
-        let tokens = split_lines_to_tokens(lines, new_line_start);
+        add_identifiers(&mut self.identifier_counts, lines);
 
+        // Now index those new lines.
         let document = self.file_id_to_document.entry(file_id).or_default();
         for word in tokens.keys() {
             self.word_to_file_id_ever_contained
@@ -649,7 +1080,22 @@ impl GitIndexer {
                 .insert(file_id as u32);
         }
 
+        for part in identifier_tokens.parts.keys() {
+            match self.identifier_part_to_file_id_ever_contained.get_mut(part) {
+                Some(file_ids) => {
+                    file_ids.insert(file_id as u32);
+                }
+                None => {
+                    self.identifier_part_to_file_id_ever_contained.insert(
+                        part.clone(),
+                        RoaringBitmap::from([file_id as u32]),
+                    );
+                }
+            }
+        }
+
         document.add_words(commit_index, tokens);
+        document.add_tokens(commit_index, identifier_tokens);
     }
 
     fn delete_lines(
@@ -663,6 +1109,8 @@ impl GitIndexer {
         assert!(diff_tracker.is_some());
 
         let diff_tracker = diff_tracker.unwrap();
+        remove_identifiers(&mut self.identifier_counts, lines);
+
         let delete_result =
             diff_tracker.delete_lines(delete_line_start, lines.len());
 
@@ -690,12 +1138,37 @@ impl GitIndexer {
             .collect::<Vec<(TrigramKey, Vec<WordKey>)>>();
 
         document.remove_words(commit_index, &word_to_lines);
+
+        let identifier_tokens = split_lines_to_identifier_tokens(
+            lines, /*line_start_index=*/ 0,
+        );
+        let to_word_keys = |tokens: AHashMap<String, Vec<usize>>| {
+            tokens
+                .into_iter()
+                .map(|(token, lines)| {
+                    (
+                        token,
+                        lines
+                            .into_iter()
+                            .map(|line| word_key_for_each_deleted_line[line])
+                            .collect::<Vec<WordKey>>(),
+                    )
+                })
+                .collect::<Vec<(String, Vec<WordKey>)>>()
+        };
+
+        document.remove_tokens(
+            commit_index,
+            &to_word_keys(identifier_tokens.identifiers),
+            &to_word_keys(identifier_tokens.parts),
+        );
     }
 
     fn delete_entire_file(
         &mut self,
         commit_index: CommitIndex,
         file_id: FileId,
+        lines: &[String],
     ) {
         let diff_tracker = self.file_id_to_diff_tracker.get_mut(&file_id);
         assert!(diff_tracker.is_some());
@@ -703,6 +1176,8 @@ impl GitIndexer {
         let diff_tracker = diff_tracker.unwrap();
         diff_tracker.delete_all();
 
+        remove_identifiers(&mut self.identifier_counts, lines);
+
         let document = self.file_id_to_document.get_mut(&file_id);
         assert!(document.is_some());
 
@@ -764,6 +1239,81 @@ fn get_file_names_from_diff_delta(d: &DiffDelta) -> Vec<String> {
     v
 }
 
+// Reads the blob unless it is read already.
+fn load_blob<'r, 'b>(
+    repo: &'r Repository,
+    blob: &'b mut Option<Blob<'r>>,
+    blob_id: Oid,
+) -> Result<&'b Blob<'r>> {
+    if blob.is_none() {
+        *blob = Some(repo.find_blob(blob_id)?);
+    }
+
+    Ok(blob.as_ref().unwrap())
+}
+
+// Diffs the decoded contents of the blobs. The zero id is an empty file.
+fn diff_transcoded(
+    repo: &Repository,
+    old_blob_id: Oid,
+    old_encoding: TextEncoding,
+    new_blob_id: Oid,
+    new_encoding: TextEncoding,
+) -> Result<Vec<GitDelta>> {
+    let read = |blob_id: Oid, encoding: TextEncoding| -> Result<String> {
+        if blob_id.is_zero() {
+            return Ok(String::new());
+        }
+
+        Ok(decode(repo.find_blob(blob_id)?.content(), encoding).into_owned())
+    };
+
+    let old = read(old_blob_id, old_encoding)?;
+    let new = read(new_blob_id, new_encoding)?;
+
+    let mut opts = git2::DiffOptions::new();
+    opts.context_lines(0);
+    opts.force_text(true);
+
+    let patch = Patch::from_buffers(
+        old.as_bytes(),
+        None,
+        new.as_bytes(),
+        None,
+        Some(&mut opts),
+    )?;
+
+    let mut deltas = vec![];
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, num_lines) = patch.hunk(hunk_index)?;
+        let mut git_delta = GitDelta {
+            prev_line_start_num: hunk.old_start(),
+            prev_line_count: hunk.old_lines(),
+            new_line_start_num: hunk.new_start(),
+            new_line_count: hunk.new_lines(),
+            added_lines: Vec::with_capacity(hunk.new_lines() as usize),
+            deleted_lines: Vec::with_capacity(hunk.old_lines() as usize),
+        };
+
+        for line_index in 0..num_lines {
+            let line = patch.line_in_hunk(hunk_index, line_index)?;
+            let content = String::from_utf8_lossy(line.content());
+            let content =
+                content.strip_suffix('\n').unwrap_or(&content).to_owned();
+
+            match line.origin() {
+                '+' => git_delta.added_lines.push(content),
+                '-' => git_delta.deleted_lines.push(content),
+                _ => {}
+            }
+        }
+
+        deltas.push(git_delta);
+    }
+
+    Ok(deltas)
+}
+
 #[derive(Debug)]
 struct GitDelta {
     prev_line_start_num: u32,
@@ -778,7 +1328,11 @@ struct GitDelta {
 
 #[cfg(test)]
 mod index_tree {
+    use crate::index::document::TokenIndex;
     use crate::index::document::WordIndex;
+    use crate::index::git_index::GitIndex;
+    use crate::search::git_searcher::GitSearcher;
+    use crate::search::git_searcher::Query;
 
     use super::*;
 
@@ -799,6 +1353,46 @@ mod index_tree {
         )
     }
 
+    // (token, [(commit, line)] of the history, commit_inclutivity)
+    type TokenHistory<'a> =
+        (&'a str, &'a [(CommitIndex, usize)], &'a RoaringBitmap);
+
+    // Tokens of the words that have a single lowercase part, e.g. `abc`.
+    fn single_part_tokens(tokens: &[TokenHistory]) -> TokenIndex {
+        let tokens = AHashMap::from_iter(tokens.iter().map(
+            |(token, history, commit_inclutivity)| {
+                (
+                    token.to_string(),
+                    WordIndex {
+                        word_history: history
+                            .iter()
+                            .map(|(commit_id, line)| WordKey {
+                                commit_id: *commit_id,
+                                line: *line,
+                            })
+                            .collect(),
+                        commit_inclutivity: (*commit_inclutivity).clone(),
+                    },
+                )
+            },
+        ));
+
+        TokenIndex {
+            identifiers: tokens.clone(),
+            parts: tokens,
+        }
+    }
+
+    // PNG header followed by the NUL-heavy data, longer than the part that
+    // detect_encoding() checks.
+    fn binary_content() -> Vec<u8> {
+        [
+            b"\x89PNG\r\n\x1a\n".as_slice(),
+            &[0, 0, 0, 0x0d].repeat(4096),
+        ]
+        .concat()
+    }
+
     fn init_repo() -> tempfile::TempDir {
         let dir = tempfile::tempdir().expect("tempdir");
         let root = dir.path();
@@ -818,6 +1412,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -898,7 +1493,13 @@ mod index_tree {
                         fst::Set::from_iter(["a", "bc", "def", "efa"]).unwrap()
                     ),
                     doc_modified_commits: RoaringBitmap::from_iter([0]),
-                    is_deleted: false
+                    is_deleted: false,
+                    tokens: single_part_tokens(&[
+                        ("a", &[(0, 0)], &commit_incl),
+                        ("bc", &[(0, 1)], &commit_incl),
+                        ("def", &[(0, 2)], &commit_incl),
+                        ("defa", &[(0, 3)], &commit_incl)
+                    ]),
                 }
             )])
         );
@@ -931,6 +1532,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1001,7 +1603,12 @@ mod index_tree {
                             fst::Set::from_iter(["abc", "bcd"]).unwrap()
                         ),
                         doc_modified_commits: RoaringBitmap::from_iter([0]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: single_part_tokens(&[(
+                            "abcd",
+                            &[(0, 0)],
+                            &first_commit_incl
+                        )]),
                     }
                 ),
                 (
@@ -1019,7 +1626,8 @@ mod index_tree {
                         ),]),
                         all_words: Some(fst::Set::from_iter(["123"]).unwrap()),
                         doc_modified_commits: RoaringBitmap::from_iter([1]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: TokenIndex::default(),
                     }
                 )
             ])
@@ -1052,6 +1660,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1147,7 +1756,12 @@ mod index_tree {
                             .unwrap()
                     ),
                     doc_modified_commits: RoaringBitmap::from_iter([0, 1]),
-                    is_deleted: false
+                    is_deleted: false,
+                    tokens: single_part_tokens(&[(
+                        "abcd",
+                        &[(1, 1)],
+                        &second_commit_incl
+                    )]),
                 }
             ),])
         );
@@ -1170,6 +1784,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1232,7 +1847,8 @@ mod index_tree {
                         fst::Set::from_iter(["1", "2", "3"]).unwrap()
                     ),
                     doc_modified_commits: RoaringBitmap::from_iter([0, 1, 2]),
-                    is_deleted: true
+                    is_deleted: true,
+                    tokens: TokenIndex::default(),
                 }
             ),])
         );
@@ -1249,12 +1865,84 @@ mod index_tree {
         )
     }
 
+    #[test]
+    fn identifier_counts_follow_head() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::write(repo_path.join("a.rs"), "fn foo() {}\nfoo();\n")
+            .unwrap();
+        std::fs::write(repo_path.join("b.rs"), "fn bar() {}\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("a.rs"), "fn foo() {}\nbaz();\n")
+            .unwrap();
+        std::fs::remove_file(repo_path.join("b.rs")).unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "second"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(
+            indexer.identifier_counts,
+            AHashMap::from_iter([("foo".to_owned(), 1), ("baz".to_owned(), 1)])
+        );
+    }
+
+    #[test]
+    fn identifier_tokens_history() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::write(repo_path.join("a.rs"), "getUserName();\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("a.rs"), "getUser();\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "second"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        let tokens = &indexer.file_id_to_document[&0].tokens;
+        let commits = |index: &AHashMap<String, WordIndex>, token: &str| {
+            index[token].commit_inclutivity.iter().collect::<Vec<_>>()
+        };
+
+        assert_eq!(commits(&tokens.identifiers, "getUserName"), vec![0]);
+        assert_eq!(commits(&tokens.identifiers, "getUser"), vec![1]);
+        assert_eq!(commits(&tokens.parts, "name"), vec![0]);
+        assert_eq!(commits(&tokens.parts, "user"), vec![0, 1]);
+        assert_eq!(
+            indexer.identifier_part_to_file_id_ever_contained["name"],
+            RoaringBitmap::from([0])
+        );
+    }
+
     #[test]
     fn rename_file_test() {
         let mut indexer = GitIndexer::new(GitIndexerConfig {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1320,7 +2008,8 @@ mod index_tree {
                             fst::Set::from_iter(["1", "2", "3"]).unwrap()
                         ),
                         doc_modified_commits: RoaringBitmap::from_iter([0, 1]),
-                        is_deleted: true
+                        is_deleted: true,
+                        tokens: TokenIndex::default(),
                     }
                 ),
                 (
@@ -1368,7 +2057,8 @@ mod index_tree {
                             fst::Set::from_iter(["1", "2", "3"]).unwrap()
                         ),
                         doc_modified_commits: RoaringBitmap::from_iter([1]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: TokenIndex::default(),
                     }
                 )
             ])
@@ -1401,6 +2091,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1484,7 +2175,11 @@ mod index_tree {
                             fst::Set::from_iter(["a", "c"]).unwrap()
                         ),
                         doc_modified_commits: RoaringBitmap::from_iter([0, 2]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: single_part_tokens(&[
+                            ("a", &[], &RoaringBitmap::from([0])),
+                            ("c", &[(2, 0)], &RoaringBitmap::from([2]))
+                        ]),
                     }
                 ),
                 (
@@ -1504,7 +2199,12 @@ mod index_tree {
                         )]),
                         all_words: Some(fst::Set::from_iter(["x"]).unwrap()),
                         doc_modified_commits: RoaringBitmap::from_iter([1]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: single_part_tokens(&[(
+                            "x",
+                            &[(1, 0)],
+                            &RoaringBitmap::from([1, 2])
+                        )]),
                     }
                 )
             ])
@@ -1546,6 +2246,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         });
 
         let repo = init_repo();
@@ -1606,7 +2307,8 @@ mod index_tree {
                         ),]),
                         all_words: Some(fst::Set::from_iter(["1"]).unwrap()),
                         doc_modified_commits: RoaringBitmap::from_iter([0]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: TokenIndex::default(),
                     }
                 ),
                 (
@@ -1624,7 +2326,8 @@ mod index_tree {
                         ),]),
                         all_words: Some(fst::Set::from_iter(["2"]).unwrap()),
                         doc_modified_commits: RoaringBitmap::from_iter([1]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: TokenIndex::default(),
                     }
                 ),
                 (
@@ -1642,7 +2345,8 @@ mod index_tree {
                         ),]),
                         all_words: Some(fst::Set::from_iter(["3"]).unwrap()),
                         doc_modified_commits: RoaringBitmap::from_iter([2]),
-                        is_deleted: false
+                        is_deleted: false,
+                        tokens: TokenIndex::default(),
                     }
                 )
             ])
@@ -1655,6 +2359,7 @@ mod index_tree {
             show_index_progress: false,
             main_branch_name: "main".to_owned(),
             ignore_utf8_error: false,
+            ..Default::default()
         };
 
         let mut indexer = GitIndexer::new(config.clone());
@@ -1699,7 +2404,8 @@ mod index_tree {
                                 fst::Set::from_iter(["1"]).unwrap()
                             ),
                             doc_modified_commits: RoaringBitmap::from_iter([0]),
-                            is_deleted: false
+                            is_deleted: false,
+                            tokens: TokenIndex::default(),
                         }
                     ),
                     (
@@ -1721,7 +2427,8 @@ mod index_tree {
                                 fst::Set::from_iter(["2"]).unwrap()
                             ),
                             doc_modified_commits: RoaringBitmap::from_iter([1]),
-                            is_deleted: false
+                            is_deleted: false,
+                            tokens: TokenIndex::default(),
                         }
                     ),
                 ])
@@ -1770,7 +2477,8 @@ mod index_tree {
                             doc_modified_commits: RoaringBitmap::from_iter([
                                 0, 2
                             ]),
-                            is_deleted: true
+                            is_deleted: true,
+                            tokens: TokenIndex::default(),
                         }
                     ),
                     (
@@ -1793,7 +2501,8 @@ mod index_tree {
                                 fst::Set::from_iter(["2"]).unwrap()
                             ),
                             doc_modified_commits: RoaringBitmap::from_iter([1]),
-                            is_deleted: false
+                            is_deleted: false,
+                            tokens: TokenIndex::default(),
                         }
                     ),
                     (
@@ -1816,11 +2525,378 @@ mod index_tree {
                                 fst::Set::from_iter(["3"]).unwrap()
                             ),
                             doc_modified_commits: RoaringBitmap::from_iter([3]),
-                            is_deleted: false
+                            is_deleted: false,
+                            tokens: TokenIndex::default(),
                         }
                     ),
                 ])
             );
         }
     }
+
+    #[test]
+    fn reuse_tokens_of_known_blob_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::write(repo_path.join("a.txt"), "abcd\nefg\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        // Copy of a.txt.
+        std::fs::write(repo_path.join("b.txt"), "abcd\nefg\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "copy"]);
+
+        std::fs::remove_file(repo_path.join("b.txt")).unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "delete"]);
+
+        std::fs::write(repo_path.join("b.txt"), "abcd\nefg\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "revert"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(indexer.commit_index_to_commit_id.len(), 4);
+        assert_eq!(indexer.blob_token_cache.num_misses, 1);
+        assert_eq!(indexer.blob_token_cache.num_hits, 2);
+
+        let a = &indexer.file_id_to_document[&0];
+        let b = &indexer.file_id_to_document[&1];
+        assert_eq!(
+            a.words.keys().collect::<AHashSet<_>>(),
+            b.words.keys().collect::<AHashSet<_>>()
+        );
+        assert_eq!(
+            b.words[&"abc".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 3,
+                line: 0
+            },])
+        );
+        assert_eq!(
+            b.words[&"efg".into()].commit_inclutivity,
+            RoaringBitmap::from_iter([1, 3])
+        );
+    }
+
+    #[test]
+    fn skip_files_by_filter_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            file_filter: FileFilterConfig {
+                exclude_patterns: vec!["vendor/".to_owned()],
+                max_file_size: Some(100),
+                skip_generated: true,
+                ..Default::default()
+            },
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::create_dir(repo_path.join("vendor")).unwrap();
+        std::fs::write(repo_path.join("vendor/lib.rs"), "abc").unwrap();
+        std::fs::write(repo_path.join("a.rs"), "abc").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("big.txt"), "a".repeat(101)).unwrap();
+        std::fs::write(repo_path.join("gen.rs"), "// @generated\nabc").unwrap();
+        std::fs::write(repo_path.join("b.rs"), "abc").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "second"]);
+
+        // Modifying the skipped file does not index it.
+        std::fs::write(repo_path.join("vendor/lib.rs"), "abcd").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "third"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(indexer.commit_index_to_commit_id.len(), 3);
+        assert_eq!(
+            indexer.file_name_to_id,
+            AHashMap::from_iter([
+                ("a.rs".to_owned(), 0),
+                ("b.rs".to_owned(), 1)
+            ])
+        );
+        assert_eq!(
+            indexer.skipped_files,
+            AHashMap::from_iter([
+                ("vendor/lib.rs".to_owned(), SkipReason::Excluded),
+                ("big.txt".to_owned(), SkipReason::TooLarge { size: 101 }),
+                ("gen.rs".to_owned(), SkipReason::Generated),
+            ])
+        );
+    }
+
+    #[test]
+    fn transcode_non_utf8_files_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        let utf16_le = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+
+        std::fs::write(repo_path.join("u16.txt"), utf16_le("alpha\nbeta\n"))
+            .unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("u16.txt"), utf16_le("alpha\ngamma\n"))
+            .unwrap();
+        std::fs::write(repo_path.join("l1.txt"), b"caf\xe9\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "second"]);
+
+        std::fs::write(repo_path.join("l1.txt"), b"caf\xe9\nna\xefve\n")
+            .unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "third"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(indexer.commit_index_to_commit_id.len(), 3);
+        assert!(indexer.ignored_non_utf8_file_path_set.is_empty());
+        assert_eq!(
+            indexer.file_id_to_encoding,
+            AHashMap::from_iter([
+                (0, TextEncoding::Utf16Le),
+                (1, TextEncoding::Latin1)
+            ])
+        );
+
+        let u16 = &indexer.file_id_to_document[&0];
+        assert_eq!(
+            u16.words[&"alp".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 0,
+                line: 0
+            }])
+        );
+        assert_eq!(
+            u16.words[&"bet".into()].commit_inclutivity,
+            RoaringBitmap::from_iter([0])
+        );
+        assert_eq!(
+            u16.words[&"gam".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 1,
+                line: 1
+            }])
+        );
+
+        let l1 = &indexer.file_id_to_document[&1];
+        assert_eq!(
+            l1.words[&"afé".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 1,
+                line: 0
+            }])
+        );
+        assert_eq!(
+            l1.words[&"aïv".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 2,
+                line: 1
+            }])
+        );
+    }
+
+    #[test]
+    fn text_binary_change_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: true,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        assert_eq!(detect_encoding(&binary_content()), None);
+
+        std::fs::write(repo_path.join("data.txt"), "hello world\n").unwrap();
+        std::fs::write(repo_path.join("image.svg"), binary_content()).unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        // data.txt turns binary and image.svg turns text.
+        std::fs::write(repo_path.join("data.txt"), binary_content()).unwrap();
+        std::fs::write(repo_path.join("image.svg"), "<svg>\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "second"]);
+
+        std::fs::write(repo_path.join("data.txt"), "hello again\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "third"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(indexer.commit_index_to_commit_id.len(), 3);
+        assert!(indexer.ignored_non_utf8_file_path_set.is_empty());
+
+        let data =
+            &indexer.file_id_to_document[&indexer.file_name_to_id["data.txt"]];
+        assert!(!data.is_deleted);
+        assert_eq!(
+            data.words[&"wor".into()].commit_inclutivity,
+            RoaringBitmap::from_iter([0])
+        );
+        assert_eq!(
+            data.words[&"hel".into()].commit_inclutivity,
+            RoaringBitmap::from_iter([0, 2])
+        );
+        assert_eq!(
+            data.words[&"aga".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 2,
+                line: 0
+            }])
+        );
+
+        let image =
+            &indexer.file_id_to_document[&indexer.file_name_to_id["image.svg"]];
+        assert_eq!(
+            image.words[&"svg".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 1,
+                line: 0
+            }])
+        );
+
+        // data.txt is at HEAD again after it turned back into a text.
+        let index = GitIndex::from(indexer);
+        assert!(index.all_paths.contains("data.txt"));
+
+        let outcome = GitSearcher::new((&index).into())
+            .search_files_with_matches(&Query::Plain("again".to_owned()), None)
+            .unwrap();
+        assert_eq!(
+            outcome
+                .files
+                .iter()
+                .map(|f| (f.file_path.as_str(), f.at_head))
+                .collect::<Vec<_>>(),
+            vec![("data.txt", true)]
+        );
+    }
+
+    #[test]
+    fn binary_to_text_follows_filter_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: true,
+            file_filter: FileFilterConfig {
+                exclude_patterns: vec!["fixtures/".to_owned()],
+                ..Default::default()
+            },
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::write(repo_path.join("a.txt"), "abc\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        std::fs::create_dir(repo_path.join("fixtures")).unwrap();
+        std::fs::write(repo_path.join("fixtures/data"), binary_content())
+            .unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "binary"]);
+
+        std::fs::write(repo_path.join("fixtures/data"), "text\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "text"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(indexer.commit_index_to_commit_id.len(), 3);
+        assert!(indexer.ignored_non_utf8_file_path_set.is_empty());
+        assert_eq!(
+            indexer.file_name_to_id,
+            AHashMap::from_iter([("a.txt".to_owned(), 0)])
+        );
+        assert_eq!(
+            indexer.skipped_files,
+            AHashMap::from_iter([(
+                "fixtures/data".to_owned(),
+                SkipReason::Excluded
+            )])
+        );
+    }
+
+    #[test]
+    fn utf8_file_turns_latin1_test() {
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+
+        let repo = init_repo();
+        let repo_path = repo.path();
+
+        std::fs::write(repo_path.join("a.sql"), "select 1;\n").unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "init"]);
+
+        // libgit2 diffs it as a text, but the new line is not in UTF-8.
+        std::fs::write(repo_path.join("a.sql"), b"select 1;\n-- caf\xe9\n")
+            .unwrap();
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "latin1"]);
+
+        let repo = Repository::open(repo_path).unwrap();
+        indexer.index_history(repo).unwrap();
+
+        assert_eq!(
+            indexer.file_id_to_encoding,
+            AHashMap::from_iter([(0, TextEncoding::Latin1)])
+        );
+
+        let document = &indexer.file_id_to_document[&0];
+        assert_eq!(
+            document.words[&"afé".into()].word_history,
+            AHashSet::from_iter([WordKey {
+                commit_id: 1,
+                line: 1
+            }])
+        );
+        assert_eq!(
+            document.words[&"sel".into()].commit_inclutivity,
+            RoaringBitmap::from_iter([0, 1])
+        );
+    }
 }
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+
+use ahash::AHashMap;
+use fst::Map;
+use fst::Set;
+
+use super::document::Document;
+use super::git_indexer::FileId;
+
+// Identifiers shorter than this are not worth completing.
+const MIN_IDENTIFIER_LEN: usize = 3;
+
+// Anything longer is most likely a generated blob (e.g. base64).
+const MAX_IDENTIFIER_LEN: usize = 128;
+
+// Splits the line into every identifier (e.g. `foo_bar`, `FooBar2`, `x`).
+pub fn split_words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| {
+            !token.is_empty()
+                && token.len() <= MAX_IDENTIFIER_LEN
+                && !token.starts_with(|c: char| c.is_ascii_digit())
+        })
+}
+
+// Identifiers that are long enough to be completed.
+pub fn split_identifiers(line: &str) -> impl Iterator<Item = &str> {
+    split_words(line).filter(|token| token.len() >= MIN_IDENTIFIER_LEN)
+}
+
+// Splits the identifier on the camelCase, snake_case and kebab-case
+// boundaries. The parts are lowercased, e.g. `HTTPServer_v2` becomes `http`,
+// `server` and `v2`.
+pub fn split_identifier_parts(identifier: &str) -> Vec<String> {
+    let mut parts = vec![];
+
+    for word in identifier.split(['_', '-']) {
+        let chars = word.char_indices().collect::<Vec<_>>();
+        let mut start = 0;
+
+        for i in 1..chars.len() {
+            let (index, current) = chars[i];
+            let prev = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|(_, c)| *c);
+
+            // `userName` -> `user` | `Name`
+            let lower_to_upper = prev.is_lowercase() && current.is_uppercase();
+            // `HTTPServer` -> `HTTP` | `Server`
+            let acronym_end = prev.is_uppercase()
+                && current.is_uppercase()
+                && next.is_some_and(|c| c.is_lowercase());
+
+            if lower_to_upper || acronym_end {
+                parts.push(word[start..index].to_lowercase());
+                start = index;
+            }
+        }
+
+        if start < word.len() {
+            parts.push(word[start..].to_lowercase());
+        }
+    }
+
+    parts
+}
+
+// Identifier level tokens of the lines, with the line numbers they appear.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IdentifierTokens {
+    pub identifiers: AHashMap<String, Vec<usize>>,
+    pub parts: AHashMap<String, Vec<usize>>,
+}
+
+pub fn split_lines_to_identifier_tokens(
+    lines: &[String],
+    line_start_index: usize,
+) -> IdentifierTokens {
+    let mut identifiers: AHashMap<String, BTreeSet<usize>> = AHashMap::new();
+    let mut parts: AHashMap<String, BTreeSet<usize>> = AHashMap::new();
+
+    for (line_num, line) in lines.iter().enumerate() {
+        let line_num = line_num + line_start_index;
+
+        for identifier in split_words(line) {
+            if let Some(lines) = identifiers.get_mut(identifier) {
+                // Parts are already added for this line.
+                if !lines.insert(line_num) {
+                    continue;
+                }
+            } else {
+                identifiers
+                    .insert(identifier.to_owned(), BTreeSet::from([line_num]));
+            }
+
+            for part in split_identifier_parts(identifier) {
+                parts.entry(part).or_default().insert(line_num);
+            }
+        }
+    }
+
+    let into_vec = |m: AHashMap<String, BTreeSet<usize>>| {
+        m.into_iter()
+            .map(|(token, lines)| (token, lines.into_iter().collect()))
+            .collect()
+    };
+
+    IdentifierTokens {
+        identifiers: into_vec(identifiers),
+        parts: into_vec(parts),
+    }
+}
+
+pub fn add_identifiers(counts: &mut AHashMap<String, u64>, lines: &[String]) {
+    for line in lines {
+        for identifier in split_identifiers(line) {
+            match counts.get_mut(identifier) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(identifier.to_owned(), 1);
+                }
+            }
+        }
+    }
+}
+
+pub fn remove_identifiers(
+    counts: &mut AHashMap<String, u64>,
+    lines: &[String],
+) {
+    for line in lines {
+        for identifier in split_identifiers(line) {
+            if let Some(count) = counts.get_mut(identifier) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    counts.remove(identifier);
+                }
+            }
+        }
+    }
+}
+
+// Maps every identifier at HEAD to the number of its occurrences.
+pub fn build_identifier_map(counts: &AHashMap<String, u64>) -> Map<Vec<u8>> {
+    let mut identifiers = counts
+        .iter()
+        .map(|(identifier, count)| (identifier.as_str(), *count))
+        .collect::<Vec<_>>();
+    identifiers.sort_unstable();
+
+    Map::from_iter(identifiers).expect("keys are sorted and deduplicated")
+}
+
+// Set of every file path that exists at HEAD.
+pub fn build_path_set(
+    file_id_to_path: &[String],
+    file_id_to_document: &AHashMap<FileId, Document>,
+) -> Set<Vec<u8>> {
+    let mut paths = file_id_to_document
+        .iter()
+        .filter(|(_, document)| !document.is_deleted)
+        .filter_map(|(file_id, _)| file_id_to_path.get(*file_id))
+        .collect::<Vec<_>>();
+    paths.sort_unstable();
+    paths.dedup();
+
+    Set::from_iter(paths).expect("paths are sorted and deduplicated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_identifiers_test() {
+        assert_eq!(
+            split_identifiers("let foo_bar = FooBar2::new(1234, x, 2ab);")
+                .collect::<Vec<_>>(),
+            vec!["let", "foo_bar", "FooBar2", "new"]
+        );
+    }
+
+    #[test]
+    fn split_identifier_parts_test() {
+        assert_eq!(
+            split_identifier_parts("getUserName"),
+            ["get", "user", "name"]
+        );
+        assert_eq!(
+            split_identifier_parts("get_user_name"),
+            ["get", "user", "name"]
+        );
+        assert_eq!(
+            split_identifier_parts("HTTPServer_v2"),
+            ["http", "server", "v2"]
+        );
+        assert_eq!(split_identifier_parts("__init__"), ["init"]);
+        assert_eq!(split_identifier_parts("utf8"), ["utf8"]);
+    }
+
+    #[test]
+    fn split_lines_to_identifier_tokens_test() {
+        let tokens = split_lines_to_identifier_tokens(
+            &["getUserName(user)".to_owned(), "user_id".to_owned()],
+            3,
+        );
+
+        assert_eq!(
+            tokens.identifiers,
+            AHashMap::from_iter([
+                ("getUserName".to_owned(), vec![3]),
+                ("user".to_owned(), vec![3]),
+                ("user_id".to_owned(), vec![4]),
+            ])
+        );
+        assert_eq!(
+            tokens.parts,
+            AHashMap::from_iter([
+                ("get".to_owned(), vec![3]),
+                ("user".to_owned(), vec![3, 4]),
+                ("name".to_owned(), vec![3]),
+                ("id".to_owned(), vec![4]),
+            ])
+        );
+    }
+
+    #[test]
+    fn counts_identifiers() {
+        let mut counts = AHashMap::new();
+
+        add_identifiers(
+            &mut counts,
+            &["foo(bar, foo)".to_owned(), "baz foo".to_owned()],
+        );
+        remove_identifiers(&mut counts, &["bar".to_owned()]);
+
+        let map = build_identifier_map(&counts);
+        assert_eq!(map.get("foo"), Some(3));
+        assert_eq!(map.get("baz"), Some(1));
+        assert_eq!(map.get("bar"), None);
+    }
+}
@@ -1,6 +1,11 @@
+mod blob_cache;
 mod check_binary;
 pub mod document;
+pub mod encoding;
+pub mod file_filter;
+pub mod git_attributes;
 pub mod git_index;
 mod git_index_debug;
 pub mod git_index_serialization;
 pub mod git_indexer;
+pub mod identifier;
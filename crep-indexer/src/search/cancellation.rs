@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+// Flag shared between the searcher and whoever issued the search. Clones refer
+// to the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Cancels the token when the returned guard is dropped (e.g. when the
+    // request future that owns it is dropped).
+    pub fn drop_guard(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+}
+
+pub struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+
+        assert!(!cloned.is_cancelled());
+        token.cancel();
+        assert!(cloned.is_cancelled());
+    }
+
+    #[test]
+    fn drop_guard_cancels() {
+        let token = CancellationToken::new();
+        {
+            let _guard = token.drop_guard();
+            assert!(!token.is_cancelled());
+        }
+
+        assert!(token.is_cancelled());
+    }
+}
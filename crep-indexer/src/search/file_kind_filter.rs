@@ -0,0 +1,136 @@
+use roaring::RoaringBitmap;
+
+use crate::index::git_attributes::FileKinds;
+
+// `is:vendored` or `-is:vendored` (and the same for `generated`) in the query.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FileKindFilter {
+    // Some(true) keeps only the vendored files and Some(false) drops them.
+    pub vendored: Option<bool>,
+    pub generated: Option<bool>,
+}
+
+impl FileKindFilter {
+    // Removes the filters from the query. The rest of the query is untouched.
+    pub fn extract(query: &str) -> (String, Self) {
+        let mut filter = Self::default();
+        let mut rest = String::new();
+        let mut found = false;
+
+        for token in query.split_inclusive(char::is_whitespace) {
+            let (negated, term) = match token.trim_end().strip_prefix('-') {
+                Some(term) => (true, term),
+                None => (false, token.trim_end()),
+            };
+
+            match term {
+                "is:vendored" => filter.vendored = Some(!negated),
+                "is:generated" => filter.generated = Some(!negated),
+                _ => {
+                    rest.push_str(token);
+                    continue;
+                }
+            }
+            found = true;
+        }
+
+        match found {
+            true => (rest.trim().to_owned(), filter),
+            false => (query.to_owned(), filter),
+        }
+    }
+
+    // The filters set here take precedence.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            vendored: self.vendored.or(other.vendored),
+            generated: self.generated.or(other.generated),
+        }
+    }
+
+    // Candidates that pass the filter. The vendored and generated files come
+    // after the others, so that they are found last.
+    pub fn order(
+        &self,
+        kinds: &FileKinds,
+        mut docs: RoaringBitmap,
+    ) -> impl Iterator<Item = u32> {
+        match self.vendored {
+            Some(true) => docs &= &kinds.vendored,
+            Some(false) => docs -= &kinds.vendored,
+            None => {}
+        }
+        match self.generated {
+            Some(true) => docs &= &kinds.generated,
+            Some(false) => docs -= &kinds.generated,
+            None => {}
+        }
+
+        let flagged = &docs & (&kinds.vendored | &kinds.generated);
+        docs -= &flagged;
+
+        docs.into_iter().chain(flagged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_filters() {
+        assert_eq!(
+            FileKindFilter::extract("foo  bar"),
+            ("foo  bar".to_owned(), FileKindFilter::default())
+        );
+        assert_eq!(
+            FileKindFilter::extract("foo -is:vendored is:generated bar"),
+            (
+                "foo bar".to_owned(),
+                FileKindFilter {
+                    vendored: Some(false),
+                    generated: Some(true)
+                }
+            )
+        );
+        assert_eq!(
+            FileKindFilter::extract("is:vendored"),
+            (
+                "".to_owned(),
+                FileKindFilter {
+                    vendored: Some(true),
+                    generated: None
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn order_candidates() {
+        let kinds = FileKinds {
+            vendored: RoaringBitmap::from([1, 4]),
+            generated: RoaringBitmap::from([2]),
+        };
+        let docs = RoaringBitmap::from([0, 1, 2, 3]);
+
+        let order = |filter: FileKindFilter| {
+            filter.order(&kinds, docs.clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(FileKindFilter::default()), vec![0, 3, 1, 2]);
+        assert_eq!(
+            order(FileKindFilter {
+                vendored: Some(false),
+                generated: None
+            }),
+            vec![0, 3, 2]
+        );
+        assert_eq!(
+            order(FileKindFilter {
+                vendored: Some(true),
+                generated: None
+            }),
+            vec![1]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use fst::IntoStreamer;
+use fst::Map;
+use fst::automaton::Levenshtein;
+
+// The Levenshtein automaton grows exponentially with the number of edits.
+pub const MAX_FUZZY_EDITS: u32 = 2;
+
+// Searching every spelling of a very common prefix is not useful.
+const MAX_SPELLINGS: usize = 64;
+
+// Short terms are already ambiguous within a single edit.
+pub fn default_max_edits(term: &str) -> u32 {
+    if term.chars().count() < 5 { 1 } else { 2 }
+}
+
+// Returns the identifiers within `max_edits` of the term, most frequent first.
+pub fn find_fuzzy_identifiers(
+    identifiers: &Map<Vec<u8>>,
+    term: &str,
+    max_edits: u32,
+) -> Result<Vec<String>, String> {
+    if max_edits > MAX_FUZZY_EDITS {
+        return Err(format!(
+            "max_edits must be at most {MAX_FUZZY_EDITS} (was {max_edits})"
+        ));
+    }
+
+    let automaton = Levenshtein::new(term, max_edits)
+        .map_err(|e| format!("Unable to build the automaton: {e}"))?;
+
+    let mut spellings = identifiers
+        .search(automaton)
+        .into_stream()
+        .into_str_vec()
+        .map_err(|e| e.to_string())?;
+
+    spellings.sort_by(|(a, a_count), (b, b_count)| {
+        b_count.cmp(a_count).then_with(|| a.cmp(b))
+    });
+    spellings.truncate(MAX_SPELLINGS);
+
+    Ok(spellings
+        .into_iter()
+        .map(|(spelling, _)| spelling)
+        .collect())
+}
+
+// Regex that matches any of the spellings as a whole identifier.
+pub fn build_spellings_regex(spellings: &[String]) -> String {
+    format!(
+        r"\b(?:{})\b",
+        spellings
+            .iter()
+            .map(|s| regex::escape(s))
+            .collect::<Vec<_>>()
+            .join("|")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_identifiers_within_edits() {
+        let identifiers = Map::from_iter([
+            ("receive_buffer", 3),
+            ("receive_buffers", 10),
+            ("recv_buffer", 1),
+            ("send_buffer", 5),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            find_fuzzy_identifiers(&identifiers, "recieve_buffer", 2).unwrap(),
+            vec!["receive_buffer"]
+        );
+        assert_eq!(
+            find_fuzzy_identifiers(&identifiers, "receive_buffer", 1).unwrap(),
+            vec!["receive_buffers", "receive_buffer"]
+        );
+        assert!(find_fuzzy_identifiers(&identifiers, "abc", 3).is_err());
+    }
+
+    #[test]
+    fn spellings_regex_matches_whole_identifiers() {
+        let regex = regex::Regex::new(&build_spellings_regex(&[
+            "foo".to_owned(),
+            "a.b".to_owned(),
+        ]))
+        .unwrap();
+
+        assert!(regex.is_match("let x = foo;"));
+        assert!(regex.is_match("a.b"));
+        assert!(!regex.is_match("foobar"));
+        assert!(!regex.is_match("axb"));
+    }
+}
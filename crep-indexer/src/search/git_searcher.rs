@@ -1,6 +1,6 @@
 use std::time::Instant;
 
-use anyhow::anyhow;
+use ahash::AHashMap;
 use log::debug;
 use log::info;
 use log::trace;
@@ -9,13 +9,23 @@ use regex_syntax::hir::HirKind;
 use roaring::RoaringBitmap;
 use trigram_hash::trigram_hash::split_lines_to_token_set;
 
+use crate::index::document::Document;
 use crate::index::git_index::GitIndexRef;
 use crate::index::git_indexer::FileId;
+use crate::index::identifier::split_identifier_parts;
+use crate::index::identifier::split_words;
+use crate::search::cancellation::CancellationToken;
 use crate::search::core::search_docs::find_all_words_containing_key;
 use crate::search::core::search_docs::find_matching_commit_histories_in_doc;
 use crate::search::core::search_docs::find_matching_commit_histories_in_doc_from_trigrams;
 use crate::search::core::search_docs::find_matching_trigram;
+use crate::search::file_kind_filter::FileKindFilter;
+use crate::search::fuzzy::build_spellings_regex;
+use crate::search::fuzzy::find_fuzzy_identifiers;
 use crate::search::permutation::PermutationIterator;
+use crate::search::summary::FileWithMatches;
+use crate::search::summary::MatchCount;
+use crate::search::summary::collect_files_with_matches;
 use crate::util::bitmap::utils::intersect_bitmap_vec;
 use crate::util::bitmap::utils::intersect_bitmaps;
 use crate::util::bitmap::utils::union_bitmaps;
@@ -23,20 +33,106 @@ use crate::util::bitmap::utils::union_bitmaps;
 use super::regex_search::RegexSearchCandidates;
 use super::regex_search::SearchPartTrigram;
 use super::regex_search::Trigram;
+use super::regex_search::can_match_newline;
+
+// Number of the lines shown before and after the match by default.
+pub const DEFAULT_CONTEXT_LINES: usize = 2;
 
 pub struct GitSearcher<'i> {
     index: GitIndexRef<'i>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct SearchOption {
     pub max_num_to_find: Option<usize>,
+
+    // Only matches the terms at the word boundaries, e.g. `id` does not match
+    // `valid`.
+    pub whole_word: bool,
+
+    // Regex queries run over the whole file, so that a match can span
+    // multiple lines. `^` and `$` still match at the line boundaries.
+    pub multiline: bool,
+
+    // Every match in the file is shown, rather than the first occurrence of
+    // each word (or the first few matching lines of the regex).
+    pub all_matches: bool,
+
+    // Number of the lines shown before and after every match. Defaults to
+    // `DEFAULT_CONTEXT_LINES`.
+    pub before_context: Option<usize>,
+    pub after_context: Option<usize>,
+
+    // The search stops at the deadline (or when the token is cancelled) and
+    // returns what it has found so far.
+    pub deadline: Option<Instant>,
+    pub cancellation: Option<CancellationToken>,
+
+    // Also set by `is:vendored` and the like in the plain query.
+    pub file_kinds: FileKindFilter,
+}
+
+impl SearchOption {
+    pub fn before_context(&self) -> usize {
+        self.before_context.unwrap_or(DEFAULT_CONTEXT_LINES)
+    }
+
+    pub fn after_context(&self) -> usize {
+        self.after_context.unwrap_or(DEFAULT_CONTEXT_LINES)
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchOutcome {
+    pub results: Vec<RawPerFileSearchResult>,
+
+    // Set when the search was interrupted before checking every candidate.
+    pub truncated: bool,
+}
+
+impl SearchOutcome {
+    fn complete(results: Vec<RawPerFileSearchResult>) -> Self {
+        Self {
+            results,
+            truncated: false,
+        }
+    }
+
+    fn truncated(results: Vec<RawPerFileSearchResult>) -> Self {
+        Self {
+            results,
+            truncated: true,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilesWithMatchesOutcome {
+    pub files: Vec<FileWithMatches>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct CountOutcome {
+    pub count: MatchCount,
+    pub truncated: bool,
 }
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum Query {
     Plain(String),
     Regex(String),
+    // Identifiers within `max_edits` (Levenshtein distance) of the term.
+    Fuzzy { term: String, max_edits: u32 },
 }
 
 impl<'i> GitSearcher<'i> {
@@ -48,45 +144,239 @@ impl<'i> GitSearcher<'i> {
         &self,
         query: &Query,
         option: Option<SearchOption>,
-    ) -> Result<Vec<RawPerFileSearchResult>, String> {
-        match query {
+    ) -> Result<SearchOutcome, String> {
+        let (query, option) = extract_file_kind_filter(query, option);
+
+        match &query {
             Query::Plain(p) => Ok(self.raw_word_search(p, option)),
-            Query::Regex(r) => self.regex_search(r, option),
+            Query::Regex(r) => {
+                let mut regex = r.to_owned();
+                if let Some(option) = &option {
+                    if option.whole_word {
+                        regex = format!(r"\b(?:{regex})\b");
+                    }
+                    if option.multiline {
+                        regex = format!("(?m){regex}");
+                    }
+                }
+
+                self.regex_search(&regex, option)
+            }
+            Query::Fuzzy { term, max_edits } => {
+                self.fuzzy_search(term, *max_edits, option)
+            }
+        }
+    }
+
+    // Files that may contain the query, answered from the index alone. Like
+    // the raw results, these are the candidates that are not verified
+    // against the file contents (see `SearchResult::verify`).
+    pub fn search_files_with_matches(
+        &self,
+        query: &Query,
+        option: Option<SearchOption>,
+    ) -> Result<FilesWithMatchesOutcome, String> {
+        let outcome = self.search(query, option)?;
+
+        Ok(FilesWithMatchesOutcome {
+            files: collect_files_with_matches(&self.index, &outcome.results),
+            truncated: outcome.truncated,
+        })
+    }
+
+    pub fn search_count(
+        &self,
+        query: &Query,
+        option: Option<SearchOption>,
+    ) -> Result<CountOutcome, String> {
+        let outcome = self.search_files_with_matches(query, option)?;
+
+        Ok(CountOutcome {
+            count: MatchCount::new(&outcome.files),
+            truncated: outcome.truncated,
+        })
+    }
+
+    // Only the identifiers that exist at HEAD are considered as the
+    // candidate spellings, but they are searched over the entire history.
+    fn fuzzy_search(
+        &self,
+        term: &str,
+        max_edits: u32,
+        option: Option<SearchOption>,
+    ) -> Result<SearchOutcome, String> {
+        if term.is_empty() {
+            return Ok(SearchOutcome::default());
+        }
+
+        let spellings =
+            find_fuzzy_identifiers(self.index.identifiers, term, max_edits)?;
+        debug!("Fuzzy spellings of {term}: {spellings:?}");
+
+        // Documents that contain the trigrams of any of the spellings.
+        let docs_per_spelling = spellings
+            .iter()
+            .filter_map(|spelling| {
+                self.get_document_bitmap_containing_word(spelling)
+            })
+            .collect::<Vec<_>>();
+
+        if docs_per_spelling.is_empty() {
+            return Ok(SearchOutcome::default());
+        }
+
+        let candidate_docs = union_bitmaps(
+            &docs_per_spelling.iter().map(|(_, b)| b).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        // Highlights the actual spelling that was matched.
+        let query = MatchedQuery::Regex(build_spellings_regex(&spellings));
+        let head_commit_index = self.index.commit_index_to_commit_id.len() - 1;
+
+        let mut result = vec![];
+        let option = option.unwrap_or_default();
+
+        for file_id in option
+            .file_kinds
+            .order(self.index.file_kinds, candidate_docs)
+        {
+            if option.is_interrupted() {
+                return Ok(SearchOutcome::truncated(result));
+            }
+
+            let Some(document) =
+                self.index.file_id_to_document.get(&(file_id as FileId))
+            else {
+                continue;
+            };
+
+            let commits_with_any_spelling = docs_per_spelling
+                .iter()
+                .filter(|(_, docs)| docs.contains(file_id))
+                .flat_map(|(spelling, _)| {
+                    find_matching_commit_histories_in_doc(document, spelling)
+                })
+                .map(|(_, commits)| commits)
+                .collect::<Vec<_>>();
+
+            if commits_with_any_spelling.is_empty() {
+                continue;
+            }
+
+            let mut doc_modified_commits =
+                document.doc_modified_commits.clone();
+            if !document.is_deleted {
+                doc_modified_commits.insert(head_commit_index as u32);
+            }
+
+            let overlapped_commits = &doc_modified_commits
+                & union_bitmaps(
+                    &commits_with_any_spelling.iter().collect::<Vec<_>>(),
+                )
+                .unwrap();
+
+            if overlapped_commits.is_empty() {
+                continue;
+            }
+
+            result.push(RawPerFileSearchResult {
+                query: query.clone(),
+                file_id,
+                overlapped_commits,
+            });
+
+            if let Some(max_num_to_find) = option.max_num_to_find
+                && result.len() >= max_num_to_find
+            {
+                break;
+            }
         }
+
+        Ok(SearchOutcome::complete(result))
     }
 
     fn raw_word_search(
         &self,
         query: &str,
         option: Option<SearchOption>,
-    ) -> Vec<RawPerFileSearchResult> {
+    ) -> SearchOutcome {
         if query.is_empty() {
-            return vec![];
+            return SearchOutcome::default();
         }
 
-        let words = query.split_whitespace();
-        let mut documents_containing_each_word: Vec<(String, RoaringBitmap)> =
-            vec![];
-
-        for word in words {
-            let results = self.get_document_bitmap_containing_word(word);
+        let whole_word = option.as_ref().is_some_and(|o| o.whole_word);
+        let mut documents_containing_each_term: Vec<(
+            PlainTerm,
+            RoaringBitmap,
+        )> = vec![];
+
+        for term in query
+            .split_whitespace()
+            .map(|term| PlainTerm::parse(term, whole_word))
+        {
+            let results = self.get_document_bitmap_containing_term(&term);
             if results.is_none() {
-                return vec![];
+                return SearchOutcome::default();
             }
 
-            documents_containing_each_word.push(results.unwrap());
+            documents_containing_each_term.push((term, results.unwrap()));
         }
 
-        self.find_overlapping_document(&documents_containing_each_word, option)
+        self.find_overlapping_document(&documents_containing_each_term, option)
+    }
+
+    fn get_document_bitmap_containing_term(
+        &self,
+        term: &PlainTerm,
+    ) -> Option<RoaringBitmap> {
+        match term {
+            PlainTerm::Text(text) => {
+                let containing_text = self
+                    .get_document_bitmap_containing_word(text)
+                    .map(|(_, docs)| docs);
+
+                let containing_part = term.identifier_part().and_then(|part| {
+                    self.index
+                        .identifier_part_to_file_id_ever_contained
+                        .get(&part)
+                });
+
+                match (containing_text, containing_part) {
+                    (Some(text_docs), Some(part_docs)) => {
+                        Some(text_docs | part_docs)
+                    }
+                    (Some(docs), None) => Some(docs),
+                    (None, Some(docs)) => Some(docs.clone()),
+                    (None, None) => None,
+                }
+            }
+            // The trigrams and the parts of the identifier narrow down the
+            // documents. The whole identifier is checked per document.
+            PlainTerm::Identifier(identifier) => {
+                let mut docs = self
+                    .get_document_bitmap_containing_word(identifier)
+                    .map(|(_, docs)| docs)?;
+
+                for part in split_identifier_parts(identifier) {
+                    docs &= self
+                        .index
+                        .identifier_part_to_file_id_ever_contained
+                        .get(&part)?;
+                }
+
+                Some(docs)
+            }
+        }
     }
 
     fn regex_search(
         &self,
         query: &str,
         option: Option<SearchOption>,
-    ) -> Result<Vec<RawPerFileSearchResult>, String> {
+    ) -> Result<SearchOutcome, String> {
         if query.is_empty() {
-            return Ok(vec![]);
+            return Ok(SearchOutcome::default());
         }
 
         let parse_start = Instant::now();
@@ -101,20 +391,31 @@ impl<'i> GitSearcher<'i> {
         let hir = hir.unwrap();
         debug!("Hir : {hir:?}");
 
+        let option = option.unwrap_or_default();
         let candidates = self
-            .build_candidates_from_hir(&hir)
+            .build_candidates_from_hir(&hir, option.multiline)
             .map_err(|e| format!("Error building candidates {e:?}"))?;
 
         trace!("Candiates: {candidates:?}");
 
         let candidate_build_done = Instant::now();
 
-        let mut search_result = vec![];
-        let option = option.unwrap_or_default();
+        let matched_query = match option.multiline {
+            true => MatchedQuery::MultilineRegex(query.to_owned()),
+            false => MatchedQuery::Regex(query.to_owned()),
+        };
+        let mut search_result: Vec<RawPerFileSearchResult> = vec![];
+        // Several candidates (e.g. of the alternations or repetitions) can
+        // match the same document.
+        let mut doc_to_result_index: AHashMap<u32, usize> = AHashMap::new();
 
         for cand in candidates.candidates {
             trace!("Checking candidate: {cand:?}");
 
+            if option.is_interrupted() {
+                return Ok(SearchOutcome::truncated(search_result));
+            }
+
             let trigrams = cand.trigrams;
 
             if trigrams.is_empty() {
@@ -126,7 +427,7 @@ impl<'i> GitSearcher<'i> {
 
             for trigram in &trigrams {
                 let fetched_trigrams =
-                    find_matching_trigram(trigram, &self.index.all_words)
+                    find_matching_trigram(trigram, self.index.all_words)
                         .map_err(|e| e.to_string())?;
 
                 let docs_that_contained_matching_trigrams = fetched_trigrams
@@ -162,7 +463,14 @@ impl<'i> GitSearcher<'i> {
             let candidate_docs = intersect_bitmap_vec(docs_bitmaps).unwrap();
             trace!("Found candidate docs: {candidate_docs:?}");
 
-            for doc_id in candidate_docs {
+            for doc_id in option
+                .file_kinds
+                .order(self.index.file_kinds, candidate_docs)
+            {
+                if option.is_interrupted() {
+                    return Ok(SearchOutcome::truncated(search_result));
+                }
+
                 let doc =
                     self.index.file_id_to_document.get(&(doc_id as FileId));
 
@@ -180,16 +488,22 @@ impl<'i> GitSearcher<'i> {
                     .map_err(|e| e.to_string())?;
 
                 if let Some(history) = matching_history {
+                    if let Some(index) = doc_to_result_index.get(&doc_id) {
+                        search_result[*index].overlapped_commits |= history;
+                        continue;
+                    }
+
+                    doc_to_result_index.insert(doc_id, search_result.len());
                     search_result.push(RawPerFileSearchResult {
                         file_id: doc_id,
-                        query: MatchedQuery::Regex(query.to_owned()),
+                        query: matched_query.clone(),
                         overlapped_commits: history,
                     });
 
                     if let Some(max_num_to_find) = option.max_num_to_find
                         && search_result.len() >= max_num_to_find
                     {
-                        return Ok(search_result);
+                        return Ok(SearchOutcome::complete(search_result));
                     }
                 }
             }
@@ -204,13 +518,19 @@ impl<'i> GitSearcher<'i> {
                 .as_millis()
         );
 
-        Ok(search_result)
+        Ok(SearchOutcome::complete(search_result))
     }
 
     fn build_candidates_from_hir(
         &self,
         hir: &Hir,
+        multiline: bool,
     ) -> anyhow::Result<RegexSearchCandidates> {
+        // The index only has the trigrams within a line.
+        if multiline && can_match_newline(hir) {
+            return self.build_multiline_candidates_from_hir(hir);
+        }
+
         match hir.kind() {
             HirKind::Empty => Ok(RegexSearchCandidates { candidates: vec![] }),
             HirKind::Literal(literal) => {
@@ -230,7 +550,7 @@ impl<'i> GitSearcher<'i> {
             }
             HirKind::Repetition(repetition) => {
                 let candidate =
-                    self.build_candidates_from_hir(&repetition.sub)?;
+                    self.build_candidates_from_hir(&repetition.sub, multiline)?;
                 Ok(RegexSearchCandidates::repeat(
                     &candidate,
                     repetition.min,
@@ -240,7 +560,7 @@ impl<'i> GitSearcher<'i> {
             HirKind::Concat(hirs) => {
                 let candidates: Vec<RegexSearchCandidates> = hirs
                     .iter()
-                    .map(|hir| self.build_candidates_from_hir(hir))
+                    .map(|hir| self.build_candidates_from_hir(hir, multiline))
                     .collect::<Result<_, _>>()?;
 
                 Ok(RegexSearchCandidates::concat(&candidates))
@@ -248,7 +568,7 @@ impl<'i> GitSearcher<'i> {
             HirKind::Alternation(hirs) => {
                 let candidates: Vec<RegexSearchCandidates> = hirs
                     .iter()
-                    .map(|hir| self.build_candidates_from_hir(hir))
+                    .map(|hir| self.build_candidates_from_hir(hir, multiline))
                     .collect::<Result<_, _>>()?;
 
                 Ok(RegexSearchCandidates::alternation(&candidates))
@@ -258,9 +578,85 @@ impl<'i> GitSearcher<'i> {
                     trigrams: vec![Trigram::from(class)],
                 }],
             }),
-            HirKind::Capture(_) | HirKind::Look(_) => {
-                Err(anyhow!("Do not use capture or look"))
+            // Zero width assertions (e.g. `\b`, `^`) do not add any trigram.
+            HirKind::Look(_) => Ok(RegexSearchCandidates {
+                candidates: vec![SearchPartTrigram { trigrams: vec![] }],
+            }),
+            // Groups do not change what is matched.
+            HirKind::Capture(capture) => {
+                self.build_candidates_from_hir(&capture.sub, multiline)
+            }
+        }
+    }
+
+    // Candidates of the part of the regex that can span multiple lines. Only
+    // the pieces that stay within a line narrow down the documents.
+    fn build_multiline_candidates_from_hir(
+        &self,
+        hir: &Hir,
+    ) -> anyhow::Result<RegexSearchCandidates> {
+        match hir.kind() {
+            HirKind::Literal(literal) => {
+                let pieces = std::str::from_utf8(&literal.0)?
+                    .split('\n')
+                    .filter(|piece| !piece.is_empty())
+                    .map(|piece| {
+                        self.build_candidates_from_hir(
+                            &Hir::literal(piece.as_bytes()),
+                            false,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(RegexSearchCandidates::all_of(&pieces))
+            }
+            HirKind::Concat(hirs) => {
+                let mut pieces = vec![];
+                // Consecutive parts within a line are concatenated as usual.
+                let mut within_line = vec![];
+
+                for hir in hirs {
+                    if !can_match_newline(hir) {
+                        within_line.push(hir.clone());
+                        continue;
+                    }
+
+                    if !within_line.is_empty() {
+                        pieces.push(self.build_candidates_from_hir(
+                            &Hir::concat(std::mem::take(&mut within_line)),
+                            false,
+                        )?);
+                    }
+
+                    pieces.push(self.build_multiline_candidates_from_hir(hir)?);
+                }
+
+                if !within_line.is_empty() {
+                    pieces.push(self.build_candidates_from_hir(
+                        &Hir::concat(within_line),
+                        false,
+                    )?);
+                }
+
+                Ok(RegexSearchCandidates::all_of(&pieces))
+            }
+            HirKind::Alternation(hirs) => {
+                let candidates: Vec<RegexSearchCandidates> = hirs
+                    .iter()
+                    .map(|hir| self.build_candidates_from_hir(hir, true))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(RegexSearchCandidates::alternation(&candidates))
+            }
+            HirKind::Capture(capture) => {
+                self.build_candidates_from_hir(&capture.sub, true)
             }
+            HirKind::Repetition(repetition) if repetition.min > 0 => {
+                self.build_candidates_from_hir(&repetition.sub, true)
+            }
+            // Classes with the newline and optional repetitions can match
+            // anything.
+            _ => Ok(RegexSearchCandidates::all_of(&[])),
         }
     }
 
@@ -307,9 +703,9 @@ impl<'i> GitSearcher<'i> {
 
     fn find_overlapping_document(
         &self,
-        bitmaps: &[(String, RoaringBitmap)],
+        bitmaps: &[(PlainTerm, RoaringBitmap)],
         option: Option<SearchOption>,
-    ) -> Vec<RawPerFileSearchResult> {
+    ) -> SearchOutcome {
         let mut result = vec![];
 
         let docs_for_each_word =
@@ -317,7 +713,10 @@ impl<'i> GitSearcher<'i> {
         let intersected_docs = intersect_bitmaps(&docs_for_each_word).unwrap();
         let option = option.unwrap_or_default();
 
-        for file_id in intersected_docs {
+        for file_id in option
+            .file_kinds
+            .order(self.index.file_kinds, intersected_docs)
+        {
             let document =
                 self.index.file_id_to_document.get(&(file_id as FileId));
 
@@ -328,12 +727,17 @@ impl<'i> GitSearcher<'i> {
             let document = document.unwrap();
             let commit_histories_per_word = bitmaps
                 .iter()
-                .map(|(word, _)| {
-                    find_matching_commit_histories_in_doc(document, word)
-                })
+                .map(|(term, _)| term.find_commit_histories_in_doc(document))
                 .collect::<Vec<_>>();
 
             debug!("commit histories {commit_histories_per_word:?}");
+
+            // The trigrams matched, but a `word:` term is not a whole
+            // identifier in this document.
+            if commit_histories_per_word.iter().any(|h| h.is_empty()) {
+                continue;
+            }
+
             let permutations = PermutationIterator::new(
                 &commit_histories_per_word
                     .iter()
@@ -357,6 +761,12 @@ impl<'i> GitSearcher<'i> {
             for permutation in permutations {
                 debug!("Permutations {permutation:?}");
 
+                // The number of permutations grows exponentially with the
+                // number of words.
+                if option.is_interrupted() {
+                    return SearchOutcome::truncated(result);
+                }
+
                 let mut selected_words = vec![];
                 let mut selected_bitmaps = vec![doc_modified_commits];
 
@@ -384,7 +794,10 @@ impl<'i> GitSearcher<'i> {
                 }
 
                 result.push(RawPerFileSearchResult {
-                    query: MatchedQuery::Words(selected_words),
+                    query: TermMatch::into_matched_query(
+                        selected_words,
+                        option.whole_word,
+                    ),
                     file_id,
                     overlapped_commits,
                 });
@@ -392,19 +805,191 @@ impl<'i> GitSearcher<'i> {
                 if let Some(max_num_to_find) = option.max_num_to_find
                     && result.len() >= max_num_to_find
                 {
-                    return result;
+                    return SearchOutcome::complete(result);
                 }
             }
         }
 
-        result
+        SearchOutcome::complete(result)
+    }
+}
+
+// Whitespace separated term of the plain query.
+#[derive(Debug)]
+enum PlainTerm<'q> {
+    // Matches the text anywhere, or an identifier part of the same spelling
+    // (case insensitive). E.g. `name` matches `getUserName`.
+    Text(&'q str),
+    // `word:getUserName` only matches the whole identifier.
+    Identifier(&'q str),
+}
+
+impl<'q> PlainTerm<'q> {
+    fn parse(term: &'q str, whole_word: bool) -> Self {
+        match term.strip_prefix("word:") {
+            Some(identifier) if !identifier.is_empty() => {
+                PlainTerm::Identifier(identifier)
+            }
+            // The whole word is the same as the whole identifier, which is
+            // tracked per commit.
+            _ if whole_word && split_words(term).eq([term]) => {
+                PlainTerm::Identifier(term)
+            }
+            _ => PlainTerm::Text(term),
+        }
+    }
+
+    // The lowercased term if it can be a part of an identifier.
+    fn identifier_part(&self) -> Option<String> {
+        match self {
+            PlainTerm::Text(text) => match split_identifier_parts(text)
+                .as_slice()
+            {
+                [part] if split_words(text).eq([*text]) => Some(part.clone()),
+                _ => None,
+            },
+            PlainTerm::Identifier(_) => None,
+        }
+    }
+
+    fn find_commit_histories_in_doc(
+        &self,
+        document: &Document,
+    ) -> Vec<(TermMatch, RoaringBitmap)> {
+        match self {
+            PlainTerm::Text(text) => {
+                let histories =
+                    find_matching_commit_histories_in_doc(document, text);
+
+                let part_history = self.identifier_part().and_then(|part| {
+                    document
+                        .tokens
+                        .parts
+                        .get(&part)
+                        .map(|index| (part, &index.commit_inclutivity))
+                });
+
+                // Text and part matches are merged so that the document is
+                // not reported twice.
+                match (histories.into_iter().next(), part_history) {
+                    (Some((text, commits)), None) => {
+                        vec![(TermMatch::Text(text), commits)]
+                    }
+                    // Most of the time the part is spelled the same as the
+                    // text.
+                    (Some((text, commits)), Some((_, part_commits)))
+                        if part_commits.is_subset(&commits) =>
+                    {
+                        vec![(TermMatch::Text(text), commits)]
+                    }
+                    (Some((text, commits)), Some((part, part_commits))) => {
+                        vec![(
+                            TermMatch::TextOrIdentifierPart(text, part),
+                            commits | part_commits,
+                        )]
+                    }
+                    (None, Some((part, part_commits))) => {
+                        vec![(
+                            TermMatch::IdentifierPart(part),
+                            part_commits.clone(),
+                        )]
+                    }
+                    (None, None) => vec![],
+                }
+            }
+            PlainTerm::Identifier(identifier) => document
+                .tokens
+                .identifiers
+                .get(*identifier)
+                .map(|index| {
+                    vec![(
+                        TermMatch::Identifier(identifier.to_string()),
+                        index.commit_inclutivity.clone(),
+                    )]
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// How a term of the plain query matched the document.
+#[derive(Debug, Clone)]
+enum TermMatch {
+    Text(String),
+    Identifier(String),
+    IdentifierPart(String),
+    TextOrIdentifierPart(String, String),
+}
+
+impl TermMatch {
+    // Plain text matches are looked up as is. Otherwise the matches are
+    // highlighted by the regex that follows the identifier boundaries.
+    fn into_matched_query(
+        matches: Vec<TermMatch>,
+        whole_word: bool,
+    ) -> MatchedQuery {
+        // Identifier parts are not looked up for the whole words.
+        if whole_word {
+            return MatchedQuery::WholeWords(
+                matches
+                    .into_iter()
+                    .map(|m| match m {
+                        TermMatch::Text(word) | TermMatch::Identifier(word) => {
+                            word
+                        }
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            );
+        }
+
+        if matches.iter().all(|m| matches!(m, TermMatch::Text(_))) {
+            return MatchedQuery::Words(
+                matches
+                    .into_iter()
+                    .map(|m| match m {
+                        TermMatch::Text(text) => text,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            );
+        }
+
+        MatchedQuery::Patterns(
+            matches
+                .iter()
+                .map(|m| match m {
+                    TermMatch::Text(text) => regex::escape(text),
+                    TermMatch::Identifier(identifier) => {
+                        format!(r"\b{}\b", regex::escape(identifier))
+                    }
+                    TermMatch::IdentifierPart(part) => {
+                        format!("(?i:{})", regex::escape(part))
+                    }
+                    TermMatch::TextOrIdentifierPart(text, part) => {
+                        format!(
+                            "{}|(?i:{})",
+                            regex::escape(text),
+                            regex::escape(part)
+                        )
+                    }
+                })
+                .collect(),
+        )
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum MatchedQuery {
     Words(Vec<String>),
+    // Every word must be found at the word boundaries.
+    WholeWords(Vec<String>),
     Regex(String),
+    // Every regex must match in the file, e.g. one per term that matches the
+    // text or an identifier part.
+    Patterns(Vec<String>),
+    // Matched against the whole file rather than line by line.
+    MultilineRegex(String),
 }
 
 #[derive(Debug, Clone)]
@@ -413,3 +998,409 @@ pub struct RawPerFileSearchResult {
     pub file_id: u32,
     pub overlapped_commits: RoaringBitmap,
 }
+
+// Moves `is:vendored` and the like from the plain query to the option. Regex
+// queries are left as is, since any whitespace in them is significant; they
+// filter the files through `SearchOption::file_kinds` instead. Fuzzy terms are
+// single identifiers, so they never have the filters.
+fn extract_file_kind_filter(
+    query: &Query,
+    option: Option<SearchOption>,
+) -> (Query, Option<SearchOption>) {
+    let (query, file_kinds) = match query {
+        Query::Plain(p) => {
+            let (p, file_kinds) = FileKindFilter::extract(p);
+            (Query::Plain(p), file_kinds)
+        }
+        Query::Regex(_) | Query::Fuzzy { .. } => {
+            return (query.clone(), option);
+        }
+    };
+
+    if file_kinds == FileKindFilter::default() {
+        return (query, option);
+    }
+
+    let mut option = option.unwrap_or_default();
+    option.file_kinds = option.file_kinds.or(file_kinds);
+
+    (query, Some(option))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use git2::Repository;
+
+    use crate::index::file_filter::SkipReason;
+    use crate::index::git_index::GitIndex;
+    use crate::index::git_indexer::GitIndexer;
+    use crate::index::git_indexer::GitIndexerConfig;
+
+    use super::*;
+
+    fn run(cwd: &Path, args: &[&str]) {
+        let status = std::process::Command::new(args[0])
+            .args(&args[1..])
+            .current_dir(cwd)
+            .output()
+            .expect("spawn ok")
+            .status;
+
+        assert!(status.success(), "{args:?} failed");
+    }
+
+    // Builds an index of a repo that has a single commit with the given files.
+    fn build_index(files: &[(&str, &str)]) -> GitIndex {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+
+        run(root, &["git", "init", "-b", "main"]);
+        run(
+            root,
+            &["git", "config", "--local", "user.email", "jaebum@test.com"],
+        );
+        run(root, &["git", "config", "--local", "user.name", "Jaebum"]);
+
+        for (path, content) in files {
+            std::fs::write(root.join(path), content).unwrap();
+        }
+
+        run(root, &["git", "add", "."]);
+        run(root, &["git", "commit", "-m", "init"]);
+
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+        indexer
+            .index_history(Repository::open(root).unwrap())
+            .unwrap();
+
+        indexer.into()
+    }
+
+    fn sample_index() -> GitIndex {
+        build_index(&[
+            ("a.txt", "hello world\nfoo bar"),
+            ("b.txt", "hello there\nworld"),
+        ])
+    }
+
+    #[test]
+    fn files_with_matches_and_count() {
+        let index = build_index(&[
+            ("a.txt", "hello world\nhello"),
+            ("b.txt", "hello there"),
+            ("c.txt", "world"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search_files_with_matches(&Query::Plain("hello".to_owned()), None)
+            .unwrap();
+        assert_eq!(
+            outcome
+                .files
+                .iter()
+                .map(|f| (f.file_path.as_str(), f.at_head))
+                .collect::<Vec<_>>(),
+            vec![("a.txt", true), ("b.txt", true)]
+        );
+
+        let outcome = searcher
+            .search_count(&Query::Plain("hello".to_owned()), None)
+            .unwrap();
+        assert_eq!(
+            outcome.count,
+            MatchCount {
+                num_files: 2,
+                num_files_at_head: 2,
+                num_commits: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn search_without_interruption_is_complete() {
+        let index = sample_index();
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search(&Query::Plain("hello".to_owned()), None)
+            .unwrap();
+
+        assert!(!outcome.truncated);
+        assert_eq!(outcome.results.len(), 2);
+    }
+
+    #[test]
+    fn cancelled_search_is_truncated() {
+        let index = sample_index();
+        let searcher = GitSearcher::new((&index).into());
+
+        let token = CancellationToken::new();
+        token.cancel();
+
+        for query in [
+            Query::Plain("hello".to_owned()),
+            Query::Regex("hel+o".to_owned()),
+            Query::Fuzzy {
+                term: "helo".to_owned(),
+                max_edits: 1,
+            },
+        ] {
+            let outcome = searcher
+                .search(
+                    &query,
+                    Some(SearchOption {
+                        cancellation: Some(token.clone()),
+                        ..Default::default()
+                    }),
+                )
+                .unwrap();
+
+            assert!(outcome.truncated, "{query:?}");
+            assert!(outcome.results.is_empty(), "{query:?}");
+        }
+    }
+
+    #[test]
+    fn expired_deadline_truncates_search() {
+        let index = sample_index();
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search(
+                &Query::Plain("hello world".to_owned()),
+                Some(SearchOption {
+                    deadline: Some(Instant::now() - Duration::from_secs(1)),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+
+        assert!(outcome.truncated);
+        assert!(outcome.results.is_empty());
+    }
+
+    #[test]
+    fn fuzzy_search_finds_misspelled_identifier() {
+        let index = build_index(&[
+            ("a.rs", "let receive_buffer = vec![];"),
+            ("b.rs", "let send_buffer = vec![];"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search(
+                &Query::Fuzzy {
+                    term: "recieve_buffer".to_owned(),
+                    max_edits: 2,
+                },
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(outcome.results.len(), 1);
+        assert_eq!(
+            index.file_id_to_path[outcome.results[0].file_id as usize],
+            "a.rs"
+        );
+        assert!(matches!(
+            &outcome.results[0].query,
+            MatchedQuery::Regex(r) if r.contains("receive_buffer")
+        ));
+    }
+
+    fn found_paths(index: &GitIndex, outcome: &SearchOutcome) -> Vec<String> {
+        let mut paths = outcome
+            .results
+            .iter()
+            .map(|r| index.file_id_to_path[r.file_id as usize].clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn plain_search_matches_identifier_parts() {
+        let index = build_index(&[
+            ("a.rs", "fn getUserName() {}"),
+            ("b.rs", "let username = 1;"),
+            ("c.rs", "getUserNames()"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search(&Query::Plain("user name".to_owned()), None)
+            .unwrap();
+
+        assert_eq!(found_paths(&index, &outcome), vec!["a.rs", "b.rs"]);
+        for result in &outcome.results {
+            match index.file_id_to_path[result.file_id as usize].as_str() {
+                "a.rs" => assert!(matches!(
+                    &result.query,
+                    MatchedQuery::Patterns(p) if p == &["(?i:user)", "(?i:name)"]
+                )),
+                _ => assert!(matches!(&result.query, MatchedQuery::Words(_))),
+            }
+        }
+    }
+
+    #[test]
+    fn word_prefix_matches_whole_identifier() {
+        let index = build_index(&[
+            ("a.rs", "fn getUserName() {}"),
+            ("c.rs", "getUserNames()"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        let outcome = searcher
+            .search(&Query::Plain("word:getUserName".to_owned()), None)
+            .unwrap();
+
+        assert_eq!(found_paths(&index, &outcome), vec!["a.rs"]);
+    }
+
+    #[test]
+    fn whole_word_search() {
+        let index = build_index(&[
+            ("a.rs", "let valid = width;"),
+            ("b.rs", "user.id = 1;"),
+            ("c.rs", "let user_id = 1;"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+        let whole_word = Some(SearchOption {
+            whole_word: true,
+            ..Default::default()
+        });
+
+        let outcome = searcher
+            .search(&Query::Plain("id".to_owned()), None)
+            .unwrap();
+        assert_eq!(found_paths(&index, &outcome), vec!["a.rs", "b.rs", "c.rs"]);
+
+        let outcome = searcher
+            .search(&Query::Plain("id".to_owned()), whole_word.clone())
+            .unwrap();
+        assert_eq!(found_paths(&index, &outcome), vec!["b.rs"]);
+        assert!(matches!(
+            &outcome.results[0].query,
+            MatchedQuery::WholeWords(words) if words == &["id"]
+        ));
+
+        // The regex candidates are only verified when the results are read.
+        for (query, option) in [
+            (Query::Regex("id".to_owned()), whole_word),
+            (Query::Regex(r"\bid\b".to_owned()), None),
+        ] {
+            let outcome = searcher.search(&query, option).unwrap();
+            assert!(matches!(
+                &outcome.results[0].query,
+                MatchedQuery::Regex(r) if r.contains(r"\b")
+            ));
+        }
+    }
+
+    #[test]
+    fn regex_with_captures_and_anchors() {
+        let index = build_index(&[
+            ("a.rs", "use std::fmt;\nfn foo(x: u32) {}"),
+            ("b.rs", "// no functions here"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        for regex in [r"fn (\w+)\(", "^use ", r"(?<name>fo+)\b"] {
+            let outcome = searcher
+                .search(&Query::Regex(regex.to_owned()), None)
+                .unwrap();
+            assert_eq!(found_paths(&index, &outcome), vec!["a.rs"], "{regex}");
+        }
+    }
+
+    #[test]
+    fn multiline_regex_search() {
+        let index = build_index(&[
+            ("a.rs", "#[derive(Debug)]\npub struct Config {}"),
+            (
+                "b.rs",
+                "#[derive(Debug)]\nstruct Other;\npub struct Config;",
+            ),
+            ("c.rs", "pub struct Config;"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+        let query = Query::Regex(
+            r"#\[derive\(.*\)\]\s*\n\s*pub struct Config".to_owned(),
+        );
+
+        // The trigrams never span the lines.
+        let outcome = searcher.search(&query, None).unwrap();
+        assert!(outcome.results.is_empty());
+
+        // Both parts are in a.rs and b.rs; the verification drops b.rs.
+        let outcome = searcher
+            .search(
+                &query,
+                Some(SearchOption {
+                    multiline: true,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        assert_eq!(found_paths(&index, &outcome), vec!["a.rs", "b.rs"]);
+        assert!(matches!(
+            &outcome.results[0].query,
+            MatchedQuery::MultilineRegex(r) if r.starts_with("(?m)")
+        ));
+    }
+
+    #[test]
+    fn vendored_files_are_ranked_last_and_filterable() {
+        let index = build_index(&[
+            (
+                ".gitattributes",
+                "0vendored.js linguist-vendored\n*.bin binary\n",
+            ),
+            ("0vendored.js", "hello vendored"),
+            ("a.js", "hello ours"),
+            ("data.bin", "hello binary"),
+        ]);
+        let searcher = GitSearcher::new((&index).into());
+
+        assert_eq!(
+            index.skipped_files.get("data.bin"),
+            Some(&SkipReason::Binary)
+        );
+
+        let paths = |query: &str| {
+            searcher
+                .search(&Query::Plain(query.to_owned()), None)
+                .unwrap()
+                .results
+                .iter()
+                .map(|r| index.file_id_to_path[r.file_id as usize].clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths("hello"), vec!["a.js", "0vendored.js"]);
+        assert_eq!(paths("hello -is:vendored"), vec!["a.js"]);
+        assert_eq!(paths("is:vendored hello"), vec!["0vendored.js"]);
+
+        // Regex queries are searched as written.
+        let regex_paths = |query: &str| {
+            searcher
+                .search(&Query::Regex(query.to_owned()), None)
+                .unwrap()
+                .results
+                .iter()
+                .map(|r| index.file_id_to_path[r.file_id as usize].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(regex_paths("hello -is:vendored"), Vec::<String>::new());
+    }
+}
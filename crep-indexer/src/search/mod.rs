@@ -1,8 +1,12 @@
+pub mod cancellation;
 mod core;
+pub mod file_kind_filter;
+pub mod fuzzy;
 pub mod git_searcher;
 mod line_formatter;
 pub mod permutation;
 mod regex_search;
 pub mod result;
 pub mod result_viewer;
-mod search_debug;
+pub mod suggest;
+pub mod summary;
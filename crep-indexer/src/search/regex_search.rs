@@ -3,9 +3,16 @@ use std::str::FromStr;
 
 use arrayvec::ArrayVec;
 use regex_syntax::hir::Class;
+use regex_syntax::hir::Hir;
+use regex_syntax::hir::HirKind;
 
 use super::permutation::PermutationIterator;
 
+// Classes like the unicode `\w` have hundreds of ranges, which makes the
+// trigram matching DFA huge. Those are widened to a single range, which still
+// matches every trigram that the class matches.
+const MAX_CLASS_RANGES: usize = 16;
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum CharacterClass {
     Char(char),
@@ -174,6 +181,13 @@ impl From<&Class> for Trigram {
             }
         }
 
+        if range.len() > MAX_CLASS_RANGES {
+            range = vec![CharRange {
+                start: range.first().unwrap().start,
+                end: range.last().unwrap().end,
+            }];
+        }
+
         Self {
             data: ArrayVec::<CharacterClass, 3>::from_iter([
                 CharacterClass::CharRange(range),
@@ -231,6 +245,42 @@ impl RegexSearchCandidates {
         }
     }
 
+    // Every part must be found, but not necessarily next to each other.
+    pub fn all_of(parts: &[RegexSearchCandidates]) -> Self {
+        let candidate_sizes = parts
+            .iter()
+            .map(|p| p.candidates.len() as u32)
+            .collect::<Vec<u32>>();
+
+        if candidate_sizes.contains(&0) {
+            return Self { candidates: vec![] };
+        }
+
+        // Nothing is required.
+        if parts.is_empty() {
+            return Self {
+                candidates: vec![SearchPartTrigram { trigrams: vec![] }],
+            };
+        }
+
+        let candidates = PermutationIterator::new(&candidate_sizes)
+            .map(|permutation| SearchPartTrigram {
+                trigrams: permutation
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, perm_index)| {
+                        parts[index].candidates[*perm_index as usize]
+                            .trigrams
+                            .iter()
+                            .cloned()
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self { candidates }
+    }
+
     pub fn alternation(parts: &[RegexSearchCandidates]) -> Self {
         Self {
             candidates: parts
@@ -281,8 +331,33 @@ impl RegexSearchCandidates {
     }
 }
 
+pub fn can_match_newline(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= '\n' && '\n' <= range.end()),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= b'\n' && b'\n' <= range.end()),
+        HirKind::Repetition(repetition) => can_match_newline(&repetition.sub),
+        HirKind::Capture(capture) => can_match_newline(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+            hirs.iter().any(can_match_newline)
+        }
+    }
+}
+
 fn merge_trigrams(trigrams: &[&Vec<Trigram>]) -> Vec<Trigram> {
-    let mut merged = trigrams[0].clone();
+    // Every part was zero width (e.g. `\b\b`).
+    let Some(first) = trigrams.first() else {
+        return vec![];
+    };
+
+    let mut merged = (*first).clone();
 
     for trigram in trigrams.iter().skip(1) {
         let left = merged.pop().unwrap();
@@ -398,6 +473,26 @@ mod trigram_test {
         );
     }
 
+    #[test]
+    fn large_class_is_widened() {
+        let hir = regex_syntax::parse(r"\w").unwrap();
+        let regex_syntax::hir::HirKind::Class(class) = hir.kind() else {
+            panic!("{hir:?} is not a class");
+        };
+
+        let trigram = Trigram::from(class);
+        let CharacterClass::CharRange(range) = &trigram.data[0] else {
+            panic!("{trigram:?} is not a range");
+        };
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].start, '0');
+    }
+
+    #[test]
+    fn merge_zero_width_trigrams() {
+        assert_eq!(merge_trigrams(&[]), vec![]);
+    }
+
     #[test]
     fn merge_trigrams_test() {
         let one_gram = Trigram::new("a");
@@ -553,6 +648,54 @@ mod regex_search_part_tests {
         );
     }
 
+    #[test]
+    fn test_all_of() {
+        let part1 = RegexSearchCandidates {
+            candidates: vec![SearchPartTrigram {
+                trigrams: vec![Trigram::new("abc")],
+            }],
+        };
+        let part2 = RegexSearchCandidates {
+            candidates: vec![
+                SearchPartTrigram {
+                    trigrams: vec![Trigram::new("d")],
+                },
+                SearchPartTrigram {
+                    trigrams: vec![Trigram::new("ef")],
+                },
+            ],
+        };
+
+        assert_eq!(
+            RegexSearchCandidates::all_of(&[part1, part2]),
+            RegexSearchCandidates {
+                candidates: vec![
+                    SearchPartTrigram {
+                        trigrams: vec![Trigram::new("abc"), Trigram::new("d")],
+                    },
+                    SearchPartTrigram {
+                        trigrams: vec![Trigram::new("abc"), Trigram::new("ef")],
+                    },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn can_match_newline_test() {
+        for (regex, expected) in [
+            (r"a\nb", true),
+            (r"a\s*b", true),
+            (r"a.*b", false),
+            (r"(?s)a.*b", true),
+            (r"(a|[^x])b", true),
+            (r"^a\b", false),
+        ] {
+            let hir = regex_syntax::parse(regex).unwrap();
+            assert_eq!(can_match_newline(&hir), expected, "{regex}");
+        }
+    }
+
     #[test]
     fn test_alternation() {
         let part1 = RegexSearchCandidates {
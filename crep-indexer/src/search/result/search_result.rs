@@ -1,3 +1,4 @@
+use roaring::RoaringBitmap;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -5,6 +6,7 @@ use crate::index::git_indexer::CommitIndex;
 use crate::index::git_indexer::FileId;
 use crate::search::git_searcher::MatchedQuery;
 use crate::search::git_searcher::RawPerFileSearchResult;
+use crate::search::git_searcher::SearchOption;
 use crate::search::result::single_commit_search_result::SingleCommitSearchResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,18 +20,34 @@ impl SearchResult {
     pub fn new<Reader: RepoReader>(
         reader: &Reader,
         result: &RawPerFileSearchResult,
+    ) -> anyhow::Result<Option<Self>> {
+        Self::new_with_option(reader, result, &SearchOption::default())
+    }
+
+    // Stops reading the file history once the option is interrupted. Returns
+    // None if that happens before the first match is found; callers should
+    // check `option.is_interrupted()` to tell it apart from "no match".
+    pub fn new_with_option<Reader: RepoReader>(
+        reader: &Reader,
+        result: &RawPerFileSearchResult,
+        option: &SearchOption,
     ) -> anyhow::Result<Option<Self>> {
         let mut file_path = None;
         let mut first = None;
         let mut last = None;
 
         for commit_id in &result.overlapped_commits {
+            if option.is_interrupted() {
+                return Ok(None);
+            }
+
             if let (file_path_read, Some(first_match)) =
                 SearchResult::get_search_result_at_commit(
                     reader,
                     &result.query,
                     commit_id as CommitIndex,
                     result.file_id as FileId,
+                    option,
                 )?
             {
                 first = Some(first_match);
@@ -44,7 +62,8 @@ impl SearchResult {
 
         let first_commit_id = first.as_ref().unwrap().commit_id;
         for commit_id in result.overlapped_commits.iter().rev() {
-            if commit_id as usize <= first_commit_id {
+            if commit_id as usize <= first_commit_id || option.is_interrupted()
+            {
                 break;
             }
 
@@ -54,6 +73,7 @@ impl SearchResult {
                     &result.query,
                     commit_id as CommitIndex,
                     result.file_id as FileId,
+                    option,
                 )?
             {
                 last = Some(last_match);
@@ -68,11 +88,49 @@ impl SearchResult {
         }))
     }
 
+    // Keeps only the commits where the file actually matches the query. The
+    // raw results are the candidates from the index, so this is needed when
+    // the exact files or counts are required. Returns None if nothing
+    // matches or the option is interrupted.
+    pub fn verify<Reader: RepoReader>(
+        reader: &Reader,
+        result: &RawPerFileSearchResult,
+        option: &SearchOption,
+    ) -> anyhow::Result<Option<RawPerFileSearchResult>> {
+        let mut overlapped_commits = RoaringBitmap::new();
+
+        for commit_id in &result.overlapped_commits {
+            if option.is_interrupted() {
+                return Ok(None);
+            }
+
+            if let (_, Some(_)) = SearchResult::get_search_result_at_commit(
+                reader,
+                &result.query,
+                commit_id as CommitIndex,
+                result.file_id as FileId,
+                option,
+            )? {
+                overlapped_commits.insert(commit_id);
+            }
+        }
+
+        if overlapped_commits.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(RawPerFileSearchResult {
+            overlapped_commits,
+            ..result.clone()
+        }))
+    }
+
     fn get_search_result_at_commit<Reader: RepoReader>(
         reader: &Reader,
         query: &MatchedQuery,
         commit_id: CommitIndex,
         file_id: FileId,
+        option: &SearchOption,
     ) -> anyhow::Result<(String, Option<SingleCommitSearchResult>)> {
         let search_result = reader
             .read_file_at_commit(commit_id as CommitIndex, file_id as FileId)?;
@@ -85,10 +143,11 @@ impl SearchResult {
 
         Ok((
             file_path,
-            SingleCommitSearchResult::new(
+            SingleCommitSearchResult::new_with_option(
                 query,
                 commit_id,
                 &content.lines().collect::<Vec<&str>>(),
+                option,
             )?,
         ))
     }
@@ -101,3 +160,50 @@ pub trait RepoReader {
         file_id: FileId,
     ) -> anyhow::Result<Option<(/*file path*/ String, /*content*/ String)>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serves the same path with the content per commit.
+    struct FakeReader(Vec<&'static str>);
+
+    impl RepoReader for FakeReader {
+        fn read_file_at_commit(
+            &self,
+            commit_id: CommitIndex,
+            _file_id: FileId,
+        ) -> anyhow::Result<Option<(String, String)>> {
+            Ok(Some(("a.txt".to_owned(), self.0[commit_id].to_owned())))
+        }
+    }
+
+    #[test]
+    fn verify_keeps_only_matching_commits() {
+        let reader = FakeReader(vec!["foo", "bar", "foo bar"]);
+        let result = RawPerFileSearchResult {
+            query: MatchedQuery::Regex("foo".to_owned()),
+            file_id: 0,
+            overlapped_commits: RoaringBitmap::from_iter([0, 1, 2]),
+        };
+
+        let verified =
+            SearchResult::verify(&reader, &result, &SearchOption::default())
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            verified.overlapped_commits,
+            RoaringBitmap::from_iter([0, 2])
+        );
+
+        let result = RawPerFileSearchResult {
+            overlapped_commits: RoaringBitmap::from_iter([1]),
+            ..result
+        };
+        assert!(
+            SearchResult::verify(&reader, &result, &SearchOption::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -3,6 +3,7 @@ use std::path::Path;
 use git2::Oid;
 use git2::Repository;
 
+use crate::index::encoding::decode_to_string;
 use crate::index::git_indexer::CommitIndex;
 use crate::index::git_indexer::FileId;
 use crate::search::result::search_result::RepoReader;
@@ -32,7 +33,7 @@ impl<'r, 'i> RepoReader for SimpleRepoReader<'r, 'i> {
         if let Some(blob) = object.as_blob() {
             Ok(Some((
                 file_path.to_owned(),
-                String::from_utf8_lossy(blob.content()).to_string(),
+                decode_to_string(blob.content()),
             )))
         } else {
             Ok(None)
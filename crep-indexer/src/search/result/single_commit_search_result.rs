@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
-use std::collections::HashMap;
 use std::collections::HashSet;
+use std::ops::Range;
 
 use aho_corasick::AhoCorasick;
 use regex::Regex;
@@ -9,12 +9,85 @@ use serde::Serialize;
 
 use crate::index::git_indexer::CommitIndex;
 use crate::search::git_searcher::MatchedQuery;
+use crate::search::git_searcher::SearchOption;
+
+// Multi-line matches can be long, so only the first few are shown.
+const MAX_MULTILINE_MATCHES: usize = 10;
+
+const MAX_REGEX_MATCHING_LINES: usize = 11;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SingleCommitSearchResult {
     pub commit_id: CommitIndex,
     pub words_per_line: BTreeMap<usize, Vec<(String, usize)>>,
     pub lines: BTreeMap<usize, String>,
+
+    // Capture groups of the regex matches, keyed by the line and then the
+    // column of the match.
+    #[serde(default)]
+    pub captures_per_line: BTreeMap<usize, BTreeMap<usize, Vec<CaptureGroup>>>,
+
+    // The last line of the multi-line regex matches, keyed by the line and
+    // then the column where the match starts.
+    #[serde(default)]
+    pub match_end_lines: BTreeMap<usize, BTreeMap<usize, usize>>,
+
+    // Number of the matches in the file, including the ones that are not
+    // shown.
+    #[serde(default)]
+    pub match_count: usize,
+}
+
+// Match of the multi-line regex. Columns are the byte offsets within the
+// lines, and the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSpan {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+impl LineSpan {
+    // The part of the match in each line, as (line, start col, end col).
+    pub fn fragments(
+        &self,
+        content: &[&str],
+    ) -> impl Iterator<Item = (usize, usize, usize)> {
+        let span = *self;
+        let line_lens = content[span.start_line..=span.end_line]
+            .iter()
+            .map(|line| line.len())
+            .collect::<Vec<_>>();
+
+        line_lens
+            .into_iter()
+            .enumerate()
+            .map(move |(offset, len)| {
+                let line_num = span.start_line + offset;
+                let start = match line_num == span.start_line {
+                    true => span.start_col,
+                    false => 0,
+                };
+                let end = match line_num == span.end_line {
+                    true => span.end_col,
+                    false => len,
+                };
+
+                (line_num, start, end)
+            })
+            .filter(|(_, start, end)| start < end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureGroup {
+    // 1-based index of the group in the regex.
+    pub index: usize,
+    pub name: Option<String>,
+    // Byte range within the line.
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -29,25 +102,131 @@ impl SingleCommitSearchResult {
         commit_id: CommitIndex,
         file_content: &[&str],
     ) -> anyhow::Result<Option<Self>> {
-        let matches = match query {
-            MatchedQuery::Words(words) => {
-                Self::find_word_matches_in_document(words, file_content)?
+        Self::new_with_option(
+            query,
+            commit_id,
+            file_content,
+            &SearchOption::default(),
+        )
+    }
+
+    pub fn new_with_option(
+        query: &MatchedQuery,
+        commit_id: CommitIndex,
+        file_content: &[&str],
+        option: &SearchOption,
+    ) -> anyhow::Result<Option<Self>> {
+        let regex = match query {
+            MatchedQuery::Regex(regex)
+            | MatchedQuery::MultilineRegex(regex) => Some(Regex::new(regex)?),
+            _ => None,
+        };
+        let mut match_end_lines: BTreeMap<usize, BTreeMap<usize, usize>> =
+            BTreeMap::new();
+
+        // Every match is counted, but only a few are shown unless all of them
+        // are requested.
+        let (match_count, matches) = match query {
+            MatchedQuery::Words(words) | MatchedQuery::WholeWords(words) => {
+                let whole_word = matches!(query, MatchedQuery::WholeWords(_));
+                let matches = Self::find_word_matches_in_document(
+                    words,
+                    file_content,
+                    whole_word,
+                )?;
+
+                let num_words_found = matches
                     .iter()
-                    .map(|(k, v)| (*k, *v))
-                    .collect::<Vec<_>>()
+                    .map(|(word, _)| *word)
+                    .collect::<HashSet<_>>()
+                    .len();
+                if num_words_found != words.iter().collect::<HashSet<_>>().len()
+                {
+                    // Not every words are found in the document.
+                    return Ok(None);
+                }
+
+                let match_count = matches.len();
+                match option.all_matches {
+                    true => (match_count, matches),
+                    false => (match_count, first_match_per_word(matches)),
+                }
             }
-            MatchedQuery::Regex(regex) => {
-                let r = Regex::new(regex)?;
-                Self::find_regex_matches_in_document(&r, file_content)
+            MatchedQuery::Patterns(patterns) => {
+                let mut match_count = 0;
+                let mut matches = vec![];
+                for pattern in patterns {
+                    let pattern_matches = Self::find_regex_matches_in_document(
+                        &Regex::new(pattern)?,
+                        file_content,
+                    );
+                    if pattern_matches.is_empty() {
+                        // Every pattern must be found in the document.
+                        return Ok(None);
+                    }
+
+                    match_count += pattern_matches.len();
+                    match option.all_matches {
+                        true => matches.extend(pattern_matches),
+                        false => matches.push(pattern_matches[0]),
+                    }
+                }
+
+                matches.sort_by_key(|(_, pos)| (pos.line_num, pos.col));
+                (match_count, matches)
             }
-        };
+            MatchedQuery::Regex(_) => {
+                let matches = Self::find_regex_matches_in_document(
+                    regex.as_ref().unwrap(),
+                    file_content,
+                );
 
-        if let MatchedQuery::Words(words) = query
-            && matches.len() != words.len()
-        {
-            // Not every words are found in the document.
-            return Ok(None);
-        }
+                let match_count = matches.len();
+                match option.all_matches {
+                    true => (match_count, matches),
+                    false => (match_count, first_match_per_line(matches)),
+                }
+            }
+            MatchedQuery::MultilineRegex(_) => {
+                let spans = find_multiline_matches(
+                    regex.as_ref().unwrap(),
+                    file_content,
+                );
+                let match_count = spans.len();
+                let num_spans_to_show = match option.all_matches {
+                    true => spans.len(),
+                    false => MAX_MULTILINE_MATCHES,
+                };
+
+                let spans = spans
+                    .into_iter()
+                    .take(num_spans_to_show)
+                    .collect::<Vec<_>>();
+                for span in &spans {
+                    match_end_lines
+                        .entry(span.start_line)
+                        .or_default()
+                        .insert(span.start_col, span.end_line);
+                }
+
+                // Every line of the match is highlighted.
+                let matches = spans
+                    .iter()
+                    .flat_map(|span| span.fragments(file_content))
+                    .map(|(line_num, start, end)| {
+                        (
+                            &file_content[line_num][start..end],
+                            MatchingWordPos {
+                                line_num,
+                                col: start,
+                            },
+                        )
+                    })
+                    .collect();
+
+                (match_count, matches)
+            }
+        };
 
         if matches.is_empty() {
             return Ok(None);
@@ -64,82 +243,472 @@ impl SingleCommitSearchResult {
         }
 
         for words in words_per_line.values_mut() {
-            words.sort_by(|left, right| left.1.cmp(&right.1));
+            words.sort_by_key(|word| word.1);
         }
 
+        let before_context = option.before_context();
+        let after_context = option.after_context();
         let lines = matches
             .iter()
             .flat_map(|(_, pos)| {
-                (pos.line_num.saturating_sub(2)..pos.line_num.saturating_add(2))
-                    .filter(|l| *l < file_content.len())
+                (pos.line_num.saturating_sub(before_context)
+                    ..=pos.line_num.saturating_add(after_context))
+                    .take_while(|l| *l < file_content.len())
             })
             .collect::<HashSet<usize>>()
             .into_iter()
             .map(|line_num| (line_num, file_content[line_num].to_owned()))
             .collect::<BTreeMap<usize, String>>();
 
+        let captures_per_line = match (&regex, query) {
+            (Some(regex), MatchedQuery::Regex(_)) => Self::find_capture_groups(
+                regex,
+                file_content,
+                matches.iter().map(|(_, pos)| *pos),
+            ),
+            _ => BTreeMap::new(),
+        };
+
         Ok(Some(SingleCommitSearchResult {
             commit_id,
             words_per_line,
             lines,
+            captures_per_line,
+            match_end_lines,
+            match_count,
         }))
     }
 
+    // Groups the lines into the windows of consecutive lines, so that the
+    // nearby matches share their context.
+    pub fn context_windows(&self) -> Vec<Range<usize>> {
+        let mut windows: Vec<Range<usize>> = vec![];
+
+        for line_num in self.lines.keys() {
+            match windows.last_mut() {
+                Some(window) if window.end == *line_num => {
+                    window.end = line_num + 1
+                }
+                _ => windows.push(*line_num..line_num + 1),
+            }
+        }
+
+        windows
+    }
+
+    // Every occurrence of the words, in the order of the positions.
     fn find_word_matches_in_document<'w>(
         words: &'w [String],
         content: &[&str],
-    ) -> anyhow::Result<HashMap<&'w str, MatchingWordPos>> {
+        whole_word: bool,
+    ) -> anyhow::Result<Vec<(&'w str, MatchingWordPos)>> {
         let ac = AhoCorasick::builder()
             .match_kind(aho_corasick::MatchKind::LeftmostFirst)
             .build(words)?;
 
-        let mut word_pos_found: HashMap<usize, MatchingWordPos> =
-            HashMap::with_capacity(words.len());
+        let mut word_pos_found = vec![];
 
         for (line_num, line) in content.iter().enumerate() {
             for m in ac.find_iter(line) {
-                if word_pos_found.contains_key(&m.pattern().as_usize()) {
+                if whole_word && !is_at_word_boundary(line, m.start(), m.end())
+                {
                     continue;
                 }
 
-                word_pos_found.insert(
-                    m.pattern().as_usize(),
+                word_pos_found.push((
+                    words[m.pattern().as_usize()].as_str(),
                     MatchingWordPos {
                         line_num,
                         col: m.start(),
                     },
-                );
+                ));
             }
         }
 
-        Ok(word_pos_found
-            .into_iter()
-            .map(|(k, v)| (words[k].as_str(), v))
-            .collect())
+        Ok(word_pos_found)
+    }
+
+    fn find_capture_groups(
+        regex: &Regex,
+        content: &[&str],
+        matches: impl Iterator<Item = MatchingWordPos>,
+    ) -> BTreeMap<usize, BTreeMap<usize, Vec<CaptureGroup>>> {
+        let mut captures_per_line: BTreeMap<
+            usize,
+            BTreeMap<usize, Vec<CaptureGroup>>,
+        > = BTreeMap::new();
+
+        // Only the implicit group of the whole match.
+        if regex.captures_len() <= 1 {
+            return captures_per_line;
+        }
+
+        for pos in matches {
+            let Some(captures) =
+                regex.captures_at(content[pos.line_num], pos.col)
+            else {
+                continue;
+            };
+
+            let groups = captures
+                .iter()
+                .zip(regex.capture_names())
+                .enumerate()
+                .skip(1)
+                .filter_map(|(index, (group, name))| {
+                    group.map(|group| CaptureGroup {
+                        index,
+                        name: name.map(str::to_owned),
+                        start: group.start(),
+                        end: group.end(),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            captures_per_line
+                .entry(pos.line_num)
+                .or_default()
+                .insert(pos.col, groups);
+        }
+
+        captures_per_line
     }
 
+    // Every non empty match of the regex within a line.
     fn find_regex_matches_in_document<'w>(
         regex: &Regex,
         content: &'w [&str],
     ) -> Vec<(&'w str, MatchingWordPos)> {
-        let mut word_pos_found_lines = vec![];
+        content
+            .iter()
+            .enumerate()
+            .flat_map(|(line_num, line)| {
+                regex
+                    .find_iter(line)
+                    .filter(|m| !m.is_empty())
+                    .map(move |m| {
+                        (
+                            m.as_str(),
+                            MatchingWordPos {
+                                line_num,
+                                col: m.start(),
+                            },
+                        )
+                    })
+            })
+            .collect()
+    }
+}
 
-        for (line_num, line) in content.iter().enumerate() {
-            if let Some(m) = regex.find(line) {
-                word_pos_found_lines.push((
-                    m.as_str(),
-                    MatchingWordPos {
-                        line_num,
-                        col: m.start(),
-                    },
-                ));
-            }
+// Only the first occurrence of each word is shown by default.
+fn first_match_per_word(
+    matches: Vec<(&str, MatchingWordPos)>,
+) -> Vec<(&str, MatchingWordPos)> {
+    let mut found = HashSet::new();
+
+    matches
+        .into_iter()
+        .filter(|(word, _)| found.insert(*word))
+        .collect()
+}
+
+// Only the first match of the first few lines is shown by default.
+fn first_match_per_line(
+    matches: Vec<(&str, MatchingWordPos)>,
+) -> Vec<(&str, MatchingWordPos)> {
+    let mut found = HashSet::new();
+
+    matches
+        .into_iter()
+        .filter(|(_, pos)| found.insert(pos.line_num))
+        .take(MAX_REGEX_MATCHING_LINES)
+        .collect()
+}
+
+// Runs the regex over the whole file, so that the match can span multiple
+// lines.
+pub fn find_multiline_matches(
+    regex: &Regex,
+    content: &[&str],
+) -> Vec<LineSpan> {
+    let text = content.join("\n");
+
+    // Byte offset of the start of every line in `text`.
+    let mut line_starts = Vec::with_capacity(content.len());
+    let mut offset = 0;
+    for line in content {
+        line_starts.push(offset);
+        offset += line.len() + 1;
+    }
 
-            if word_pos_found_lines.len() > 10 {
-                break;
+    let to_line_col = |offset: usize| {
+        let line_num =
+            line_starts.partition_point(|start| *start <= offset) - 1;
+        (line_num, offset - line_starts[line_num])
+    };
+
+    regex
+        .find_iter(&text)
+        .filter(|m| !m.is_empty())
+        .map(|m| {
+            let (start_line, start_col) = to_line_col(m.start());
+            // The end is exclusive; a match that ends with the newline ends
+            // at the end of the previous line.
+            let (end_line, end_col) = to_line_col(m.end() - 1);
+
+            LineSpan {
+                start_line,
+                start_col,
+                end_line,
+                end_col: (end_col + 1).min(content[end_line].len()),
             }
-        }
+        })
+        .collect()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Same as the `\b` of the regex on both ends of `line[start..end]`.
+pub fn is_at_word_boundary(line: &str, start: usize, end: usize) -> bool {
+    let is_boundary = |before: Option<char>, after: Option<char>| {
+        before.is_some_and(is_word_char) != after.is_some_and(is_word_char)
+    };
+
+    is_boundary(
+        line[..start].chars().next_back(),
+        line[start..].chars().next(),
+    ) && is_boundary(
+        line[..end].chars().next_back(),
+        line[end..].chars().next(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_word_matches_only_at_boundaries() {
+        let words = vec!["id".to_owned()];
+        let content = ["let valid = width;", "user.id = 1;"];
+
+        let matches = SingleCommitSearchResult::find_word_matches_in_document(
+            &words, &content, true,
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].1.line_num, 1);
+        assert_eq!(matches[0].1.col, 5);
+
+        let matches = SingleCommitSearchResult::find_word_matches_in_document(
+            &words, &content, false,
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].1.line_num, 0);
+    }
+
+    #[test]
+    fn regex_matches_carry_capture_groups() {
+        let query =
+            MatchedQuery::Regex(r"fn (?<name>\w+)\((\w+)?\)".to_owned());
+        let content = ["// fn", "pub fn foo() {}", "fn bar(x) {}"];
+
+        let result = SingleCommitSearchResult::new(&query, 0, &content)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            result.captures_per_line[&1][&4],
+            vec![CaptureGroup {
+                index: 1,
+                name: Some("name".to_owned()),
+                start: 7,
+                end: 10,
+            }]
+        );
+        assert_eq!(
+            result.captures_per_line[&2][&0]
+                .iter()
+                .map(|g| (g.index, g.start, g.end))
+                .collect::<Vec<_>>(),
+            vec![(1, 3, 6), (2, 7, 8)]
+        );
+    }
+
+    #[test]
+    fn every_pattern_must_match() {
+        let query = MatchedQuery::Patterns(vec![
+            "foo".to_owned(),
+            "(?i:name)".to_owned(),
+        ]);
+
+        let result =
+            SingleCommitSearchResult::new(&query, 0, &["foo", "userName"])
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            result.words_per_line,
+            BTreeMap::from([
+                (0, vec![("foo".to_owned(), 0)]),
+                (1, vec![("Name".to_owned(), 4)]),
+            ])
+        );
+
+        assert!(
+            SingleCommitSearchResult::new(&query, 0, &["foo", "bar"])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn pattern_match_count_includes_every_match() {
+        let query =
+            MatchedQuery::Patterns(vec!["foo".to_owned(), "bar".to_owned()]);
+        let content = ["foo bar", "foo", "foofoo"];
+
+        let result = SingleCommitSearchResult::new(&query, 0, &content)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.match_count, 5);
+        assert_eq!(
+            result.words_per_line,
+            BTreeMap::from([(
+                0,
+                vec![("foo".to_owned(), 0), ("bar".to_owned(), 4)]
+            )])
+        );
+
+        let result = SingleCommitSearchResult::new_with_option(
+            &query,
+            0,
+            &content,
+            &SearchOption {
+                all_matches: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(result.match_count, 5);
+    }
+
+    #[test]
+    fn all_matches_and_context_windows() {
+        let query = MatchedQuery::Words(vec!["foo".to_owned()]);
+        let content = [
+            "foo(foo)", "foo", "a", "b", "c", "d", "e", "f", "foo", "g", "h",
+        ];
+
+        let result = SingleCommitSearchResult::new(&query, 0, &content)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.match_count, 4);
+        assert_eq!(
+            result.words_per_line,
+            BTreeMap::from([(0, vec![("foo".to_owned(), 0)])])
+        );
+
+        let result = SingleCommitSearchResult::new_with_option(
+            &query,
+            0,
+            &content,
+            &SearchOption {
+                all_matches: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(result.match_count, 4);
+        assert_eq!(
+            result.words_per_line,
+            BTreeMap::from([
+                (0, vec![("foo".to_owned(), 0), ("foo".to_owned(), 4)]),
+                (1, vec![("foo".to_owned(), 0)]),
+                (8, vec![("foo".to_owned(), 0)]),
+            ])
+        );
+        assert_eq!(result.context_windows(), vec![0..4, 6..11]);
+    }
+
+    #[test]
+    fn context_lines_around_match() {
+        let query = MatchedQuery::Words(vec!["foo".to_owned()]);
+        let content = ["a", "b", "c", "foo", "d", "e", "f"];
+
+        let result = SingleCommitSearchResult::new(&query, 0, &content)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.context_windows(), vec![1..6]);
+
+        let result = SingleCommitSearchResult::new_with_option(
+            &query,
+            0,
+            &content,
+            &SearchOption {
+                before_context: Some(0),
+                after_context: Some(10),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(result.context_windows(), vec![3..7]);
+    }
+
+    #[test]
+    fn multiline_regex_spans_lines() {
+        let query = MatchedQuery::MultilineRegex(
+            r"(?m)#\[derive\(.*\)\]\s*\n\s*pub struct Config".to_owned(),
+        );
+        let content = ["#[derive(Debug)]", "  pub struct Config {}"];
+
+        let result = SingleCommitSearchResult::new(&query, 0, &content)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            result.words_per_line,
+            BTreeMap::from([
+                (0, vec![("#[derive(Debug)]".to_owned(), 0)]),
+                (1, vec![("  pub struct Config".to_owned(), 0)]),
+            ])
+        );
+        assert_eq!(result.match_end_lines[&0][&0], 1);
+
+        let content =
+            ["#[derive(Debug)]", "struct Other;", "pub struct Config;"];
+        assert!(
+            SingleCommitSearchResult::new(&query, 0, &content)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn multiline_match_ending_with_newline() {
+        let regex = Regex::new(r"b\n").unwrap();
+
+        assert_eq!(
+            find_multiline_matches(&regex, &["ab", "c"]),
+            vec![LineSpan {
+                start_line: 0,
+                start_col: 1,
+                end_line: 0,
+                end_col: 2,
+            }]
+        );
+    }
 
-        word_pos_found_lines
+    #[test]
+    fn is_at_word_boundary_test() {
+        assert!(is_at_word_boundary("(id)", 1, 3));
+        assert!(!is_at_word_boundary("valid", 3, 5));
+        assert!(!is_at_word_boundary("id_x", 0, 2));
+        assert!(!is_at_word_boundary("a..b", 1, 2));
+        assert!(is_at_word_boundary("é id", 3, 5));
     }
 }
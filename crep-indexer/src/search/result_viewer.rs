@@ -10,12 +10,14 @@ use git2::Repository;
 use owo_colors::OwoColorize;
 use regex::Regex;
 
+use crate::index::encoding::decode_to_string;
 use crate::index::git_index::GitIndex;
 use crate::index::git_indexer::CommitIndex;
 
 use super::git_searcher::MatchedQuery;
 use super::git_searcher::RawPerFileSearchResult;
 use super::line_formatter::highlight_line_by_positions;
+use super::result::single_commit_search_result::is_at_word_boundary;
 
 static MAX_SEARCH_RESULT: usize = 10;
 
@@ -75,21 +77,44 @@ impl<'i> GitSearchResultViewer<'i> {
 
         let matches = match &result.query {
             MatchedQuery::Words(words) => self
-                .find_word_matches_in_document(words, &file_content)?
+                .find_word_matches_in_document(words, &file_content, false)?
                 .iter()
                 .map(|(k, v)| (*k, *v))
                 .collect::<Vec<_>>(),
-            MatchedQuery::Regex(regex) => {
+            MatchedQuery::WholeWords(words) => self
+                .find_word_matches_in_document(words, &file_content, true)?
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<_>>(),
+            MatchedQuery::Patterns(patterns) => {
+                let mut matches = vec![];
+                for pattern in patterns {
+                    let pattern_matches = self.find_regex_matches_in_document(
+                        &Regex::new(pattern)?,
+                        &file_content,
+                    );
+                    if pattern_matches.is_empty() {
+                        // Not every pattern is found in the document.
+                        return Ok(None);
+                    }
+                    matches.extend(pattern_matches);
+                }
+                matches
+            }
+            // The viewer only matches within a line.
+            MatchedQuery::Regex(regex)
+            | MatchedQuery::MultilineRegex(regex) => {
                 let r = Regex::new(regex)?;
                 self.find_regex_matches_in_document(&r, &file_content)
             }
         };
 
-        if let MatchedQuery::Words(ref words) = result.query {
-            if matches.len() != words.len() {
-                // Not every words are found in the document.
-                return Ok(None);
-            }
+        if let MatchedQuery::Words(ref words)
+        | MatchedQuery::WholeWords(ref words) = result.query
+            && matches.len() != words.len()
+        {
+            // Not every words are found in the document.
+            return Ok(None);
         }
 
         let mut line_to_words: HashMap<usize, Vec<(&str, usize)>> =
@@ -103,7 +128,7 @@ impl<'i> GitSearchResultViewer<'i> {
         }
 
         for words in line_to_words.values_mut() {
-            words.sort_by(|left, right| left.1.cmp(&right.1));
+            words.sort_by_key(|word| word.1);
         }
 
         let lines_to_show = matches
@@ -178,7 +203,7 @@ impl<'i> GitSearchResultViewer<'i> {
 
         let object = entry.to_object(&self.repo)?;
         if let Some(blob) = object.as_blob() {
-            Ok(String::from_utf8_lossy(blob.content()).to_string())
+            Ok(decode_to_string(blob.content()))
         } else {
             anyhow::bail!("Path is not a blob file {file_path}");
         }
@@ -188,6 +213,7 @@ impl<'i> GitSearchResultViewer<'i> {
         &self,
         words: &'w [String],
         content: &[String],
+        whole_word: bool,
     ) -> Result<HashMap<&'w str, (usize, usize)>> {
         let ac = AhoCorasick::builder()
             .match_kind(aho_corasick::MatchKind::LeftmostFirst)
@@ -198,7 +224,10 @@ impl<'i> GitSearchResultViewer<'i> {
 
         for (line_num, line) in content.iter().enumerate() {
             for m in ac.find_iter(line) {
-                if word_pos_found.contains_key(&m.pattern().as_usize()) {
+                if word_pos_found.contains_key(&m.pattern().as_usize())
+                    || (whole_word
+                        && !is_at_word_boundary(line, m.start(), m.end()))
+                {
                     continue;
                 }
 
@@ -209,7 +238,7 @@ impl<'i> GitSearchResultViewer<'i> {
 
         Ok(word_pos_found
             .into_iter()
-            .map(|(k, v)| return (words[k].as_str(), v))
+            .map(|(k, v)| (words[k].as_str(), v))
             .collect())
     }
 
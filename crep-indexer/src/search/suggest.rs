@@ -0,0 +1,136 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use fst::Automaton;
+use fst::IntoStreamer;
+use fst::Map;
+use fst::Set;
+use fst::Streamer;
+use fst::automaton::Str;
+
+use crate::search::core::search_docs::find_all_words_containing_key;
+
+#[derive(Debug, PartialEq)]
+pub struct IdentifierSuggestion {
+    pub identifier: String,
+    pub count: u64,
+}
+
+// Returns the most frequent identifiers that start with `prefix`.
+pub fn suggest_identifiers(
+    identifiers: &Map<Vec<u8>>,
+    prefix: &str,
+    limit: usize,
+) -> Vec<IdentifierSuggestion> {
+    if prefix.is_empty() || limit == 0 {
+        return vec![];
+    }
+
+    let mut stream = identifiers
+        .search(Str::new(prefix).starts_with())
+        .into_stream();
+
+    // Min heap that keeps the `limit` most frequent identifiers. Ties are
+    // broken by the lexicographical order.
+    let mut top = BinaryHeap::with_capacity(limit + 1);
+    while let Some((identifier, count)) = stream.next() {
+        if identifier == prefix.as_bytes() {
+            // Nothing to complete.
+            continue;
+        }
+
+        top.push(Reverse((count, Reverse(identifier.to_vec()))));
+        if top.len() > limit {
+            top.pop();
+        }
+    }
+
+    top.into_sorted_vec()
+        .into_iter()
+        .map(
+            |Reverse((count, Reverse(identifier)))| IdentifierSuggestion {
+                identifier: String::from_utf8(identifier)
+                    .expect("identifiers are built from str"),
+                count,
+            },
+        )
+        .collect()
+}
+
+// Returns the paths that contain `fragment`. Paths whose file name starts with
+// the fragment come first, then the shorter ones.
+pub fn suggest_paths(
+    paths: &Set<Vec<u8>>,
+    fragment: &str,
+    limit: usize,
+) -> Vec<String> {
+    if fragment.is_empty() || limit == 0 {
+        return vec![];
+    }
+
+    let mut matches = find_all_words_containing_key(fragment, paths);
+    matches.sort_by_cached_key(|path| {
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+
+        let rank = if file_name.starts_with(fragment) {
+            0
+        } else if path.starts_with(fragment) {
+            1
+        } else {
+            2
+        };
+
+        (rank, path.len(), path.clone())
+    });
+    matches.truncate(limit);
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_most_frequent_identifiers() {
+        let identifiers = Map::from_iter([
+            ("get", 10),
+            ("get_file", 3),
+            ("get_index", 7),
+            ("get_path", 3),
+            ("set_path", 100),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            suggest_identifiers(&identifiers, "get", 2),
+            vec![
+                IdentifierSuggestion {
+                    identifier: "get_index".to_owned(),
+                    count: 7
+                },
+                IdentifierSuggestion {
+                    identifier: "get_file".to_owned(),
+                    count: 3
+                },
+            ]
+        );
+        assert!(suggest_identifiers(&identifiers, "xyz", 2).is_empty());
+    }
+
+    #[test]
+    fn suggests_paths_by_file_name_first() {
+        let paths = Set::from_iter([
+            "index.rs",
+            "src/git_index.rs",
+            "src/index.rs",
+            "src/index/document.rs",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            suggest_paths(&paths, "index", 3),
+            vec!["index.rs", "src/index.rs", "src/git_index.rs"]
+        );
+    }
+}
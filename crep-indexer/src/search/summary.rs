@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use roaring::RoaringBitmap;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::index::git_index::GitIndexRef;
+use crate::index::git_indexer::FileId;
+use crate::search::git_searcher::RawPerFileSearchResult;
+
+// What the search reports. Only `Matches` reads the files from the
+// repository; the others are answered from the index.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    #[default]
+    Matches,
+    Count,
+    FilesWithMatches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileWithMatches {
+    pub file_id: FileId,
+    pub file_path: String,
+    // Commits that changed the file while it contained the query.
+    pub commits: RoaringBitmap,
+    // The file exists at HEAD and still contains the query.
+    pub at_head: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchCount {
+    pub num_files: usize,
+    pub num_files_at_head: usize,
+    // Distinct commits that changed any of the files while it contained the
+    // query.
+    pub num_commits: u64,
+}
+
+impl MatchCount {
+    pub fn new(files: &[FileWithMatches]) -> Self {
+        let mut commits = RoaringBitmap::new();
+        for file in files {
+            commits |= &file.commits;
+        }
+
+        Self {
+            num_files: files.len(),
+            num_files_at_head: files.iter().filter(|f| f.at_head).count(),
+            num_commits: commits.len(),
+        }
+    }
+}
+
+// Merges the raw results of the same file (e.g. the plain query has one per
+// combination of the matched words), ordered by the path.
+pub fn collect_files_with_matches(
+    index: &GitIndexRef,
+    results: &[RawPerFileSearchResult],
+) -> Vec<FileWithMatches> {
+    let head_commit_index =
+        index.commit_index_to_commit_id.len().saturating_sub(1) as u32;
+
+    let mut commits_per_file: BTreeMap<u32, RoaringBitmap> = BTreeMap::new();
+    for result in results.iter().filter(|r| !r.overlapped_commits.is_empty()) {
+        *commits_per_file.entry(result.file_id).or_default() |=
+            &result.overlapped_commits;
+    }
+
+    let mut files = commits_per_file
+        .into_iter()
+        .filter_map(|(file_id, commits)| {
+            let file_id = file_id as FileId;
+            let file_path = index.file_id_to_path.get(file_id)?.to_owned();
+            let is_deleted = index
+                .file_id_to_document
+                .get(&file_id)
+                .is_none_or(|document| document.is_deleted);
+
+            Some(FileWithMatches {
+                file_id,
+                file_path,
+                at_head: !is_deleted && commits.contains(head_commit_index),
+                commits,
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+    files
+}
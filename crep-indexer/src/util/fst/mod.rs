@@ -1 +0,0 @@
-pub mod test_util;
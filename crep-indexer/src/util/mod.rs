@@ -1,3 +1,2 @@
 pub mod bitmap;
-pub mod fst;
 pub mod serde;
@@ -2,32 +2,9 @@ pub mod fst_set_to_vec {
     use fst::Set;
     use serde::Deserialize;
     use serde::Deserializer;
-    use serde::Serialize;
     use serde::Serializer;
     use serde::de::Error as DeError;
 
-    pub fn serialize<S>(
-        set: &Set<Vec<u8>>,
-        serializer: S,
-    ) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let v = set.stream().into_bytes();
-        v.serialize(serializer)
-    }
-
-    pub fn deserialize<'de, D>(
-        deserializer: D,
-    ) -> Result<Set<Vec<u8>>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let v = Vec::<Vec<u8>>::deserialize(deserializer)?;
-        Set::<Vec<u8>>::from_iter(v.iter())
-            .map_err(|e| D::Error::custom(format!("conversion error {e}")))
-    }
-
     pub mod option {
         use super::*;
 
@@ -59,7 +36,7 @@ pub mod fst_set_to_vec {
                     .map_err(|e| {
                         D::Error::custom(format!("conversion error {e}"))
                     })
-                    .map(|s| Some(s)),
+                    .map(Some),
                 None => Ok(None),
             }
         }
@@ -0,0 +1,79 @@
+use axum::Json;
+use axum::extract::Query as QueryParams;
+use axum::extract::State;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::api::error::ApiError;
+use crate::api::error::ErrorResponse;
+use crate::search::blame::BlameRange;
+use crate::search::blame::blame_file;
+use crate::server_context::ServerContext;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BlameParams {
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlameResponse {
+    pub path: String,
+    // The blame is always computed at the indexed HEAD.
+    pub head_commit: String,
+    pub ranges: Vec<BlameRange>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/blame",
+    params(BlameParams),
+    responses(
+        (status = 200, description = "Origin of every line at the indexed HEAD", body = BlameResponse),
+        (status = 404, description = "File not found at HEAD", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "file"
+)]
+pub async fn blame(
+    State(context): State<ServerContext>,
+    QueryParams(params): QueryParams<BlameParams>,
+) -> Result<Json<BlameResponse>, ApiError> {
+    info!("Blame request {:?}", params);
+
+    let index_guard = context.indexer.get_search_index();
+    let repo_pool = context.repo_pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let index = index_guard.as_index_ref();
+        let not_found = || {
+            ApiError::not_found(format!(
+                "{} does not exist at the indexed HEAD",
+                params.path
+            ))
+        };
+
+        let file_id = *index
+            .file_name_to_id
+            .get(&params.path)
+            .ok_or_else(not_found)?;
+
+        let ranges = blame_file(
+            &repo_pool.repos.first().unwrap().lock(),
+            &index,
+            file_id,
+        )
+        .map_err(|e| ApiError::internal("Unable to blame", e))?
+        .ok_or_else(not_found)?;
+
+        Ok(Json(BlameResponse {
+            path: params.path,
+            head_commit: index_guard.head_commit_id().unwrap_or_default(),
+            ranges,
+        }))
+    })
+    .await
+    .map_err(|e| ApiError::internal("Error during join", e))?
+}
@@ -1,31 +1,61 @@
+use crep_indexer::search::summary::OutputMode;
 use utoipa::OpenApi;
 
+use crate::api::blame::BlameResponse;
 use crate::api::error::ErrorResponse;
+use crate::api::file::FileResponse;
 use crate::api::reindex::ReindexRequest;
 use crate::api::reindex::ReindexResponse;
 use crate::api::search::SearchMode;
 use crate::api::search::SearchRequest;
 use crate::api::search::SearchResponse;
+use crate::api::search_stream::SearchStreamDone;
+use crate::api::search_stream::SearchStreamHit;
+use crate::api::search_stream::SearchStreamProgress;
+use crate::api::suggest::IdentifierSuggestion;
+use crate::api::suggest::SuggestResponse;
+use crate::search::blame::BlameRange;
+use crate::search::search::CaptureSpan;
+use crate::search::search::ContextWindow;
+use crate::search::search::FileMatch;
 use crate::search::search::LineHighlight;
 use crate::search::search::LineMatch;
 use crate::search::search::MatchDetail;
+use crate::search::search::SearchCount;
 use crate::search::search::SearchHit;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::api::search::search,
+        crate::api::search_stream::search_stream,
+        crate::api::suggest::suggest,
+        crate::api::file::file,
+        crate::api::blame::blame,
         crate::api::reindex::reindex
     ),
     components(
         schemas(
             SearchRequest,
             SearchResponse,
+            SearchStreamHit,
+            SearchStreamProgress,
+            SearchStreamDone,
+            SuggestResponse,
+            IdentifierSuggestion,
+            FileResponse,
+            BlameResponse,
+            BlameRange,
             SearchHit,
             MatchDetail,
+            ContextWindow,
             LineMatch,
             LineHighlight,
+            CaptureSpan,
             SearchMode,
+            OutputMode,
+            SearchCount,
+            FileMatch,
             ErrorResponse,
             ReindexRequest,
             ReindexResponse
@@ -33,6 +63,7 @@ use crate::search::search::SearchHit;
     ),
     tags(
         (name = "search", description = "Git history search operations"),
+        (name = "file", description = "File contents at the indexed commits"),
         (name = "reindex", description = "Git history reindex operations")
     )
 )]
@@ -58,4 +89,67 @@ mod tests {
                 .is_some()
         );
     }
+
+    #[test]
+    fn includes_search_stream_operation_and_schemas() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(
+            document
+                .pointer("/paths/~1api~1search~1stream/get")
+                .is_some()
+        );
+        assert!(
+            document
+                .pointer("/components/schemas/SearchStreamHit")
+                .is_some()
+        );
+        assert!(
+            document
+                .pointer("/components/schemas/SearchStreamDone")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn includes_file_operation_and_schema() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(document.pointer("/paths/~1api~1file/get").is_some());
+        assert!(
+            document
+                .pointer("/components/schemas/FileResponse")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn includes_blame_operation_and_schemas() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(document.pointer("/paths/~1api~1blame/get").is_some());
+        assert!(
+            document
+                .pointer("/components/schemas/BlameResponse")
+                .is_some()
+        );
+        assert!(document.pointer("/components/schemas/BlameRange").is_some());
+    }
+
+    #[test]
+    fn includes_suggest_operation_and_schemas() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(document.pointer("/paths/~1api~1suggest/get").is_some());
+        assert!(
+            document
+                .pointer("/components/schemas/SuggestResponse")
+                .is_some()
+        );
+        assert!(
+            document
+                .pointer("/components/schemas/IdentifierSuggestion")
+                .is_some()
+        );
+    }
 }
@@ -27,6 +27,14 @@ impl ApiError {
         }
     }
 
+    pub fn not_found<'a>(message: impl Into<Cow<'a, str>>) -> Self {
+        let message: Cow<'a, str> = message.into();
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into_owned(),
+        }
+    }
+
     pub fn internal(context: &str, err: impl std::fmt::Display) -> Self {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
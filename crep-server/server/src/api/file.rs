@@ -0,0 +1,584 @@
+use ahash::AHashMap;
+use axum::Json;
+use axum::extract::Query as QueryParams;
+use axum::extract::State;
+use crep_indexer::index::file_filter::not_indexed_message;
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::search::fuzzy::build_spellings_regex;
+use crep_indexer::search::fuzzy::default_max_edits;
+use crep_indexer::search::fuzzy::find_fuzzy_identifiers;
+use crep_indexer::search::result::search_result::RepoReader;
+use crep_indexer::search::result::single_commit_search_result::find_multiline_matches;
+use fst::Map;
+use git2::Oid;
+use regex::Regex;
+use roaring::RoaringBitmap;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::api::error::ApiError;
+use crate::api::error::ErrorResponse;
+use crate::api::search::SearchMode;
+use crate::search::search::CaptureSpan;
+use crate::search::search::LineHighlight;
+use crate::search::search::LineMatch;
+use crate::server_context::ServerContext;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FileParams {
+    pub path: String,
+
+    // Any revision that resolves to an indexed commit (e.g. a full or short
+    // sha). Defaults to the indexed HEAD.
+    pub commit: Option<String>,
+
+    // 0-based line range [start_line, end_line). Defaults to the whole file.
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+
+    // Highlights the matches of this query in the returned lines.
+    pub query: Option<String>,
+    #[serde(default)]
+    pub mode: SearchMode,
+    // Maximum edit distance of the fuzzy mode.
+    pub max_edits: Option<u32>,
+    // Only highlights the matches at the word boundaries.
+    #[serde(default)]
+    pub whole_word: bool,
+    // Regex matches can span multiple lines.
+    #[serde(default)]
+    pub multiline: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FileResponse {
+    pub path: String,
+    pub commit_sha: String,
+    pub is_head: bool,
+    pub total_lines: usize,
+    pub lines: Vec<LineMatch>,
+
+    // The commit that produced the content at `commit_sha`.
+    pub last_modified_commit: Option<String>,
+    // The commit that modified the file before `last_modified_commit`.
+    pub previous_commit: Option<String>,
+    // The first commit after `commit_sha` that modified (or deleted) the file.
+    pub next_commit: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/file",
+    params(FileParams),
+    responses(
+        (status = 200, description = "File content at the commit", body = FileResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "File or commit not found", body = ErrorResponse)
+    ),
+    tag = "file"
+)]
+pub async fn file(
+    State(context): State<ServerContext>,
+    QueryParams(params): QueryParams<FileParams>,
+) -> Result<Json<FileResponse>, ApiError> {
+    info!("File request {:?}", params);
+
+    let index_guard = context.indexer.get_search_index();
+    let repo_pool = context.repo_pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let index = index_guard.as_index_ref();
+
+        let highlighter = match params.query.as_deref().map(str::trim) {
+            Some(query) if !query.is_empty() => Some(build_highlighter(
+                query,
+                params.mode,
+                params.max_edits,
+                params.whole_word,
+                params.multiline,
+                index.identifiers,
+            )?),
+            _ => None,
+        };
+
+        let file_id =
+            *index.file_name_to_id.get(&params.path).ok_or_else(|| {
+                ApiError::not_found(not_indexed_message(
+                    &params.path,
+                    index.skipped_files.get(&params.path),
+                ))
+            })?;
+
+        let head_commit_index = index_guard
+            .head_commit_index()
+            .ok_or_else(|| ApiError::not_found("Nothing is indexed yet"))?;
+
+        let commit_index = match &params.commit {
+            Some(commit) => {
+                let commit_id = repo_pool
+                    .repos
+                    .first()
+                    .unwrap()
+                    .lock()
+                    .revparse_single(commit)
+                    .and_then(|object| object.peel_to_commit())
+                    .map_err(|e| ApiError::not_found(e.to_string()))?
+                    .id();
+
+                let mut commit_id_raw = [0u8; 20];
+                commit_id_raw.copy_from_slice(commit_id.as_bytes());
+
+                *index
+                    .commit_id_to_commit_index
+                    .get(&commit_id_raw)
+                    .ok_or_else(|| {
+                        ApiError::bad_request(format!(
+                            "{commit} is not indexed"
+                        ))
+                    })?
+            }
+            None => head_commit_index,
+        };
+
+        let (path, content) = repo_pool
+            .reader_for_current_thread(&index)
+            .read_file_at_commit(commit_index, file_id)
+            .ok()
+            .flatten()
+            .ok_or_else(|| {
+                ApiError::not_found(format!(
+                    "{} does not exist at the commit",
+                    params.path
+                ))
+            })?;
+
+        let all_lines = content.lines().collect::<Vec<_>>();
+        let end_line = params
+            .end_line
+            .unwrap_or(all_lines.len())
+            .min(all_lines.len());
+        let start_line = params.start_line.unwrap_or_default().min(end_line);
+
+        let mut multiline_highlights = match &highlighter {
+            Some(highlighter)
+                if params.multiline
+                    && matches!(params.mode, SearchMode::Regex) =>
+            {
+                Some(highlight_multiline(highlighter, &all_lines))
+            }
+            _ => None,
+        };
+
+        let lines = all_lines[start_line..end_line]
+            .iter()
+            .enumerate()
+            .map(|(offset, line)| {
+                let line_number = start_line + offset;
+                let highlights = match &mut multiline_highlights {
+                    Some(highlights) => {
+                        highlights.remove(&line_number).unwrap_or_default()
+                    }
+                    None => highlighter
+                        .as_ref()
+                        .map(|r| highlight_line(r, line))
+                        .unwrap_or_default(),
+                };
+
+                LineMatch {
+                    line_number,
+                    content: (*line).to_owned(),
+                    highlights,
+                    introduced_in: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let history = index
+            .file_id_to_document
+            .get(&file_id)
+            .map(|doc| {
+                ModificationHistory::new(
+                    &doc.doc_modified_commits,
+                    commit_index,
+                )
+            })
+            .unwrap_or_default();
+
+        let commit_sha = |commit_index: CommitIndex| {
+            Oid::from_bytes(&index.commit_index_to_commit_id[commit_index])
+                .map(|oid| oid.to_string())
+                .map_err(|e| ApiError::internal("Invalid commit id", e))
+        };
+
+        Ok(Json(FileResponse {
+            path,
+            commit_sha: commit_sha(commit_index)?,
+            is_head: commit_index == head_commit_index,
+            total_lines: all_lines.len(),
+            lines,
+            last_modified_commit: history
+                .last_modified
+                .map(commit_sha)
+                .transpose()?,
+            previous_commit: history.previous.map(commit_sha).transpose()?,
+            next_commit: history.next.map(commit_sha).transpose()?,
+        }))
+    })
+    .await
+    .map_err(|e| ApiError::internal("Error during join", e))?
+}
+
+// Fuzzy queries highlight every identifier within the edit distance.
+fn build_highlighter(
+    query: &str,
+    mode: SearchMode,
+    max_edits: Option<u32>,
+    whole_word: bool,
+    multiline: bool,
+    identifiers: &Map<Vec<u8>>,
+) -> Result<Regex, ApiError> {
+    let pattern = match mode {
+        SearchMode::Plain => query
+            .split_whitespace()
+            .map(|term| match term.strip_prefix("word:") {
+                Some(identifier) if !identifier.is_empty() => {
+                    format!(r"\b{}\b", regex::escape(identifier))
+                }
+                _ if whole_word => format!(r"\b{}\b", regex::escape(term)),
+                _ => regex::escape(term),
+            })
+            .collect::<Vec<_>>()
+            .join("|"),
+        SearchMode::Regex => {
+            let mut pattern = query.to_owned();
+            if whole_word {
+                pattern = format!(r"\b(?:{pattern})\b");
+            }
+            if multiline {
+                pattern = format!("(?m){pattern}");
+            }
+
+            pattern
+        }
+        SearchMode::Fuzzy => {
+            let spellings = find_fuzzy_identifiers(
+                identifiers,
+                query,
+                max_edits.unwrap_or_else(|| default_max_edits(query)),
+            )
+            .map_err(ApiError::bad_request)?;
+
+            if spellings.is_empty() {
+                // Matches nothing.
+                r"[^\s\S]".to_owned()
+            } else {
+                build_spellings_regex(&spellings)
+            }
+        }
+    };
+
+    Regex::new(&pattern).map_err(|e| ApiError::bad_request(e.to_string()))
+}
+
+fn highlight_line(highlighter: &Regex, line: &str) -> Vec<LineHighlight> {
+    highlighter
+        .captures_iter(line)
+        .filter_map(|captures| {
+            let m = captures.get(0).filter(|m| !m.is_empty())?;
+
+            Some(LineHighlight {
+                groups: captures
+                    .iter()
+                    .zip(highlighter.capture_names())
+                    .enumerate()
+                    .skip(1)
+                    .filter_map(|(index, (group, name))| {
+                        group.map(|group| {
+                            CaptureSpan::new(
+                                line,
+                                index,
+                                name.map(str::to_owned),
+                                group.range(),
+                            )
+                        })
+                    })
+                    .collect(),
+                ..LineHighlight::new(line, m.range())
+            })
+        })
+        .collect()
+}
+
+// Highlights of every line, where a match can span multiple lines.
+fn highlight_multiline(
+    highlighter: &Regex,
+    lines: &[&str],
+) -> AHashMap<usize, Vec<LineHighlight>> {
+    let mut highlights: AHashMap<usize, Vec<LineHighlight>> = AHashMap::new();
+
+    for span in find_multiline_matches(highlighter, lines) {
+        for (line_num, start, end) in span.fragments(lines) {
+            highlights.entry(line_num).or_default().push(LineHighlight {
+                end_line: (line_num == span.start_line)
+                    .then_some(span.end_line),
+                ..LineHighlight::new(lines[line_num], start..end)
+            });
+        }
+    }
+
+    highlights
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ModificationHistory {
+    last_modified: Option<CommitIndex>,
+    previous: Option<CommitIndex>,
+    next: Option<CommitIndex>,
+}
+
+impl ModificationHistory {
+    fn new(
+        modified_commits: &RoaringBitmap,
+        commit_index: CommitIndex,
+    ) -> Self {
+        let commit_index = commit_index as u32;
+
+        // Number of modifications at or before the commit.
+        let rank = modified_commits.rank(commit_index);
+        let last_modified = rank
+            .checked_sub(1)
+            .and_then(|n| modified_commits.select(n as u32));
+        let previous = rank
+            .checked_sub(2)
+            .and_then(|n| modified_commits.select(n as u32));
+        let next = modified_commits.select(rank as u32);
+
+        Self {
+            last_modified: last_modified.map(|c| c as CommitIndex),
+            previous: previous.map(|c| c as CommitIndex),
+            next: next.map(|c| c as CommitIndex),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modification_history_around_commit() {
+        let modified = RoaringBitmap::from_iter([1, 3, 5, 8]);
+
+        assert_eq!(
+            ModificationHistory::new(&modified, 5),
+            ModificationHistory {
+                last_modified: Some(5),
+                previous: Some(3),
+                next: Some(8),
+            }
+        );
+        assert_eq!(
+            ModificationHistory::new(&modified, 6),
+            ModificationHistory {
+                last_modified: Some(5),
+                previous: Some(3),
+                next: Some(8),
+            }
+        );
+        assert_eq!(
+            ModificationHistory::new(&modified, 1),
+            ModificationHistory {
+                last_modified: Some(1),
+                previous: None,
+                next: Some(3),
+            }
+        );
+        assert_eq!(
+            ModificationHistory::new(&modified, 9),
+            ModificationHistory {
+                last_modified: Some(8),
+                previous: Some(5),
+                next: None,
+            }
+        );
+    }
+
+    #[test]
+    fn highlights_every_plain_word() {
+        let highlighter = build_highlighter(
+            "foo  bar",
+            SearchMode::Plain,
+            None,
+            false,
+            false,
+            &Map::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            highlight_line(&highlighter, "foo(bar, foo)")
+                .into_iter()
+                .map(|h| (h.term, h.column))
+                .collect::<Vec<_>>(),
+            vec![
+                ("foo".to_owned(), 0),
+                ("bar".to_owned(), 4),
+                ("foo".to_owned(), 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_whole_words() {
+        let highlighter = build_highlighter(
+            "id word:user",
+            SearchMode::Plain,
+            None,
+            true,
+            false,
+            &Map::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            highlight_line(&highlighter, "valid(user.id, username)")
+                .into_iter()
+                .map(|h| (h.term, h.column))
+                .collect::<Vec<_>>(),
+            vec![("user".to_owned(), 6), ("id".to_owned(), 11)]
+        );
+    }
+
+    #[test]
+    fn highlights_carry_capture_groups() {
+        let highlighter = build_highlighter(
+            r"fn (?<name>\w+)",
+            SearchMode::Regex,
+            None,
+            false,
+            false,
+            &Map::default(),
+        )
+        .unwrap();
+
+        let highlights = highlight_line(&highlighter, "pub fn foo() {}");
+        assert_eq!(highlights.len(), 1);
+        assert_eq!(highlights[0].column, 4);
+        assert_eq!(
+            highlights[0].groups,
+            vec![CaptureSpan {
+                index: 1,
+                name: Some("name".to_owned()),
+                start: 7,
+                end: 10,
+                utf16_start: 7,
+                utf16_end: 10,
+            }]
+        );
+    }
+
+    #[test]
+    fn highlights_report_utf16_columns() {
+        let highlighter = build_highlighter(
+            "foo",
+            SearchMode::Plain,
+            None,
+            false,
+            false,
+            &Map::default(),
+        )
+        .unwrap();
+
+        // `é` is 2 bytes in UTF-8 and `🦀` is 4, but they are 1 and 2 code
+        // units in UTF-16.
+        let highlights = highlight_line(&highlighter, "é🦀 foo");
+        assert_eq!(
+            highlights
+                .iter()
+                .map(|h| (
+                    h.column,
+                    h.end_column,
+                    h.length,
+                    h.utf16_column,
+                    h.utf16_end_column,
+                    h.utf16_length
+                ))
+                .collect::<Vec<_>>(),
+            vec![(7, 10, 3, 4, 7, 3)]
+        );
+    }
+
+    #[test]
+    fn highlights_multiline_matches() {
+        let highlighter = build_highlighter(
+            r"\)\]\n\s*pub",
+            SearchMode::Regex,
+            None,
+            false,
+            true,
+            &Map::default(),
+        )
+        .unwrap();
+
+        let highlights = highlight_multiline(
+            &highlighter,
+            &["#[derive(Debug)]", "  pub struct Config;"],
+        );
+        assert_eq!(
+            highlights[&0]
+                .iter()
+                .map(|h| (h.term.as_str(), h.column, h.end_line))
+                .collect::<Vec<_>>(),
+            vec![(")]", 14, Some(1))]
+        );
+        assert_eq!(
+            highlights[&1]
+                .iter()
+                .map(|h| (h.term.as_str(), h.column, h.end_line))
+                .collect::<Vec<_>>(),
+            vec![("  pub", 0, None)]
+        );
+    }
+
+    #[test]
+    fn invalid_regex_is_bad_request() {
+        assert!(
+            build_highlighter(
+                "a(",
+                SearchMode::Regex,
+                None,
+                false,
+                false,
+                &Map::default()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn highlights_fuzzy_spellings() {
+        let identifiers =
+            Map::from_iter([("receive_buffer", 1), ("send_buffer", 1)])
+                .unwrap();
+        let highlighter = build_highlighter(
+            "recieve_buffer",
+            SearchMode::Fuzzy,
+            None,
+            false,
+            false,
+            &identifiers,
+        )
+        .unwrap();
+
+        assert_eq!(
+            highlight_line(&highlighter, "receive_buffer(send_buffer)")
+                .into_iter()
+                .map(|h| (h.term, h.column))
+                .collect::<Vec<_>>(),
+            vec![("receive_buffer".to_owned(), 0)]
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+
+use crate::api::error::ApiError;
+use crate::server_context::ServerContext;
+
+// Prometheus scrape endpoint.
+pub async fn metrics(
+    State(context): State<ServerContext>,
+) -> Result<impl IntoResponse, ApiError> {
+    context
+        .metrics
+        .set_index_size(&context.indexer.get_search_index().as_index_ref());
+
+    let body = context
+        .metrics
+        .encode()
+        .map_err(|e| ApiError::internal("Unable to encode metrics", e))?;
+
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body))
+}
@@ -1,11 +1,16 @@
 use axum::Json;
 use utoipa::OpenApi;
 
+pub mod blame;
 mod doc;
 pub mod error;
+pub mod file;
 pub mod health;
+pub mod metrics;
 pub mod reindex;
 pub mod search;
+pub mod search_stream;
+pub mod suggest;
 
 pub use doc::ApiDoc;
 pub use health::health;
@@ -1,20 +1,18 @@
-use std::path::Path;
-use std::sync::Arc;
-use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
 use axum::Json;
 use axum::extract::State;
-use crep_indexer::index::git_indexer::CommitIndex;
-use crep_indexer::index::git_indexer::FileId;
+use crep_indexer::search::cancellation::CancellationToken;
+use crep_indexer::search::fuzzy::default_max_edits;
 use crep_indexer::search::git_searcher::GitSearcher;
 use crep_indexer::search::git_searcher::Query;
 use crep_indexer::search::git_searcher::SearchOption;
-use crep_indexer::search::result::search_result::RepoReader;
+use crep_indexer::search::git_searcher::SearchOutcome;
 use crep_indexer::search::result::search_result::SearchResult;
-use git2::Oid;
-use git2::Repository;
+use crep_indexer::search::summary::MatchCount;
+use crep_indexer::search::summary::OutputMode;
+use crep_indexer::search::summary::collect_files_with_matches;
 use serde::Deserialize;
 use serde::Serialize;
 use tracing::info;
@@ -22,17 +20,65 @@ use utoipa::ToSchema;
 
 use crate::api::error::ApiError;
 use crate::api::error::ErrorResponse;
+use crate::indexer::index::SearchIndexGuard;
+use crate::metrics::SearchStage;
+use crate::search::search::FileMatch;
+use crate::search::search::SearchCount;
 use crate::search::search::SearchHit;
 use crate::search::search_cache::CacheResult;
+use crate::search::search_cache::SearchKey;
 use crate::server_context::ServerContext;
 use rayon::prelude::*;
 
+// Upper bound of `before_context` and `after_context`, so that a single hit
+// cannot carry the whole file.
+const MAX_CONTEXT_LINES: usize = 100;
+
+pub fn validate_context_lines(
+    before_context: Option<usize>,
+    after_context: Option<usize>,
+) -> Result<(), ApiError> {
+    match before_context.max(after_context) {
+        Some(lines) if lines > MAX_CONTEXT_LINES => Err(ApiError::bad_request(
+            format!("context lines must be at most {MAX_CONTEXT_LINES}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, ToSchema, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum SearchMode {
     #[default]
     Plain,
     Regex,
+    // Identifiers within a few edits of the query.
+    Fuzzy,
+}
+
+impl SearchMode {
+    // Label of the mode in the metrics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchMode::Plain => "plain",
+            SearchMode::Regex => "regex",
+            SearchMode::Fuzzy => "fuzzy",
+        }
+    }
+
+    // `max_edits` is only used by the fuzzy mode. Defaults to the one based on
+    // the length of the query.
+    pub fn build_query(self, query: &str, max_edits: Option<u32>) -> Query {
+        match self {
+            SearchMode::Plain => Query::Plain(query.to_owned()),
+            SearchMode::Regex => Query::Regex(query.to_owned()),
+            SearchMode::Fuzzy => Query::Fuzzy {
+                term: query.to_owned(),
+                max_edits: max_edits
+                    .unwrap_or_else(|| default_max_edits(query)),
+            },
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -40,6 +86,29 @@ pub struct SearchRequest {
     pub query: String,
     #[serde(default)]
     pub mode: SearchMode,
+    // Maximum edit distance of the fuzzy mode.
+    pub max_edits: Option<u32>,
+    // Only matches the terms at the word boundaries.
+    #[serde(default)]
+    pub whole_word: bool,
+    // Regex matches can span multiple lines.
+    #[serde(default)]
+    pub multiline: bool,
+    // Returns every match in the file instead of the first few.
+    #[serde(default)]
+    pub all_matches: bool,
+    // Number of the lines around every match. Defaults to 2.
+    pub before_context: Option<usize>,
+    pub after_context: Option<usize>,
+
+    // What the search returns. `count` and `files_with_matches` are answered
+    // from the index without reading the files, unless `verify` is set.
+    #[serde(default)]
+    pub output: OutputMode,
+    // Checks the `count` and `files_with_matches` candidates against the file
+    // contents, which makes them exact but reads every candidate commit.
+    #[serde(default)]
+    pub verify: bool,
 
     #[serde(default)]
     pub page: usize,
@@ -51,6 +120,18 @@ pub struct SearchRequest {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SearchResponse {
     pub results: Vec<Option<SearchHit>>,
+
+    // The HEAD commit of the index snapshot that answered the query.
+    pub head_commit: String,
+
+    // Set when the search hit the request timeout; `results` only contains
+    // what was found until then.
+    pub truncated: bool,
+
+    // Only set by the `count` output.
+    pub count: Option<SearchCount>,
+    // Only set by the `files_with_matches` output. Not paginated.
+    pub files: Option<Vec<FileMatch>>,
 }
 
 #[utoipa::path(
@@ -74,39 +155,93 @@ pub async fn search(
     if query.is_empty() {
         return Err(ApiError::bad_request("query must not be empty"));
     }
-
-    let index_guard = context.indexer.get_search_index().await;
-
-    let searcher = GitSearcher::new(index_guard.as_index_ref());
-    let option = Some(SearchOption {
-        max_num_to_find: None,
-    });
+    validate_context_lines(request.before_context, request.after_context)?;
+
+    let mode = request.mode.as_str();
+    context.metrics.search_requested(mode, "search");
+
+    let index_guard = context.indexer.get_search_index();
+    let head_commit_index = index_guard.head_commit_index().unwrap_or_default();
+
+    // Dropping the guard (e.g. the client went away and the request future is
+    // dropped) stops the search.
+    let cancellation = CancellationToken::new();
+    let _cancel_on_drop = cancellation.drop_guard();
+    let option = SearchOption {
+        deadline: context.search_deadline(),
+        cancellation: Some(cancellation),
+        whole_word: request.whole_word,
+        multiline: request.multiline,
+        all_matches: request.all_matches,
+        before_context: request.before_context,
+        after_context: request.after_context,
+        ..Default::default()
+    };
+    let mut truncated = false;
 
     let search_start = Instant::now();
 
-    let query = match request.mode {
-        SearchMode::Plain => Query::Plain(query.to_owned()),
-        SearchMode::Regex => Query::Regex(query.to_owned()),
+    let query = request.mode.build_query(query, request.max_edits);
+    let cache_key = SearchKey {
+        query: query.clone(),
+        whole_word: request.whole_word,
+        multiline: request.multiline,
+        all_matches: request.all_matches,
+        before_context: request.before_context,
+        after_context: request.after_context,
     };
 
+    if request.output != OutputMode::Matches {
+        let response = summarize_search(
+            &context,
+            &request,
+            &query,
+            &cache_key,
+            option,
+            &index_guard,
+        )
+        .await?;
+
+        context.metrics.observe_search(
+            mode,
+            SearchStage::Total,
+            Instant::now().duration_since(search_start),
+        );
+
+        return Ok(Json(response));
+    }
+
     let results = context.search_cache.find(
-        &query,
+        &cache_key,
+        head_commit_index,
         request.page * request.page_size
             ..(request.page + 1) * request.page_size,
     );
 
     let results = match results {
         None => {
-            let raw_results = searcher
-                .search(&query, option)
-                .map(|res| {
-                    res.into_iter().map(CacheResult::Miss).collect::<Vec<_>>()
-                })
-                .map_err(ApiError::bad_request);
-
-            if let Ok(raw_results) = &raw_results {
+            let raw_results = run_raw_search(&index_guard, &query, &option)
+                .await
+                .map(|outcome| {
+                    truncated = outcome.truncated;
+                    context.metrics.observe_candidate_documents(
+                        mode,
+                        outcome.results.len(),
+                    );
+                    outcome
+                        .results
+                        .into_iter()
+                        .map(CacheResult::Miss)
+                        .collect::<Vec<_>>()
+                });
+
+            // Partial results must not be served to the later requests.
+            if let Ok(raw_results) = &raw_results
+                && !truncated
+            {
                 context.search_cache.put_raw_result(
-                    &query,
+                    &cache_key,
+                    head_commit_index,
                     raw_results
                         .iter()
                         .map(|r| match r {
@@ -136,14 +271,20 @@ pub async fn search(
         Some(results) => Ok(results),
     }?;
 
+    let raw_duration = Instant::now().duration_since(search_start);
+    context
+        .metrics
+        .observe_search(mode, SearchStage::Raw, raw_duration);
+
     info!(
         "Getting raw results took: {}ms, count: {}",
-        Instant::now().duration_since(search_start).as_millis(),
+        raw_duration.as_millis(),
         results.len()
     );
 
     let repo_pool = context.repo_pool.clone();
     let index_guard_cloned = index_guard.clone();
+    let conversion_option = option.clone();
 
     let conversion_start = Instant::now();
     let result = tokio::task::spawn_blocking(move || {
@@ -152,15 +293,7 @@ pub async fn search(
         results
             .into_par_iter()
             .map_init(
-                || ThreadSafeRepoReader {
-                    repo: repo_pool
-                        .repos
-                        .get(rayon::current_thread_index().unwrap())
-                        .unwrap()
-                        .clone(),
-                    file_id_to_path: index.file_id_to_path,
-                    commit_index_to_commit_id: index.commit_index_to_commit_id,
-                },
+                || repo_pool.reader_for_current_thread(&index),
                 |reader, result| match result {
                     CacheResult::Hit(search_res) => {
                         Ok(SearchConversionResult {
@@ -170,21 +303,33 @@ pub async fn search(
                     }
                     CacheResult::Miss(raw_res) => {
                         let conversion_start = Instant::now();
-                        SearchResult::new(reader, &raw_res)
-                            .map_err(|e| {
-                                ApiError::internal(
-                                    "Unable to parse search result",
-                                    e,
-                                )
-                            })
-                            .map(|r| SearchConversionResult {
+                        SearchResult::new_with_option(
+                            reader,
+                            &raw_res,
+                            &conversion_option,
+                        )
+                        .map_err(|e| {
+                            ApiError::internal(
+                                "Unable to parse search result",
+                                e,
+                            )
+                        })
+                        .map(|r| {
+                            // The result might be incomplete (or missing)
+                            // if the conversion was interrupted.
+                            let interrupted =
+                                conversion_option.is_interrupted();
+
+                            SearchConversionResult {
                                 result: r,
-                                should_update_cache: true,
+                                should_update_cache: !interrupted,
+                                interrupted,
                                 duration: Some(
                                     Instant::now()
                                         .duration_since(conversion_start),
                                 ),
-                            })
+                            }
+                        })
                     }
                     _ => Ok(SearchConversionResult::default()),
                 },
@@ -196,11 +341,23 @@ pub async fn search(
     .into_iter()
     .collect::<Result<Vec<SearchConversionResult>, _>>()?;
 
+    let conversion_duration = Instant::now().duration_since(conversion_start);
+    context.metrics.observe_search(
+        mode,
+        SearchStage::Conversion,
+        conversion_duration,
+    );
+
     info!(
         "To SearchResult conversion took {}ms",
-        Instant::now().duration_since(conversion_start).as_millis()
+        conversion_duration.as_millis()
     );
 
+    truncated |= result.iter().any(|c| c.interrupted);
+    if truncated {
+        context.metrics.search_truncated(mode);
+    }
+
     let timings = result
         .iter()
         .filter_map(|c| c.duration.map(|c| c.as_millis()))
@@ -224,18 +381,20 @@ pub async fn search(
             }
         });
 
-    context
-        .search_cache
-        .put_search_results(&query, results_to_update);
+    context.search_cache.put_search_results(
+        &cache_key,
+        head_commit_index,
+        results_to_update,
+    );
 
-    let repo = context.repo_pool.repos.first().unwrap().lock().unwrap();
+    let repo = context.repo_pool.repos.first().unwrap().lock();
     let results = result
         .into_iter()
         .map(|c| {
             if let Some(result) = c.result {
                 SearchHit::from_search_result(
                     &repo,
-                    index_guard.as_index_ref().commit_index_to_commit_id,
+                    &index_guard.as_index_ref(),
                     result,
                 )
                 .map(Some)
@@ -251,46 +410,128 @@ pub async fn search(
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(Json(SearchResponse { results }))
-}
+    context.metrics.observe_search(
+        mode,
+        SearchStage::Total,
+        Instant::now().duration_since(search_start),
+    );
 
-#[derive(Default)]
-struct SearchConversionResult {
-    result: Option<SearchResult>,
-    should_update_cache: bool,
-    duration: Option<Duration>,
+    Ok(Json(SearchResponse {
+        results,
+        head_commit: index_guard.head_commit_id().unwrap_or_default(),
+        truncated,
+        count: None,
+        files: None,
+    }))
 }
 
-struct ThreadSafeRepoReader<'i> {
-    pub repo: Arc<Mutex<Repository>>,
-    pub file_id_to_path: &'i [String],
-    pub commit_index_to_commit_id: &'i [[u8; 20]],
+// Runs the raw search on the blocking pool, as it never yields to tokio.
+async fn run_raw_search(
+    index_guard: &SearchIndexGuard,
+    query: &Query,
+    option: &SearchOption,
+) -> Result<SearchOutcome, ApiError> {
+    let index_guard = index_guard.clone();
+    let query = query.clone();
+    let option = option.clone();
+
+    tokio::task::spawn_blocking(move || {
+        GitSearcher::new(index_guard.as_index_ref())
+            .search(&query, Some(option))
+    })
+    .await
+    .map_err(|e| ApiError::internal("Error during join", e))?
+    .map_err(ApiError::bad_request)
 }
 
-impl<'i> RepoReader for ThreadSafeRepoReader<'i> {
-    fn read_file_at_commit(
-        &self,
-        commit_id: CommitIndex,
-        file_id: FileId,
-    ) -> anyhow::Result<Option<(/*file path*/ String, /*content*/ String)>>
-    {
-        let file_path = self.file_id_to_path.get(file_id).unwrap();
-        let commit =
-            Oid::from_bytes(&self.commit_index_to_commit_id[commit_id])?;
-
-        let repo = self.repo.lock().unwrap();
-        let commit = repo.find_commit(commit)?;
-        let tree = commit.tree()?;
-
-        let entry = tree.get_path(Path::new(&file_path))?;
-        let object = entry.to_object(&repo)?;
-        if let Some(blob) = object.as_blob() {
-            Ok(Some((
-                file_path.to_owned(),
-                String::from_utf8_lossy(blob.content()).to_string(),
-            )))
-        } else {
-            Ok(None)
+// Answers the `count` and `files_with_matches` outputs from the bitmaps of the
+// raw results.
+async fn summarize_search(
+    context: &ServerContext,
+    request: &SearchRequest,
+    query: &Query,
+    cache_key: &SearchKey,
+    option: SearchOption,
+    index_guard: &SearchIndexGuard,
+) -> Result<SearchResponse, ApiError> {
+    let head_commit_index = index_guard.head_commit_index().unwrap_or_default();
+
+    let (raw_results, mut truncated) =
+        match context.search_cache.find_raw(cache_key, head_commit_index) {
+            Some(raw_results) => (raw_results, false),
+            None => {
+                let outcome =
+                    run_raw_search(index_guard, query, &option).await?;
+
+                if !outcome.truncated {
+                    context.search_cache.put_raw_result(
+                        cache_key,
+                        head_commit_index,
+                        outcome.results.clone(),
+                    );
+                }
+
+                (outcome.results, outcome.truncated)
+            }
+        };
+
+    let raw_results = match request.verify {
+        true => {
+            let repo_pool = context.repo_pool.clone();
+            let index_guard = index_guard.clone();
+            let verify_option = option.clone();
+
+            let verified = tokio::task::spawn_blocking(move || {
+                let index = index_guard.as_index_ref();
+
+                raw_results
+                    .par_iter()
+                    .map_init(
+                        || repo_pool.reader_for_current_thread(&index),
+                        |reader, result| {
+                            SearchResult::verify(reader, result, &verify_option)
+                        },
+                    )
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .await
+            .map_err(|e| ApiError::internal("Error during join", e))?
+            .map_err(|e| {
+                ApiError::internal("Unable to verify search result", e)
+            })?;
+
+            truncated |= option.is_interrupted();
+            verified.into_iter().flatten().collect()
         }
+        false => raw_results,
+    };
+
+    let files =
+        collect_files_with_matches(&index_guard.as_index_ref(), &raw_results);
+    let (count, files) = match request.output {
+        OutputMode::Count => {
+            (Some(SearchCount::from(MatchCount::new(&files))), None)
+        }
+        _ => (None, Some(files.iter().map(FileMatch::from).collect())),
+    };
+
+    if truncated {
+        context.metrics.search_truncated(request.mode.as_str());
     }
+
+    Ok(SearchResponse {
+        results: vec![],
+        head_commit: index_guard.head_commit_id().unwrap_or_default(),
+        truncated,
+        count,
+        files,
+    })
+}
+
+#[derive(Default)]
+struct SearchConversionResult {
+    result: Option<SearchResult>,
+    should_update_cache: bool,
+    interrupted: bool,
+    duration: Option<Duration>,
 }
@@ -0,0 +1,538 @@
+use std::convert::Infallible;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use axum::extract::Query as QueryParams;
+use axum::extract::State;
+use axum::response::Sse;
+use axum::response::sse::Event;
+use axum::response::sse::KeepAlive;
+use crep_indexer::index::git_index::GitIndexRef;
+use crep_indexer::search::cancellation::CancellationToken;
+use crep_indexer::search::git_searcher::GitSearcher;
+use crep_indexer::search::git_searcher::RawPerFileSearchResult;
+use crep_indexer::search::git_searcher::SearchOption;
+use crep_indexer::search::result::search_result::SearchResult;
+use futures_util::Stream;
+use futures_util::stream;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::info;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::api::error::ApiError;
+use crate::api::error::ErrorResponse;
+use crate::api::search::SearchMode;
+use crate::api::search::validate_context_lines;
+use crate::metrics::SearchStage;
+use crate::search::repo_pool::RepoPool;
+use crate::search::search::SearchHit;
+use crate::search::search_cache::SearchKey;
+use crate::server_context::ServerContext;
+
+// Number of converted candidates between two progress events.
+const PROGRESS_INTERVAL: usize = 16;
+
+// Number of events that can be buffered before the conversion workers have to
+// wait for the client to catch up.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchStreamParams {
+    pub query: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+    // Maximum edit distance of the fuzzy mode.
+    pub max_edits: Option<u32>,
+    // Only matches the terms at the word boundaries.
+    #[serde(default)]
+    pub whole_word: bool,
+    // Regex matches can span multiple lines.
+    #[serde(default)]
+    pub multiline: bool,
+    // Returns every match in the file instead of the first few.
+    #[serde(default)]
+    pub all_matches: bool,
+    // Number of the lines around every match. Defaults to 2.
+    pub before_context: Option<usize>,
+    pub after_context: Option<usize>,
+}
+
+// Sent as the `hit` event.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchStreamHit {
+    // Index of the hit within the raw (unconverted) results. Hits are not
+    // emitted in this order.
+    pub index: usize,
+    pub hit: SearchHit,
+}
+
+// Sent as the `progress` event.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchStreamProgress {
+    // Number of candidate documents that are checked so far.
+    pub checked: usize,
+    // Total number of candidate documents.
+    pub total: usize,
+}
+
+// Sent as the `done` event. No more events follow.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchStreamDone {
+    pub num_hits: usize,
+    pub head_commit: String,
+
+    // Set when the search hit the request timeout before checking every
+    // candidate.
+    pub truncated: bool,
+}
+
+enum StreamEvent {
+    Hit(Box<SearchStreamHit>),
+    Progress(SearchStreamProgress),
+    Done(SearchStreamDone),
+    Error(ErrorResponse),
+}
+
+impl StreamEvent {
+    fn into_sse_event(self) -> Event {
+        let event = match &self {
+            StreamEvent::Hit(hit) => {
+                Event::default().event("hit").json_data(hit)
+            }
+            StreamEvent::Progress(progress) => {
+                Event::default().event("progress").json_data(progress)
+            }
+            StreamEvent::Done(done) => {
+                Event::default().event("done").json_data(done)
+            }
+            StreamEvent::Error(error) => {
+                Event::default().event("error").json_data(error)
+            }
+        };
+
+        event.unwrap_or_else(|e| {
+            Event::default().event("error").data(e.to_string())
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/search/stream",
+    params(SearchStreamParams),
+    responses(
+        (status = 200, description = "Server-Sent Events. `hit` events carry SearchStreamHit, `progress` events SearchStreamProgress and the final `done` event SearchStreamDone", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid query", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn search_stream(
+    State(context): State<ServerContext>,
+    QueryParams(params): QueryParams<SearchStreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    info!("Stream request {:?}", params);
+
+    let query = params.query.trim();
+    if query.is_empty() {
+        return Err(ApiError::bad_request("query must not be empty"));
+    }
+    validate_context_lines(params.before_context, params.after_context)?;
+
+    let mode = params.mode.as_str();
+    context.metrics.search_requested(mode, "stream");
+
+    let search_start = Instant::now();
+    let query = params.mode.build_query(query, params.max_edits);
+    let index_guard = context.indexer.get_search_index();
+    let head_commit_index = index_guard.head_commit_index().unwrap_or_default();
+
+    // The guard lives as long as the response stream, so the search stops
+    // once the client disconnects.
+    let cancellation = CancellationToken::new();
+    let cancel_on_drop = cancellation.drop_guard();
+    let option = SearchOption {
+        deadline: context.search_deadline(),
+        cancellation: Some(cancellation),
+        whole_word: params.whole_word,
+        multiline: params.multiline,
+        all_matches: params.all_matches,
+        before_context: params.before_context,
+        after_context: params.after_context,
+        ..Default::default()
+    };
+    let cache_key = SearchKey {
+        query: query.clone(),
+        whole_word: params.whole_word,
+        multiline: params.multiline,
+        all_matches: params.all_matches,
+        before_context: params.before_context,
+        after_context: params.after_context,
+    };
+
+    let (raw_results, raw_truncated) =
+        match context.search_cache.find_raw(&cache_key, head_commit_index) {
+            Some(raw_results) => (raw_results, false),
+            None => {
+                let index_guard = index_guard.clone();
+                let query_cloned = query.clone();
+                let option = option.clone();
+                let outcome = tokio::task::spawn_blocking(move || {
+                    GitSearcher::new(index_guard.as_index_ref())
+                        .search(&query_cloned, Some(option))
+                })
+                .await
+                .map_err(|e| ApiError::internal("Error during join", e))?
+                .map_err(ApiError::bad_request)?;
+
+                context
+                    .metrics
+                    .observe_candidate_documents(mode, outcome.results.len());
+                context.metrics.observe_search(
+                    mode,
+                    SearchStage::Raw,
+                    search_start.elapsed(),
+                );
+
+                if !outcome.truncated {
+                    context.search_cache.put_raw_result(
+                        &cache_key,
+                        head_commit_index,
+                        outcome.results.clone(),
+                    );
+                }
+
+                (outcome.results, outcome.truncated)
+            }
+        };
+
+    let (send, recv) = mpsc::channel::<StreamEvent>(EVENT_BUFFER_SIZE);
+    let repo_pool = context.repo_pool.clone();
+    let metrics = context.metrics.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let conversion_start = Instant::now();
+        let index = index_guard.as_index_ref();
+        let total = raw_results.len();
+
+        // Every send fails once the client is gone (the receiver is dropped
+        // together with the response stream), which stops the remaining
+        // conversions.
+        let Some(num_hits) =
+            send_hits(&index, &repo_pool, &raw_results, &option, |event| {
+                send.blocking_send(event).is_ok()
+            })
+        else {
+            info!("Client disconnected; stopped streaming the results");
+            return;
+        };
+
+        let truncated = raw_truncated || option.is_interrupted();
+        if truncated {
+            metrics.search_truncated(mode);
+        }
+
+        metrics.observe_search(
+            mode,
+            SearchStage::Conversion,
+            conversion_start.elapsed(),
+        );
+        metrics.observe_search(
+            mode,
+            SearchStage::Total,
+            search_start.elapsed(),
+        );
+
+        let _ =
+            send.blocking_send(StreamEvent::Progress(SearchStreamProgress {
+                checked: total,
+                total,
+            }));
+        let _ = send.blocking_send(StreamEvent::Done(SearchStreamDone {
+            num_hits,
+            head_commit: index_guard.head_commit_id().unwrap_or_default(),
+            truncated,
+        }));
+    });
+
+    let stream = stream::unfold(
+        (recv, cancel_on_drop),
+        |(mut recv, guard)| async move {
+            recv.recv()
+                .await
+                .map(|event| (Ok(event.into_sse_event()), (recv, guard)))
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// Converts the raw results in parallel and passes every hit (and the progress
+// in between) to `send`. Returns the number of hits, or None as soon as `send`
+// returns false.
+fn send_hits(
+    index: &GitIndexRef,
+    repo_pool: &RepoPool,
+    raw_results: &[RawPerFileSearchResult],
+    option: &SearchOption,
+    send: impl Fn(StreamEvent) -> bool + Sync,
+) -> Option<usize> {
+    let total = raw_results.len();
+    let checked = AtomicUsize::new(0);
+    let num_hits = AtomicUsize::new(0);
+
+    let completed: Result<(), ()> =
+        raw_results.par_iter().enumerate().try_for_each_init(
+            || repo_pool.reader_for_current_thread(index),
+            |reader, (result_index, raw_result)| {
+                let event = match SearchResult::new_with_option(
+                    reader, raw_result, option,
+                ) {
+                    Ok(Some(result)) => SearchHit::from_search_result(
+                        &reader.repo.lock(),
+                        index,
+                        result,
+                    )
+                    .map(|hit| {
+                        num_hits.fetch_add(1, Ordering::Relaxed);
+                        Some(StreamEvent::Hit(Box::new(SearchStreamHit {
+                            index: result_index,
+                            hit,
+                        })))
+                    })
+                    .unwrap_or_else(|e| {
+                        Some(StreamEvent::Error(ErrorResponse {
+                            message: format!(
+                                "Unable to convert search result: {e}"
+                            ),
+                        }))
+                    }),
+                    Ok(None) => None,
+                    Err(e) => Some(StreamEvent::Error(ErrorResponse {
+                        message: format!("Unable to parse search result: {e}"),
+                    })),
+                };
+
+                if let Some(event) = event
+                    && !send(event)
+                {
+                    return Err(());
+                }
+
+                let checked = checked.fetch_add(1, Ordering::Relaxed) + 1;
+                if checked.is_multiple_of(PROGRESS_INTERVAL)
+                    && !send(StreamEvent::Progress(SearchStreamProgress {
+                        checked,
+                        total,
+                    }))
+                {
+                    return Err(());
+                }
+
+                Ok(())
+            },
+        );
+
+    completed.ok().map(|_| num_hits.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::response::IntoResponse;
+    use crep_indexer::index::git_indexer::GitIndexer;
+    use crep_indexer::index::git_indexer::GitIndexerConfig;
+    use crep_indexer::search::git_searcher::Query;
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::indexer::indexer::Indexer;
+    use crate::metrics::Metrics;
+    use crate::search::search_cache::SearchCache;
+    use crate::test_util::init_repo;
+    use crate::test_util::run;
+
+    // Commits every file at once and indexes the repo.
+    fn build_context(
+        files: &[(String, &str)],
+        search_timeout: Option<Duration>,
+    ) -> (TempDir, ServerContext) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+        for (name, content) in files {
+            std::fs::write(repo_path.join(name), content).unwrap();
+        }
+        run(repo_path, &["git", "add", "."]);
+        run(repo_path, &["git", "commit", "-m", "files"]);
+
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+        indexer
+            .index_history(git2::Repository::open(repo_path).unwrap())
+            .unwrap();
+
+        let metrics = Arc::new(Metrics::new());
+        let (send_reindex_signal, _) = tokio::sync::mpsc::unbounded_channel();
+        let context = ServerContext {
+            indexer: Arc::new(Indexer::new(
+                indexer,
+                repo_path,
+                send_reindex_signal,
+            )),
+            repo_pool: Arc::new(RepoPool::new(
+                repo_path.to_str().unwrap(),
+                metrics.clone(),
+            )),
+            search_cache: Arc::new(SearchCache::new(
+                NonZeroUsize::new(16).unwrap(),
+                metrics.clone(),
+            )),
+            search_timeout,
+            metrics,
+        };
+
+        (dir, context)
+    }
+
+    fn params(query: &str) -> SearchStreamParams {
+        SearchStreamParams {
+            query: query.to_owned(),
+            mode: SearchMode::Plain,
+            max_edits: None,
+            whole_word: false,
+            multiline: false,
+            all_matches: false,
+            before_context: None,
+            after_context: None,
+        }
+    }
+
+    // Reads the whole response as the (event, data) pairs.
+    async fn stream_events(
+        context: ServerContext,
+        params: SearchStreamParams,
+    ) -> Vec<(String, Value)> {
+        let body = search_stream(State(context), QueryParams(params))
+            .await
+            .unwrap()
+            .into_response()
+            .into_body();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .split("\n\n")
+            .filter_map(|block| {
+                let mut event = None;
+                let mut data = None;
+                for line in block.lines() {
+                    if let Some(name) = line.strip_prefix("event:") {
+                        event = Some(name.trim().to_owned());
+                    } else if let Some(json) = line.strip_prefix("data:") {
+                        data = Some(serde_json::from_str(json.trim()).unwrap());
+                    }
+                }
+
+                Some((event?, data?))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn streams_hits_and_done() {
+        let (_dir, context) = build_context(
+            &[
+                ("a.txt".to_owned(), "hello world"),
+                ("b.txt".to_owned(), "say hello"),
+                ("c.txt".to_owned(), "goodbye"),
+            ],
+            None,
+        );
+
+        let events = stream_events(context, params("hello")).await;
+
+        let mut hit_paths = events
+            .iter()
+            .filter(|(event, _)| event == "hit")
+            .map(|(_, data)| data["hit"]["file_path"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        hit_paths.sort();
+        assert_eq!(hit_paths, vec!["a.txt", "b.txt"]);
+
+        assert!(!events.iter().any(|(event, _)| event == "error"));
+
+        let (event, done) = events.last().unwrap();
+        assert_eq!(event, "done");
+        assert_eq!(done["num_hits"], 2);
+        assert_eq!(done["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn done_reports_truncation() {
+        let (_dir, context) = build_context(
+            &[
+                ("a.txt".to_owned(), "hello world"),
+                ("b.txt".to_owned(), "say hello"),
+            ],
+            // Every search is past the deadline before it starts.
+            Some(Duration::ZERO),
+        );
+
+        let events = stream_events(context, params("hello")).await;
+
+        let (event, done) = events.last().unwrap();
+        assert_eq!(event, "done");
+        assert_eq!(done["truncated"], true);
+    }
+
+    #[test]
+    fn dropped_receiver_stops_conversions() {
+        // More candidates than the ones that can be in flight at once.
+        let num_files = rayon::current_num_threads() * 4 + 16;
+        let files = (0..num_files)
+            .map(|i| (format!("{i}.txt"), "hello"))
+            .collect::<Vec<_>>();
+        let (_dir, context) = build_context(&files, None);
+
+        let index_guard = context.indexer.get_search_index();
+        let index = index_guard.as_index_ref();
+        let raw_results = GitSearcher::new(index_guard.as_index_ref())
+            .search(&Query::Plain("hello".to_owned()), None)
+            .unwrap()
+            .results;
+        assert_eq!(raw_results.len(), num_files);
+
+        let (send, recv) = mpsc::channel::<StreamEvent>(EVENT_BUFFER_SIZE);
+        drop(recv);
+
+        let num_sends = AtomicUsize::new(0);
+        let num_hits = send_hits(
+            &index,
+            &context.repo_pool,
+            &raw_results,
+            &SearchOption::default(),
+            |event| {
+                num_sends.fetch_add(1, Ordering::Relaxed);
+                send.blocking_send(event).is_ok()
+            },
+        );
+
+        assert_eq!(num_hits, None);
+        // Only the conversions that were already running try to send.
+        assert!(num_sends.into_inner() <= rayon::current_num_threads() * 2);
+    }
+}
@@ -0,0 +1,79 @@
+use axum::Json;
+use axum::extract::Query as QueryParams;
+use axum::extract::State;
+use crep_indexer::search::suggest::suggest_identifiers;
+use crep_indexer::search::suggest::suggest_paths;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::api::error::ApiError;
+use crate::api::error::ErrorResponse;
+use crate::server_context::ServerContext;
+
+const DEFAULT_SUGGESTION_LIMIT: usize = 10;
+const MAX_SUGGESTION_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SuggestParams {
+    pub prefix: String,
+
+    // Maximum number of identifiers and paths to return (each).
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct IdentifierSuggestion {
+    pub identifier: String,
+    // Number of occurrences at the indexed HEAD.
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SuggestResponse {
+    // Identifiers that start with the prefix, most frequent first.
+    pub identifiers: Vec<IdentifierSuggestion>,
+    // Paths at the indexed HEAD that contain the prefix.
+    pub paths: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/suggest",
+    params(SuggestParams),
+    responses(
+        (status = 200, description = "Completions for the prefix", body = SuggestResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn suggest(
+    State(context): State<ServerContext>,
+    QueryParams(params): QueryParams<SuggestParams>,
+) -> Result<Json<SuggestResponse>, ApiError> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SUGGESTION_LIMIT)
+        .min(MAX_SUGGESTION_LIMIT);
+
+    let index_guard = context.indexer.get_search_index();
+
+    tokio::task::spawn_blocking(move || {
+        let index = index_guard.as_index_ref();
+        let prefix = params.prefix.trim();
+
+        Json(SuggestResponse {
+            identifiers: suggest_identifiers(index.identifiers, prefix, limit)
+                .into_iter()
+                .map(|s| IdentifierSuggestion {
+                    identifier: s.identifier,
+                    count: s.count,
+                })
+                .collect(),
+            paths: suggest_paths(index.all_paths, prefix, limit),
+        })
+    })
+    .await
+    .map_err(|e| ApiError::internal("Error during join", e))
+}
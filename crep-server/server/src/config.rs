@@ -1,5 +1,6 @@
 use std::fs;
 
+use crep_indexer::index::file_filter::FileFilterConfig;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -9,6 +10,14 @@ pub struct ServerConfig {
     pub live_index_config: Option<LiveIndexConfig>,
 
     pub saved_index_path: String,
+
+    // Searches that take longer than this return the partial results found
+    // so far.
+    pub search_timeout_milliseconds: Option<u64>,
+
+    // Which files are indexed when the new commits are indexed.
+    #[serde(default)]
+    pub file_filter: FileFilterConfig,
 }
 
 #[derive(Deserialize)]
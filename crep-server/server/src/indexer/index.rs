@@ -3,25 +3,41 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crep_indexer::index::git_index::GitIndexRef;
+use crep_indexer::index::git_indexer::CommitIndex;
 use crep_indexer::index::git_indexer::GitIndexer;
+use crep_indexer::index::identifier::build_identifier_map;
+use crep_indexer::index::identifier::build_path_set;
+use fst::Map;
 use fst::Set;
-use tokio::sync::OwnedRwLockReadGuard;
 
 // Current git index status. The GitIndex is generated as a read only "view"
 // so that we dont unnecessarily copy the data.
+//
+// A SearchIndex is never mutated once it is published to the readers. The
+// re-indexing is done on a copy, which replaces the published snapshot when
+// it is complete.
 pub struct SearchIndex {
     indexer: GitIndexer,
     repo_path: PathBuf,
     all_words: Set<Vec<u8>>,
+    identifiers: Map<Vec<u8>>,
+    all_paths: Set<Vec<u8>>,
 }
 
 impl SearchIndex {
     pub fn new(indexer: GitIndexer, repo_path: &Path) -> Self {
         let all_words = build_all_words(&indexer);
+        let identifiers = build_identifier_map(&indexer.identifier_counts);
+        let all_paths = build_path_set(
+            &indexer.file_id_to_path,
+            &indexer.file_id_to_document,
+        );
 
         Self {
             indexer,
             all_words,
+            identifiers,
+            all_paths,
             repo_path: PathBuf::from(repo_path),
         }
     }
@@ -30,19 +46,64 @@ impl SearchIndex {
         GitIndexRef {
             commit_index_to_commit_id: &self.indexer.commit_index_to_commit_id,
             commit_id_to_commit_index: &self.indexer.commit_id_to_commit_index,
+            file_name_to_id: &self.indexer.file_name_to_id,
             file_id_to_path: &self.indexer.file_id_to_path,
+            file_id_to_diff_tracker: &self.indexer.file_id_to_diff_tracker,
             file_id_to_document: &self.indexer.file_id_to_document,
             word_to_file_id_ever_contained: &self
                 .indexer
                 .word_to_file_id_ever_contained,
+            identifier_part_to_file_id_ever_contained: &self
+                .indexer
+                .identifier_part_to_file_id_ever_contained,
             all_words: &self.all_words,
+            identifiers: &self.identifiers,
+            all_paths: &self.all_paths,
+            skipped_files: &self.indexer.skipped_files,
+            file_kinds: &self.indexer.file_kinds,
         }
     }
 
+    // Index of the last commit that this snapshot contains.
+    pub fn head_commit_index(&self) -> Option<CommitIndex> {
+        self.indexer.commit_index_to_commit_id.len().checked_sub(1)
+    }
+
+    pub fn head_commit_id(&self) -> Option<String> {
+        self.indexer
+            .commit_index_to_commit_id
+            .last()
+            .map(hex::encode)
+    }
+
+    // Number of commits reachable from `commit_id` that this snapshot does not
+    // contain yet.
+    pub fn commits_behind(&self, commit_id: &str) -> anyhow::Result<usize> {
+        let repo = git2::Repository::open(&self.repo_path)?;
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(git2::Oid::from_str(commit_id)?)?;
+        if let Some(head) = self.indexer.commit_index_to_commit_id.last() {
+            revwalk.hide(git2::Oid::from_bytes(head)?)?;
+        }
+
+        Ok(revwalk.count())
+    }
+
+    // Builds the new snapshot that contains `commit_id`. Returns None if the
+    // commit is already indexed.
+    //
+    // The current snapshot is left untouched so that the readers can keep
+    // searching over it while the new one is being built.
+    //
+    // This is not free: the whole GitIndexer (every Document, the trigram
+    // maps and the blob token cache) is cloned before indexing, so the peak
+    // memory is roughly twice the size of the index until the old snapshot
+    // is released by its last reader.
     pub fn do_incremental_index(
-        &mut self,
+        &self,
         commit_id: &str,
-    ) -> anyhow::Result<bool> {
+    ) -> anyhow::Result<Option<SearchIndex>> {
         let mut commit_id_raw = [0u8; 20];
         hex::decode_to_slice(commit_id, &mut commit_id_raw)?;
 
@@ -52,16 +113,19 @@ impl SearchIndex {
             .get(&commit_id_raw)
             .is_some()
         {
-            return Ok(false);
+            return Ok(None);
         }
 
         // This is the commit that is not seen yet. Let's re-index!
 
         let repo = git2::Repository::open(&self.repo_path)?;
-        self.indexer.index_history(repo)?;
-        self.all_words = build_all_words(&self.indexer);
 
-        Ok(true)
+        // Readers search directly over the maps of the GitIndexer, so the new
+        // commits can not be indexed in place without blocking them.
+        let mut indexer = self.indexer.clone();
+        indexer.index_history(repo)?;
+
+        Ok(Some(SearchIndex::new(indexer, &self.repo_path)))
     }
 }
 
@@ -76,9 +140,10 @@ fn build_all_words(indexer: &GitIndexer) -> Set<Vec<u8>> {
     Set::from_iter(keys).expect("keys are sorted and deduplicated")
 }
 
-// Represents the immutable view over the GitIndex.
+// Represents the immutable view over the GitIndex. Holding the guard keeps
+// the snapshot alive even after the re-indexer publishes the newer one.
 #[derive(Clone)]
-pub struct SearchIndexGuard(pub Arc<OwnedRwLockReadGuard<SearchIndex>>);
+pub struct SearchIndexGuard(pub Arc<SearchIndex>);
 
 impl SearchIndexGuard {
     pub fn as_index_ref(&self) -> GitIndexRef<'_> {
@@ -93,3 +158,53 @@ impl Deref for SearchIndexGuard {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crep_indexer::index::git_indexer::GitIndexerConfig;
+
+    use super::*;
+    use crate::test_util::commit_file;
+    use crate::test_util::init_repo;
+
+    #[test]
+    fn incremental_index_keeps_previous_snapshot() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let repo_path = dir.path();
+
+        init_repo(repo_path);
+
+        let first_commit = commit_file(repo_path, "file.txt", "abcd");
+
+        let mut indexer = GitIndexer::new(GitIndexerConfig {
+            show_index_progress: false,
+            main_branch_name: "main".to_owned(),
+            ignore_utf8_error: false,
+            ..Default::default()
+        });
+        indexer
+            .index_history(git2::Repository::open(repo_path).unwrap())
+            .unwrap();
+
+        let index = SearchIndex::new(indexer, repo_path);
+        assert!(index.do_incremental_index(&first_commit).unwrap().is_none());
+
+        let second_commit = commit_file(repo_path, "file2.txt", "efgh");
+        assert_eq!(index.commits_behind(&second_commit).unwrap(), 1);
+
+        let new_index = index
+            .do_incremental_index(&second_commit)
+            .unwrap()
+            .expect("new commit should produce a new snapshot");
+
+        assert_eq!(index.head_commit_id(), Some(first_commit));
+        assert_eq!(index.as_index_ref().file_id_to_path.len(), 1);
+
+        assert_eq!(new_index.head_commit_id(), Some(second_commit.clone()));
+        assert_eq!(new_index.head_commit_index(), Some(1));
+        assert_eq!(new_index.commits_behind(&second_commit).unwrap(), 0);
+        assert_eq!(new_index.as_index_ref().file_id_to_path.len(), 2);
+        assert!(new_index.as_index_ref().all_paths.contains("file2.txt"));
+        assert_eq!(new_index.as_index_ref().identifiers.get("efgh"), Some(1));
+    }
+}
@@ -1,18 +1,24 @@
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Instant;
 
 use crep_indexer::index::git_indexer::GitIndexer;
-use tokio::sync::RwLock;
+use tracing::info;
 
 use crate::indexer::index::SearchIndex;
 use crate::indexer::index::SearchIndexGuard;
+use crate::metrics::Metrics;
+use crate::metrics::ReindexOutcome;
 use crate::reindex_notify::reindex_signal::ReindexSignal;
 use crate::reindex_notify::reindex_signal::ReindexSignalReceiver;
 use crate::reindex_notify::reindex_signal::ReindexSignalSender;
 use crate::search::search_cache::SearchCache;
 
 pub struct Indexer {
-    index: Arc<RwLock<SearchIndex>>,
+    // The currently published snapshot. The lock is only held while cloning
+    // or swapping the Arc, never while indexing.
+    index: Arc<RwLock<Arc<SearchIndex>>>,
     send_reindex_signal: ReindexSignalSender,
 }
 
@@ -23,7 +29,9 @@ impl Indexer {
         send_reindex_signal: ReindexSignalSender,
     ) -> Self {
         Self {
-            index: Arc::new(RwLock::new(SearchIndex::new(indexer, repo_path))),
+            index: Arc::new(RwLock::new(Arc::new(SearchIndex::new(
+                indexer, repo_path,
+            )))),
             send_reindex_signal,
         }
     }
@@ -32,20 +40,22 @@ impl Indexer {
         self.send_reindex_signal.send(reindex_singal).unwrap();
     }
 
-    pub async fn get_search_index(&self) -> SearchIndexGuard {
-        SearchIndexGuard(Arc::new(self.index.clone().read_owned().await))
+    pub fn get_search_index(&self) -> SearchIndexGuard {
+        SearchIndexGuard(self.index.read().unwrap().clone())
     }
 
     pub fn spawn_re_indexer(
         &self,
         recv_indexer_signal: ReindexSignalReceiver,
         search_cache: Arc<SearchCache>,
+        metrics: Arc<Metrics>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(
             ReIndexer {
                 recv_indexer_signal,
                 index: self.index.clone(),
                 search_cache,
+                metrics,
             }
             .handle_re_index(),
         )
@@ -54,32 +64,56 @@ impl Indexer {
 
 struct ReIndexer {
     recv_indexer_signal: ReindexSignalReceiver,
-    index: Arc<RwLock<SearchIndex>>,
+    index: Arc<RwLock<Arc<SearchIndex>>>,
     search_cache: Arc<SearchCache>,
+    metrics: Arc<Metrics>,
 }
 
 impl ReIndexer {
     async fn handle_re_index(mut self) {
         while let Some(signal) = self.recv_indexer_signal.recv().await {
-            let index = self.index.clone();
+            let current = self.index.read().unwrap().clone();
+            let metrics = self.metrics.clone();
+            let reindex_start = Instant::now();
+
             let result = tokio::task::spawn_blocking(move || {
-                let mut index = index.blocking_write();
-                index.do_incremental_index(&signal.head_commit_id)
+                match current.commits_behind(&signal.head_commit_id) {
+                    Ok(commits_behind) => {
+                        metrics.set_commits_behind(commits_behind)
+                    }
+                    Err(e) => eprintln!("Unable to count new commits {:?}", e),
+                }
+
+                current.do_incremental_index(&signal.head_commit_id)
             })
             .await;
 
-            match result {
-                Ok(Ok(true)) => {
+            let outcome = match result {
+                Ok(Ok(Some(new_index))) => {
+                    info!(
+                        "Publishing the new index at {}",
+                        new_index.head_commit_id().unwrap_or_default()
+                    );
+
+                    *self.index.write().unwrap() = Arc::new(new_index);
                     self.search_cache.evict_cache_after_reindex();
+                    self.metrics.set_commits_behind(0);
+
+                    ReindexOutcome::Published
                 }
+                Ok(Ok(None)) => ReindexOutcome::Skipped,
                 Ok(Err(e)) => {
                     eprintln!("Failed reindex {:?}", e);
+                    ReindexOutcome::Failed
                 }
                 Err(e) => {
                     eprintln!("Reindex task failed {:?}", e);
+                    ReindexOutcome::Failed
                 }
-                _ => {}
-            }
+            };
+
+            self.metrics
+                .observe_reindex(outcome, reindex_start.elapsed());
         }
     }
 }
@@ -1,2 +1,3 @@
 pub mod index;
+#[allow(clippy::module_inception)]
 pub mod indexer;
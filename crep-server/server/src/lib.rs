@@ -10,15 +10,23 @@ use axum::routing::post;
 pub mod api;
 pub mod config;
 pub mod indexer;
+pub mod metrics;
 pub mod reindex_notify;
 mod search;
 pub mod server_context;
+#[cfg(test)]
+mod test_util;
 
 pub fn router(state: ServerContext, config: &ServerConfig) -> Router {
     let mut router = Router::new()
         .route("/api/health", get(api::health::health))
         .route("/api/search", post(api::search::search))
-        .route("/docs.json", get(api::docs_json));
+        .route("/api/search/stream", get(api::search_stream::search_stream))
+        .route("/api/file", get(api::file::file))
+        .route("/api/blame", get(api::blame::blame))
+        .route("/api/suggest", get(api::suggest::suggest))
+        .route("/docs.json", get(api::docs_json))
+        .route("/metrics", get(api::metrics::metrics));
 
     if let Some(LiveIndexConfig::OnWebhookNotify) = &config.live_index_config {
         router = router.route("/webhook/reindex", post(reindex::reindex));
@@ -60,6 +60,7 @@ async fn main() -> anyhow::Result<()> {
         show_index_progress: false,
         main_branch_name: "main".to_owned(),
         ignore_utf8_error: true,
+        file_filter: config.file_filter.clone(),
     };
 
     let (send_indexer_signal, recv_indexer_signal) =
@@ -91,7 +92,11 @@ async fn main() -> anyhow::Result<()> {
 
     let context = ServerContext::new(&config, indexer.clone())?;
 
-    indexer.spawn_re_indexer(recv_indexer_signal, context.search_cache.clone());
+    indexer.spawn_re_indexer(
+        recv_indexer_signal,
+        context.search_cache.clone(),
+        context.metrics.clone(),
+    );
 
     let app = router(context, &config);
     let addr: SocketAddr = std::env::var("BIND_ADDR")
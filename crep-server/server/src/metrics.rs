@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use crep_indexer::index::git_index::GitIndexRef;
+use prometheus::Encoder;
+use prometheus::Histogram;
+use prometheus::HistogramOpts;
+use prometheus::HistogramVec;
+use prometheus::IntCounter;
+use prometheus::IntCounterVec;
+use prometheus::IntGauge;
+use prometheus::Opts;
+use prometheus::Registry;
+use prometheus::TextEncoder;
+
+const SEARCH_LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+const CANDIDATE_DOCUMENT_BUCKETS: &[f64] =
+    &[0.0, 1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0];
+
+const REINDEX_LATENCY_BUCKETS: &[f64] =
+    &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0];
+
+const LOCK_WAIT_BUCKETS: &[f64] = &[0.0001, 0.001, 0.01, 0.1, 1.0, 10.0];
+
+#[derive(Debug, Clone, Copy)]
+pub enum SearchStage {
+    // Finding the candidate documents from the index.
+    Raw,
+    // Reading the files from the repo and building the SearchResults.
+    Conversion,
+    Total,
+}
+
+impl SearchStage {
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchStage::Raw => "raw",
+            SearchStage::Conversion => "conversion",
+            SearchStage::Total => "total",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReindexOutcome {
+    Published,
+    // The commit was already indexed.
+    Skipped,
+    Failed,
+}
+
+impl ReindexOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReindexOutcome::Published => "published",
+            ReindexOutcome::Skipped => "skipped",
+            ReindexOutcome::Failed => "failed",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+
+    search_requests: IntCounterVec,
+    search_duration: HistogramVec,
+    search_truncated: IntCounterVec,
+    search_candidate_documents: HistogramVec,
+
+    search_cache_lookups: IntCounterVec,
+    search_cache_evictions: IntCounterVec,
+
+    reindex_duration: Histogram,
+    reindex_total: IntCounterVec,
+    index_commits_behind: IntGauge,
+    index_last_published_timestamp: IntGauge,
+
+    index_commits: IntGauge,
+    index_files: IntGauge,
+    index_skipped_files: IntGauge,
+    index_trigrams: IntGauge,
+
+    repo_pool_lock_contended: IntCounter,
+    repo_pool_lock_wait: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("crep".to_owned()), None)
+            .expect("valid prefix");
+
+        let metrics = Self {
+            search_requests: IntCounterVec::new(
+                Opts::new("search_requests_total", "Number of searches"),
+                &["mode", "endpoint"],
+            )
+            .unwrap(),
+            search_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "search_duration_seconds",
+                    "Time spent on each stage of the search",
+                )
+                .buckets(SEARCH_LATENCY_BUCKETS.to_vec()),
+                &["mode", "stage"],
+            )
+            .unwrap(),
+            search_truncated: IntCounterVec::new(
+                Opts::new(
+                    "search_truncated_total",
+                    "Number of searches that hit the timeout",
+                ),
+                &["mode"],
+            )
+            .unwrap(),
+            search_candidate_documents: HistogramVec::new(
+                HistogramOpts::new(
+                    "search_candidate_documents",
+                    "Number of candidate documents found by the index",
+                )
+                .buckets(CANDIDATE_DOCUMENT_BUCKETS.to_vec()),
+                &["mode"],
+            )
+            .unwrap(),
+            search_cache_lookups: IntCounterVec::new(
+                Opts::new(
+                    "search_cache_lookups_total",
+                    "Number of search cache lookups",
+                ),
+                &["result"],
+            )
+            .unwrap(),
+            search_cache_evictions: IntCounterVec::new(
+                Opts::new(
+                    "search_cache_evictions_total",
+                    "Number of evicted search cache entries",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            reindex_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "reindex_duration_seconds",
+                    "Time spent on building the new index snapshot",
+                )
+                .buckets(REINDEX_LATENCY_BUCKETS.to_vec()),
+            )
+            .unwrap(),
+            reindex_total: IntCounterVec::new(
+                Opts::new("reindex_total", "Number of re-index requests"),
+                &["outcome"],
+            )
+            .unwrap(),
+            index_commits_behind: IntGauge::new(
+                "index_commits_behind",
+                "Number of commits on the watched branch that are not indexed",
+            )
+            .unwrap(),
+            index_last_published_timestamp: IntGauge::new(
+                "index_last_published_timestamp_seconds",
+                "Unix time when the last index snapshot was published",
+            )
+            .unwrap(),
+            index_commits: IntGauge::new(
+                "index_commits",
+                "Number of indexed commits",
+            )
+            .unwrap(),
+            index_files: IntGauge::new(
+                "index_files",
+                "Number of indexed files",
+            )
+            .unwrap(),
+            index_skipped_files: IntGauge::new(
+                "index_skipped_files",
+                "Number of files skipped by the file filter rules",
+            )
+            .unwrap(),
+            index_trigrams: IntGauge::new(
+                "index_trigrams",
+                "Number of distinct trigrams in the index",
+            )
+            .unwrap(),
+            repo_pool_lock_contended: IntCounter::new(
+                "repo_pool_lock_contended_total",
+                "Number of times a RepoPool repo was already locked",
+            )
+            .unwrap(),
+            repo_pool_lock_wait: Histogram::with_opts(
+                HistogramOpts::new(
+                    "repo_pool_lock_wait_seconds",
+                    "Time spent waiting for a contended RepoPool repo",
+                )
+                .buckets(LOCK_WAIT_BUCKETS.to_vec()),
+            )
+            .unwrap(),
+            registry,
+        };
+
+        metrics.register_all();
+        metrics
+    }
+
+    fn register_all(&self) {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.search_requests.clone()),
+            Box::new(self.search_duration.clone()),
+            Box::new(self.search_truncated.clone()),
+            Box::new(self.search_candidate_documents.clone()),
+            Box::new(self.search_cache_lookups.clone()),
+            Box::new(self.search_cache_evictions.clone()),
+            Box::new(self.reindex_duration.clone()),
+            Box::new(self.reindex_total.clone()),
+            Box::new(self.index_commits_behind.clone()),
+            Box::new(self.index_last_published_timestamp.clone()),
+            Box::new(self.index_commits.clone()),
+            Box::new(self.index_files.clone()),
+            Box::new(self.index_skipped_files.clone()),
+            Box::new(self.index_trigrams.clone()),
+            Box::new(self.repo_pool_lock_contended.clone()),
+            Box::new(self.repo_pool_lock_wait.clone()),
+        ];
+
+        for collector in collectors {
+            self.registry.register(collector).unwrap();
+        }
+
+        // Memory (resident / virtual) and cpu of the server process.
+        #[cfg(target_os = "linux")]
+        self.registry
+            .register(Box::new(
+                prometheus::process_collector::ProcessCollector::for_self(),
+            ))
+            .unwrap();
+    }
+
+    pub fn search_requested(&self, mode: &str, endpoint: &str) {
+        self.search_requests
+            .with_label_values(&[mode, endpoint])
+            .inc();
+    }
+
+    pub fn observe_search(
+        &self,
+        mode: &str,
+        stage: SearchStage,
+        duration: Duration,
+    ) {
+        self.search_duration
+            .with_label_values(&[mode, stage.as_str()])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_candidate_documents(&self, mode: &str, count: usize) {
+        self.search_candidate_documents
+            .with_label_values(&[mode])
+            .observe(count as f64);
+    }
+
+    pub fn search_truncated(&self, mode: &str) {
+        self.search_truncated.with_label_values(&[mode]).inc();
+    }
+
+    pub fn search_cache_lookup(&self, hit: bool) {
+        self.search_cache_lookups
+            .with_label_values(&[if hit { "hit" } else { "miss" }])
+            .inc();
+    }
+
+    pub fn search_cache_evicted(&self, reason: &str, count: usize) {
+        self.search_cache_evictions
+            .with_label_values(&[reason])
+            .inc_by(count as u64);
+    }
+
+    pub fn observe_reindex(&self, outcome: ReindexOutcome, duration: Duration) {
+        self.reindex_total
+            .with_label_values(&[outcome.as_str()])
+            .inc();
+        self.reindex_duration.observe(duration.as_secs_f64());
+
+        if let ReindexOutcome::Published = outcome {
+            self.index_last_published_timestamp
+                .set(chrono::Utc::now().timestamp());
+        }
+    }
+
+    pub fn set_commits_behind(&self, commits_behind: usize) {
+        self.index_commits_behind.set(commits_behind as i64);
+    }
+
+    pub fn set_index_size(&self, index: &GitIndexRef) {
+        self.index_commits
+            .set(index.commit_index_to_commit_id.len() as i64);
+        self.index_files.set(index.file_id_to_path.len() as i64);
+        self.index_skipped_files
+            .set(index.skipped_files.len() as i64);
+        self.index_trigrams
+            .set(index.word_to_file_id_ever_contained.len() as i64);
+    }
+
+    pub fn observe_repo_lock_wait(&self, duration: Duration) {
+        self.repo_pool_lock_contended.inc();
+        self.repo_pool_lock_wait.observe(duration.as_secs_f64());
+    }
+
+    // Renders every metric in the Prometheus text format.
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_recorded_metrics() {
+        let metrics = Metrics::new();
+
+        metrics.search_requested("regex", "search");
+        metrics.observe_search(
+            "regex",
+            SearchStage::Total,
+            Duration::from_millis(30),
+        );
+        metrics.search_cache_lookup(true);
+        metrics.search_cache_evicted("reindex", 3);
+        metrics.set_commits_behind(2);
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"crep_search_requests_total{endpoint="search",mode="regex"} 1"#
+        ));
+        assert!(text.contains(
+            r#"crep_search_duration_seconds_bucket{mode="regex",stage="total",le="0.05"} 1"#
+        ));
+        assert!(
+            text.contains(r#"crep_search_cache_lookups_total{result="hit"} 1"#)
+        );
+        assert!(text.contains(
+            r#"crep_search_cache_evictions_total{reason="reindex"} 3"#
+        ));
+        assert!(text.contains("crep_index_commits_behind 2"));
+    }
+}
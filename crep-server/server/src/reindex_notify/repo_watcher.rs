@@ -137,6 +137,8 @@ mod tests {
                     },
                 )),
                 saved_index_path: "".to_owned(),
+                search_timeout_milliseconds: None,
+                file_filter: Default::default(),
             },
             send_indexer_signal,
         );
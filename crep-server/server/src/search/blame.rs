@@ -0,0 +1,91 @@
+use ahash::AHashMap;
+use chrono::DateTime;
+use crep_indexer::index::git_index::GitIndexRef;
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::index::git_indexer::FileId;
+use git2::Oid;
+use git2::Repository;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct BlameRange {
+    // [start_line, end_line) of the file at the indexed HEAD (0-based).
+    pub start_line: usize,
+    pub end_line: usize,
+
+    pub commit_sha: String,
+    pub commit_date: String,
+    pub author: String,
+    pub commit_summary: String,
+}
+
+// Answers the blame from the FileDiffTracker instead of walking the history.
+// Returns None if the file does not exist at the indexed HEAD.
+pub fn blame_file(
+    repo: &Repository,
+    index: &GitIndexRef,
+    file_id: FileId,
+) -> anyhow::Result<Option<Vec<BlameRange>>> {
+    let tracker = match index.file_id_to_diff_tracker.get(&file_id) {
+        Some(tracker) if !tracker.commit_indexes.is_empty() => tracker,
+        _ => return Ok(None),
+    };
+
+    let mut commits: AHashMap<CommitIndex, CommitSummary> = AHashMap::new();
+    let mut ranges = vec![];
+
+    for range in tracker.blame() {
+        let commit = match commits.get(&range.commit_id) {
+            Some(commit) => commit,
+            None => {
+                let commit = CommitSummary::new(
+                    repo,
+                    index.commit_index_to_commit_id,
+                    range.commit_id,
+                )?;
+                commits.entry(range.commit_id).or_insert(commit)
+            }
+        };
+
+        ranges.push(BlameRange {
+            start_line: range.line_start,
+            end_line: range.line_end,
+            commit_sha: commit.sha.clone(),
+            commit_date: commit.date.clone(),
+            author: commit.author.clone(),
+            commit_summary: commit.summary.clone(),
+        });
+    }
+
+    Ok(Some(ranges))
+}
+
+struct CommitSummary {
+    sha: String,
+    date: String,
+    author: String,
+    summary: String,
+}
+
+impl CommitSummary {
+    fn new(
+        repo: &Repository,
+        commit_index_to_commit_id: &[[u8; 20]],
+        commit_index: CommitIndex,
+    ) -> anyhow::Result<Self> {
+        let commit_id =
+            Oid::from_bytes(&commit_index_to_commit_id[commit_index])?;
+        let commit = repo.find_commit(commit_id)?;
+
+        Ok(Self {
+            sha: commit_id.to_string(),
+            date: DateTime::from_timestamp_secs(commit.time().seconds())
+                .ok_or_else(|| anyhow::anyhow!("invalid commit timestamp"))?
+                .to_rfc3339(),
+            author: commit.author().name().unwrap_or_default().to_owned(),
+            summary: commit.summary().unwrap_or_default().to_owned(),
+        })
+    }
+}
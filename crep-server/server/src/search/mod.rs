@@ -1,3 +1,5 @@
+pub mod blame;
 pub mod repo_pool;
+#[allow(clippy::module_inception)]
 pub mod search;
 pub mod search_cache;
@@ -1,22 +1,115 @@
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::TryLockError;
+use std::time::Instant;
 
+use crep_indexer::index::encoding::decode_to_string;
+use crep_indexer::index::git_index::GitIndexRef;
+use crep_indexer::index::git_indexer::CommitIndex;
+use crep_indexer::index::git_indexer::FileId;
+use crep_indexer::search::result::search_result::RepoReader;
+use git2::Oid;
 use git2::Repository;
 
+use crate::metrics::Metrics;
+
 #[derive(Clone)]
 pub struct RepoPool {
-    pub repos: Vec<Arc<Mutex<Repository>>>,
+    pub repos: Vec<Arc<PooledRepo>>,
 }
 
 impl RepoPool {
-    pub fn new(path: &str) -> Self {
+    pub fn new(path: &str, metrics: Arc<Metrics>) -> Self {
         let num_threads = rayon::current_num_threads();
         assert!(num_threads > 0);
 
         RepoPool {
             repos: (0..num_threads)
-                .map(|_| Arc::new(Mutex::new(Repository::open(path).unwrap())))
+                .map(|_| {
+                    Arc::new(PooledRepo {
+                        repo: Mutex::new(Repository::open(path).unwrap()),
+                        metrics: metrics.clone(),
+                    })
+                })
                 .collect::<Vec<_>>(),
         }
     }
+
+    // Returns the reader that uses the repo dedicated to the current rayon
+    // thread. Falls back to the first repo outside of the rayon pool.
+    pub fn reader_for_current_thread<'i>(
+        &self,
+        index: &GitIndexRef<'i>,
+    ) -> ThreadSafeRepoReader<'i> {
+        ThreadSafeRepoReader {
+            repo: self
+                .repos
+                .get(rayon::current_thread_index().unwrap_or_default())
+                .unwrap()
+                .clone(),
+            file_id_to_path: index.file_id_to_path,
+            commit_index_to_commit_id: index.commit_index_to_commit_id,
+        }
+    }
+}
+
+pub struct PooledRepo {
+    repo: Mutex<Repository>,
+    metrics: Arc<Metrics>,
+}
+
+impl PooledRepo {
+    // Records how long the caller waited when the repo was held by another
+    // thread.
+    pub fn lock(&self) -> MutexGuard<'_, Repository> {
+        match self.repo.try_lock() {
+            Ok(repo) => repo,
+            Err(TryLockError::WouldBlock) => {
+                let wait_start = Instant::now();
+                let repo = self.repo.lock().unwrap();
+                self.metrics.observe_repo_lock_wait(wait_start.elapsed());
+
+                repo
+            }
+            Err(TryLockError::Poisoned(e)) => {
+                panic!("Repo lock is poisoned: {e}")
+            }
+        }
+    }
+}
+
+pub struct ThreadSafeRepoReader<'i> {
+    pub repo: Arc<PooledRepo>,
+    pub file_id_to_path: &'i [String],
+    pub commit_index_to_commit_id: &'i [[u8; 20]],
+}
+
+impl<'i> RepoReader for ThreadSafeRepoReader<'i> {
+    fn read_file_at_commit(
+        &self,
+        commit_id: CommitIndex,
+        file_id: FileId,
+    ) -> anyhow::Result<Option<(/*file path*/ String, /*content*/ String)>>
+    {
+        let file_path = self.file_id_to_path.get(file_id).unwrap();
+        let commit =
+            Oid::from_bytes(&self.commit_index_to_commit_id[commit_id])?;
+
+        let repo = self.repo.lock();
+        let commit = repo.find_commit(commit)?;
+        let tree = commit.tree()?;
+
+        let entry = tree.get_path(Path::new(&file_path))?;
+        let object = entry.to_object(&repo)?;
+        if let Some(blob) = object.as_blob() {
+            Ok(Some((
+                file_path.to_owned(),
+                decode_to_string(blob.content()),
+            )))
+        } else {
+            Ok(None)
+        }
+    }
 }
@@ -1,6 +1,12 @@
+use std::ops::Range;
+
 use chrono::DateTime;
+use crep_indexer::git::diff::FileDiffTracker;
+use crep_indexer::index::git_index::GitIndexRef;
 use crep_indexer::search::result::search_result::SearchResult;
 use crep_indexer::search::result::single_commit_search_result::SingleCommitSearchResult;
+use crep_indexer::search::summary::FileWithMatches;
+use crep_indexer::search::summary::MatchCount;
 use git2::Oid;
 use git2::Repository;
 use serde::Deserialize;
@@ -17,28 +23,73 @@ pub struct SearchHit {
 impl SearchHit {
     pub fn from_search_result(
         repo: &Repository,
-        commit_index_to_commit_id: &[[u8; 20]],
+        index: &GitIndexRef,
         s: SearchResult,
     ) -> anyhow::Result<Self> {
+        // Only knows the origin of the lines at HEAD.
+        let diff_tracker = index
+            .file_name_to_id
+            .get(&s.file_path)
+            .and_then(|file_id| index.file_id_to_diff_tracker.get(file_id));
+
         Ok(Self {
-            file_path: s.file_path,
             first_match: MatchDetail::from_single_commit_result(
                 repo,
-                commit_index_to_commit_id,
+                index.commit_index_to_commit_id,
+                diff_tracker,
                 s.first_match,
             )?,
             last_match: match s.last_match {
                 Some(last) => Some(MatchDetail::from_single_commit_result(
                     repo,
-                    commit_index_to_commit_id,
+                    index.commit_index_to_commit_id,
+                    diff_tracker,
                     last,
                 )?),
                 _ => None,
             },
+            file_path: s.file_path,
         })
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct FileMatch {
+    pub file_path: String,
+    // Number of the commits that changed the file while it contained the
+    // query.
+    pub num_commits: u64,
+    // The file still contains the query at HEAD.
+    pub at_head: bool,
+}
+
+impl From<&FileWithMatches> for FileMatch {
+    fn from(file: &FileWithMatches) -> Self {
+        Self {
+            file_path: file.file_path.clone(),
+            num_commits: file.commits.len(),
+            at_head: file.at_head,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct SearchCount {
+    pub num_files: usize,
+    pub num_files_at_head: usize,
+    pub num_commits: u64,
+}
+
+impl From<MatchCount> for SearchCount {
+    fn from(count: MatchCount) -> Self {
+        Self {
+            num_files: count.num_files,
+            num_files_at_head: count.num_files_at_head,
+            num_commits: count.num_commits,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct MatchDetail {
     pub commit_sha: String,
@@ -46,16 +97,55 @@ pub struct MatchDetail {
     pub commit_summary: String,
     pub is_head: bool,
     pub lines: Vec<LineMatch>,
+
+    // Number of the matches in the file at the commit, including the ones
+    // that are not in `lines`.
+    pub match_count: usize,
+    // Ranges of the consecutive `lines`, so that the nearby matches are
+    // shown together.
+    pub context_windows: Vec<ContextWindow>,
+}
+
+// Lines [start_line, end_line).
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct ContextWindow {
+    pub start_line: usize,
+    pub end_line: usize,
 }
 
 impl MatchDetail {
     fn from_single_commit_result(
         repo: &Repository,
         commit_index_to_commit_id: &[[u8; 20]],
+        diff_tracker: Option<&FileDiffTracker>,
         result: SingleCommitSearchResult,
     ) -> anyhow::Result<Self> {
         let commit_id =
             Oid::from_bytes(&commit_index_to_commit_id[result.commit_id])?;
+        let is_head = result.commit_id == commit_index_to_commit_id.len() - 1;
+
+        let mut lines = LineMatch::new(&result);
+        let context_windows = result
+            .context_windows()
+            .into_iter()
+            .map(|window| ContextWindow {
+                start_line: window.start,
+                end_line: window.end,
+            })
+            .collect();
+        if is_head && let Some(diff_tracker) = diff_tracker {
+            for line in lines.iter_mut().filter(|l| !l.highlights.is_empty()) {
+                line.introduced_in = diff_tracker
+                    .blame_line(line.line_number)
+                    .map(|(commit_index, _)| {
+                        Oid::from_bytes(
+                            &commit_index_to_commit_id[commit_index],
+                        )
+                        .map(|oid| oid.to_string())
+                    })
+                    .transpose()?;
+            }
+        }
 
         let commit = repo.find_commit(commit_id)?;
         Ok(Self {
@@ -64,8 +154,10 @@ impl MatchDetail {
                 .ok_or_else(|| anyhow::anyhow!("invalid commit timestamp"))?
                 .to_rfc3339(),
             commit_summary: commit.summary().unwrap_or_default().to_owned(),
-            is_head: result.commit_id == commit_index_to_commit_id.len() - 1,
-            lines: LineMatch::new(&result),
+            is_head,
+            lines,
+            match_count: result.match_count,
+            context_windows,
         })
     }
 }
@@ -75,6 +167,9 @@ pub struct LineMatch {
     pub line_number: usize,
     pub content: String,
     pub highlights: Vec<LineHighlight>,
+
+    // The commit that added the line. Only set for the matched lines at HEAD.
+    pub introduced_in: Option<String>,
 }
 
 impl LineMatch {
@@ -82,12 +177,32 @@ impl LineMatch {
         s.lines
             .iter()
             .map(|(k, v)| {
+                let captures = s.captures_per_line.get(k);
+                let end_lines = s.match_end_lines.get(k);
                 let highlights = match s.words_per_line.get(k) {
                     Some(words_in_line) => words_in_line
                         .iter()
                         .map(|(word, col)| LineHighlight {
-                            term: word.to_owned(),
-                            column: *col,
+                            groups: captures
+                                .and_then(|c| c.get(col))
+                                .map(|groups| {
+                                    groups
+                                        .iter()
+                                        .map(|group| {
+                                            CaptureSpan::new(
+                                                v,
+                                                group.index,
+                                                group.name.clone(),
+                                                group.start..group.end,
+                                            )
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                            end_line: end_lines
+                                .and_then(|e| e.get(col))
+                                .copied(),
+                            ..LineHighlight::new(v, *col..col + word.len())
                         })
                         .collect::<Vec<_>>(),
                     None => vec![],
@@ -97,14 +212,87 @@ impl LineMatch {
                     line_number: *k,
                     content: v.to_owned(),
                     highlights,
+                    introduced_in: None,
                 }
             })
             .collect::<Vec<_>>()
     }
 }
 
+// Columns are the byte offsets within the line, and the `utf16_` ones are
+// in the UTF-16 code units (e.g. for JavaScript strings and LSP positions).
+// The ends are exclusive.
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct LineHighlight {
     pub term: String,
     pub column: usize,
+    pub end_column: usize,
+    pub length: usize,
+    pub utf16_column: usize,
+    pub utf16_end_column: usize,
+    pub utf16_length: usize,
+
+    // Capture groups of the regex that participated in the match.
+    #[serde(default)]
+    pub groups: Vec<CaptureSpan>,
+
+    // The last line of the multi-line match that starts here. The following
+    // lines carry their part of the match as the separate highlights.
+    pub end_line: Option<usize>,
+}
+
+impl LineHighlight {
+    // Highlight of the byte range of the line.
+    pub fn new(line: &str, range: Range<usize>) -> Self {
+        let utf16_column = utf16_offset(line, range.start);
+        let utf16_end_column = utf16_offset(line, range.end);
+
+        Self {
+            term: line[range.clone()].to_owned(),
+            column: range.start,
+            end_column: range.end,
+            length: range.len(),
+            utf16_column,
+            utf16_end_column,
+            utf16_length: utf16_end_column - utf16_column,
+            groups: vec![],
+            end_line: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct CaptureSpan {
+    // 1-based index of the group in the regex.
+    pub index: usize,
+    pub name: Option<String>,
+    // Byte range within the line.
+    pub start: usize,
+    pub end: usize,
+    // Range in the UTF-16 code units.
+    pub utf16_start: usize,
+    pub utf16_end: usize,
+}
+
+impl CaptureSpan {
+    pub fn new(
+        line: &str,
+        index: usize,
+        name: Option<String>,
+        range: Range<usize>,
+    ) -> Self {
+        Self {
+            index,
+            name,
+            start: range.start,
+            end: range.end,
+            utf16_start: utf16_offset(line, range.start),
+            utf16_end: utf16_offset(line, range.end),
+        }
+    }
+}
+
+// Converts the byte offset in the line to the one in the UTF-16 code units.
+fn utf16_offset(line: &str, byte_offset: usize) -> usize {
+    line[..byte_offset].encode_utf16().count()
 }
@@ -1,32 +1,55 @@
 use std::num::NonZeroUsize;
+use std::sync::Arc;
 use std::sync::Mutex;
 
 use ahash::AHashMap;
+use crep_indexer::index::git_indexer::CommitIndex;
 use crep_indexer::search::git_searcher::Query;
 use crep_indexer::search::git_searcher::RawPerFileSearchResult;
 use crep_indexer::search::result::search_result::SearchResult;
 use tracing::info;
 
+use crate::metrics::Metrics;
+
+// The query and the search options that change its results.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct SearchKey {
+    pub query: Query,
+    pub whole_word: bool,
+    pub multiline: bool,
+    pub all_matches: bool,
+    pub before_context: Option<usize>,
+    pub after_context: Option<usize>,
+}
+
 pub struct SearchCache {
-    results: Mutex<lru::LruCache<Query, CachedSearchResults>>,
+    results: Mutex<lru::LruCache<SearchKey, CachedSearchResults>>,
+    metrics: Arc<Metrics>,
 }
 
 impl SearchCache {
-    pub fn new(cache_size: NonZeroUsize) -> Self {
+    pub fn new(cache_size: NonZeroUsize, metrics: Arc<Metrics>) -> Self {
         Self {
             results: Mutex::new(lru::LruCache::new(cache_size)),
+            metrics,
         }
     }
 
+    // Results are only returned when they were computed against the index
+    // snapshot at `head_commit_index`. A search that is still running on the
+    // previous snapshot must not see (or pollute) the newer cache entries.
     pub fn find<I>(
         &self,
-        q: &Query,
+        q: &SearchKey,
+        head_commit_index: CommitIndex,
         result_index: I,
     ) -> Option<Vec<CacheResult>>
     where
         I: IntoIterator<Item = usize>,
     {
-        if let Some(c) = self.results.lock().unwrap().get(q) {
+        if let Some(c) = self.results.lock().unwrap().get(q)
+            && c.head_commit_index == head_commit_index
+        {
             info!("Match found for {:?}; raw size: {}", q, c.raw_result.len());
 
             let mut cache_result = vec![];
@@ -48,15 +71,34 @@ impl SearchCache {
                 }
             }
 
+            self.metrics.search_cache_lookup(true);
             Some(cache_result)
         } else {
+            self.metrics.search_cache_lookup(false);
             None
         }
     }
 
+    pub fn find_raw(
+        &self,
+        q: &SearchKey,
+        head_commit_index: CommitIndex,
+    ) -> Option<Vec<RawPerFileSearchResult>> {
+        let raw_result = match self.results.lock().unwrap().get(q) {
+            Some(c) if c.head_commit_index == head_commit_index => {
+                Some(c.raw_result.clone())
+            }
+            _ => None,
+        };
+
+        self.metrics.search_cache_lookup(raw_result.is_some());
+        raw_result
+    }
+
     pub fn put_raw_result(
         &self,
-        q: &Query,
+        q: &SearchKey,
+        head_commit_index: CommitIndex,
         raw_result: Vec<RawPerFileSearchResult>,
     ) {
         info!(
@@ -67,17 +109,31 @@ impl SearchCache {
 
         let mut results = self.results.lock().unwrap();
 
+        if !results.contains(q) && results.len() == results.cap().get() {
+            // Inserting the new entry evicts the least recently used one.
+            self.metrics.search_cache_evicted("capacity", 1);
+        }
+
         let entry = results
             .try_get_or_insert_mut(q.clone(), || -> Result<_, ()> {
                 Ok(CachedSearchResults::default())
             })
             .unwrap();
 
+        if entry.head_commit_index != head_commit_index {
+            entry.raw_index_to_search_result.clear();
+        }
+
+        entry.head_commit_index = head_commit_index;
         entry.raw_result = raw_result;
     }
 
-    pub fn put_search_results<I>(&self, q: &Query, results: I)
-    where
+    pub fn put_search_results<I>(
+        &self,
+        q: &SearchKey,
+        head_commit_index: CommitIndex,
+        results: I,
+    ) where
         I: IntoIterator<Item = (usize, Option<SearchResult>)>,
     {
         let mut iter = results.into_iter().peekable();
@@ -88,11 +144,14 @@ impl SearchCache {
         }
 
         let mut cache = self.results.lock().unwrap();
-        let entry = cache
-            .try_get_or_insert_mut(q.clone(), || -> Result<_, ()> {
-                Ok(CachedSearchResults::default())
-            })
-            .unwrap();
+        let entry = match cache.get_mut(q) {
+            Some(entry) if entry.head_commit_index == head_commit_index => {
+                entry
+            }
+            // The raw results are stale (or evicted); the converted results
+            // are meaningless without them.
+            _ => return,
+        };
 
         for (index, result) in iter {
             entry.raw_index_to_search_result.insert(index, result);
@@ -100,12 +159,16 @@ impl SearchCache {
     }
 
     pub fn evict_cache_after_reindex(&self) {
-        self.results.lock().unwrap().clear()
+        let mut results = self.results.lock().unwrap();
+
+        self.metrics.search_cache_evicted("reindex", results.len());
+        results.clear()
     }
 }
 
 #[derive(Default)]
 struct CachedSearchResults {
+    head_commit_index: CommitIndex,
     raw_result: Vec<RawPerFileSearchResult>,
     raw_index_to_search_result: AHashMap<usize, Option<SearchResult>>,
 }
@@ -1,8 +1,11 @@
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use crate::config::ServerConfig;
 use crate::indexer::indexer::Indexer;
+use crate::metrics::Metrics;
 use crate::search::repo_pool::RepoPool;
 use crate::search::search_cache::SearchCache;
 
@@ -11,6 +14,8 @@ pub struct ServerContext {
     pub indexer: Arc<Indexer>,
     pub repo_pool: Arc<RepoPool>,
     pub search_cache: Arc<SearchCache>,
+    pub search_timeout: Option<Duration>,
+    pub metrics: Arc<Metrics>,
 }
 
 impl ServerContext {
@@ -18,12 +23,27 @@ impl ServerContext {
         config: &ServerConfig,
         indexer: Arc<Indexer>,
     ) -> anyhow::Result<Self> {
+        let metrics = Arc::new(Metrics::new());
+
         Ok(Self {
             indexer,
-            repo_pool: Arc::new(RepoPool::new(&config.repo_path)),
+            repo_pool: Arc::new(RepoPool::new(
+                &config.repo_path,
+                metrics.clone(),
+            )),
             search_cache: Arc::new(SearchCache::new(
                 NonZeroUsize::new(1024).unwrap(),
+                metrics.clone(),
             )),
+            search_timeout: config
+                .search_timeout_milliseconds
+                .map(Duration::from_millis),
+            metrics,
         })
     }
+
+    // Deadline of the search request that starts now.
+    pub fn search_deadline(&self) -> Option<Instant> {
+        self.search_timeout.map(|timeout| Instant::now() + timeout)
+    }
 }
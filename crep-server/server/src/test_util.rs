@@ -0,0 +1,37 @@
+use std::path::Path;
+
+pub fn run(cwd: &Path, args: &[&str]) {
+    std::process::Command::new(args[0])
+        .args(&args[1..])
+        .current_dir(cwd)
+        .output()
+        .expect("spawn ok");
+}
+
+pub fn init_repo(repo_path: &Path) {
+    run(repo_path, &["git", "init"]);
+    run(
+        repo_path,
+        &["git", "config", "--local", "user.email", "jaebum@test.com"],
+    );
+    run(
+        repo_path,
+        &["git", "config", "--local", "user.name", "Jaebum"],
+    );
+}
+
+// Returns the id of the new commit.
+pub fn commit_file(repo_path: &Path, name: &str, content: &str) -> String {
+    std::fs::write(repo_path.join(name), content).unwrap();
+    run(repo_path, &["git", "add", "."]);
+    run(repo_path, &["git", "commit", "-m", name]);
+
+    git2::Repository::open(repo_path)
+        .unwrap()
+        .head()
+        .unwrap()
+        .peel_to_commit()
+        .unwrap()
+        .id()
+        .to_string()
+}
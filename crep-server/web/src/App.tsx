@@ -1,11 +1,13 @@
 import type { FormEvent, ReactNode } from "react";
-import { useState } from "react";
-import { executeSearch } from "./api/client";
+import { useEffect, useRef, useState } from "react";
+import { fetchFile, fetchSuggestions, streamSearch } from "./api/client";
 import type {
+  FileResponse,
   LineMatch,
   MatchDetail,
-  SearchHit,
   SearchMode,
+  SearchStreamHit,
+  SearchStreamProgress,
 } from "./api/types";
 import "./App.css";
 
@@ -23,15 +25,20 @@ const highlightLine = (line: LineMatch) => {
     return line.content;
   }
 
-  const sorted = [...line.highlights].sort((a, b) => a.column - b.column);
+  // The content is indexed by the UTF-16 code units in JavaScript.
+  const sorted = [...line.highlights].sort(
+    (a, b) => a.utf16_column - b.utf16_column,
+  );
 
   const segments: ReactNode[] = [];
   let cursor = 0;
 
   sorted.forEach((highlight, index) => {
-    const start = Math.max(0, highlight.column);
-    const termLength = highlight.term.length;
-    const end = Math.min(start + termLength, line.content.length);
+    const start = Math.max(0, highlight.utf16_column);
+    const end = Math.min(
+      Math.max(start, highlight.utf16_end_column),
+      line.content.length,
+    );
 
     if (start > cursor) {
       segments.push(
@@ -61,9 +68,24 @@ const highlightLine = (line: LineMatch) => {
   return segments;
 };
 
+// Whether the line starts a context window that is not adjacent to the
+// previous one.
+const startsNewWindow = (
+  detail: MatchDetail,
+  line: LineMatch,
+  index: number,
+) =>
+  index > 0 &&
+  detail.context_windows.some(
+    (window) =>
+      window.start_line === line.line_number &&
+      detail.lines[index - 1].line_number < line.line_number - 1,
+  );
+
 const renderMatchSection = (
   label: string,
   detail: MatchDetail | null | undefined,
+  onOpenFile: (commit: string) => void,
 ) => {
   if (!detail) {
     return null;
@@ -83,22 +105,40 @@ const renderMatchSection = (
             Commit: <code>{detail.commit_sha.substring(0, 7)}</code>
           </span>
           <span>When: {formattedDate}</span>
+          <span>
+            {detail.match_count} {detail.match_count === 1 ? "match" : "matches"}
+          </span>
         </div>
         {detail.commit_summary && (
           <p className="mt-2 text-[#3c4043]">{detail.commit_summary}</p>
         )}
+        <button
+          type="button"
+          onClick={() => onOpenFile(detail.commit_sha)}
+          className="mt-2 text-sm text-[#1a73e8] hover:underline"
+        >
+          View file at this commit
+        </button>
       </header>
 
       <div className="overflow-x-auto rounded-lg bg-[#f1f3f4] p-3 font-mono text-[0.95rem]">
-        {detail.lines.map((line) => (
+        {detail.lines.map((line, index) => (
           <div
             key={`${detail.commit_sha}-${line.line_number}`}
-            className="flex items-start gap-4"
+            className={`flex items-start gap-4 ${startsNewWindow(detail, line, index) ? "mt-2 border-t border-black/10 pt-2" : ""}`}
           >
             <span className="min-w-[3rem] text-right text-[#9aa0a6]">
               {line.line_number}
             </span>
             <code className="whitespace-pre-wrap">{highlightLine(line)}</code>
+            {line.introduced_in && (
+              <span
+                className="ml-auto text-xs text-[#9aa0a6]"
+                title={line.introduced_in}
+              >
+                introduced in {line.introduced_in.slice(0, 8)}
+              </span>
+            )}
           </div>
         ))}
       </div>
@@ -106,28 +146,240 @@ const renderMatchSection = (
   );
 };
 
+type FileTarget = {
+  path: string;
+  commit: string;
+};
+
+type FileViewerProps = {
+  target: FileTarget;
+  query: string;
+  mode: SearchMode;
+  wholeWord: boolean;
+  multiline: boolean;
+  onNavigate: (target: FileTarget) => void;
+  onClose: () => void;
+};
+
+const FileViewer = ({
+  target,
+  query,
+  mode,
+  wholeWord,
+  multiline,
+  onNavigate,
+  onClose,
+}: FileViewerProps) => {
+  const [file, setFile] = useState<FileResponse | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    let cancelled = false;
+    setFile(null);
+    setError(null);
+
+    fetchFile(target.path, {
+      commit: target.commit,
+      query,
+      mode,
+      wholeWord,
+      multiline,
+    })
+      .then((response) => {
+        if (!cancelled) {
+          setFile(response);
+        }
+      })
+      .catch((err) => {
+        if (!cancelled) {
+          setError(
+            err instanceof Error ? err.message : "Unable to load the file.",
+          );
+        }
+      });
+
+    return () => {
+      cancelled = true;
+    };
+  }, [target.path, target.commit, query, mode, wholeWord, multiline]);
+
+  const navigateTo = (commit: string | null | undefined) => {
+    if (commit) {
+      onNavigate({ path: target.path, commit });
+    }
+  };
+
+  return (
+    <article className="mb-10 rounded-xl border border-black/10 bg-white px-5 py-4 shadow-sm">
+      <header className="mb-3 flex flex-wrap items-center gap-3">
+        <h2 className="flex-1 text-[1.1rem] text-[#1a0dab] break-all">
+          {target.path}
+        </h2>
+        <code className="text-sm text-[#5f6368]">
+          {file?.is_head ? "HEAD" : target.commit.substring(0, 7)}
+        </code>
+        <button
+          type="button"
+          disabled={!file?.previous_commit}
+          onClick={() => navigateTo(file?.previous_commit)}
+          className="text-sm text-[#1a73e8] hover:underline disabled:text-[#9aa0a6] disabled:no-underline"
+        >
+          ← Previous change
+        </button>
+        <button
+          type="button"
+          disabled={!file?.next_commit}
+          onClick={() => navigateTo(file?.next_commit)}
+          className="text-sm text-[#1a73e8] hover:underline disabled:text-[#9aa0a6] disabled:no-underline"
+        >
+          Next change →
+        </button>
+        <button
+          type="button"
+          onClick={onClose}
+          className="text-sm text-[#5f6368] hover:underline"
+        >
+          Close
+        </button>
+      </header>
+
+      {error && (
+        <div role="alert" className="text-[0.95rem] text-[#d93025]">
+          {error}
+        </div>
+      )}
+
+      {!file && !error && <p className="text-[#5f6368]">Loading file…</p>}
+
+      {file && (
+        <div className="max-h-[70vh] overflow-auto rounded-lg bg-[#f1f3f4] p-3 font-mono text-[0.95rem]">
+          {file.lines.map((line) => (
+            <div key={line.line_number} className="flex items-start gap-4">
+              <span className="min-w-[3rem] text-right text-[#9aa0a6]">
+                {line.line_number}
+              </span>
+              <code className="whitespace-pre-wrap">
+                {highlightLine(line)}
+              </code>
+            </div>
+          ))}
+        </div>
+      )}
+    </article>
+  );
+};
+
+// The last (partially typed) word of the query.
+const currentWord = (query: string) => query.match(/[\w./-]*$/)?.[0] ?? "";
+
 function App() {
   const [query, setQuery] = useState("");
   const [mode, setMode] = useState<SearchMode>("plain");
-  const [results, setResults] = useState<(SearchHit | null)[]>([]);
+  const [wholeWord, setWholeWord] = useState(false);
+  const [multiline, setMultiline] = useState(false);
+  const [allMatches, setAllMatches] = useState(false);
+  const [contextLines, setContextLines] = useState(2);
+  const [results, setResults] = useState<SearchStreamHit[]>([]);
+  const [progress, setProgress] = useState<SearchStreamProgress | null>(null);
   const [loading, setLoading] = useState(false);
+  const [truncated, setTruncated] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [hasSubmitted, setHasSubmitted] = useState(false);
+  // The query and the mode of the results on screen (the input may have
+  // changed since).
+  const [submitted, setSubmitted] = useState<{
+    query: string;
+    mode: SearchMode;
+    wholeWord: boolean;
+    multiline: boolean;
+  }>({ query: "", mode: "plain", wholeWord: false, multiline: false });
+  const [fileTarget, setFileTarget] = useState<FileTarget | null>(null);
+  const [suggestions, setSuggestions] = useState<string[]>([]);
+  const cancelSearch = useRef<(() => void) | null>(null);
+
+  useEffect(() => () => cancelSearch.current?.(), []);
+
+  useEffect(() => {
+    const word = currentWord(query);
+    if (word.length < 2) {
+      setSuggestions([]);
+      return;
+    }
+
+    const controller = new AbortController();
+    // Debounce while the user is typing.
+    const timer = window.setTimeout(() => {
+      fetchSuggestions(word, controller.signal)
+        .then(({ identifiers, paths }) => {
+          const head = query.slice(0, query.length - word.length);
+          setSuggestions(
+            [...identifiers.map((s) => s.identifier), ...paths].map(
+              (completion) => head + completion,
+            ),
+          );
+        })
+        .catch(() => setSuggestions([]));
+    }, 150);
 
-  const handleSubmit = async (event: FormEvent<HTMLFormElement>) => {
+    return () => {
+      window.clearTimeout(timer);
+      controller.abort();
+    };
+  }, [query]);
+
+  const handleSubmit = (event: FormEvent<HTMLFormElement>) => {
     event.preventDefault();
+    cancelSearch.current?.();
+    cancelSearch.current = null;
+
     setHasSubmitted(true);
     setLoading(true);
     setError(null);
+    setResults([]);
+    setProgress(null);
+    setTruncated(false);
+    // Only the regex matches can span multiple lines.
+    const multilineRegex = mode === "regex" && multiline;
+    setSubmitted({ query, mode, wholeWord, multiline: multilineRegex });
+    setFileTarget(null);
+
+    const finish = () => {
+      cancelSearch.current = null;
+      setLoading(false);
+    };
 
     try {
-      const response = await executeSearch(query, { mode });
-      setResults(response.results);
+      cancelSearch.current = streamSearch(
+        query,
+        {
+          mode,
+          wholeWord,
+          multiline: multilineRegex,
+          allMatches,
+          beforeContext: contextLines,
+          afterContext: contextLines,
+        },
+        {
+          // Hits arrive out of order; keep them sorted like the non-streaming
+          // response.
+          onHit: (hit) =>
+            setResults((prev) =>
+              [...prev, hit].sort((a, b) => a.index - b.index),
+            ),
+          onProgress: setProgress,
+          onDone: (done) => {
+            setTruncated(done.truncated);
+            finish();
+          },
+          onError: (message) => {
+            setError(message);
+            finish();
+          },
+        },
+      );
     } catch (err) {
-      setResults([]);
       setError(err instanceof Error ? err.message : "Unknown search error.");
-    } finally {
-      setLoading(false);
+      finish();
     }
   };
 
@@ -149,9 +401,16 @@ function App() {
               value={query}
               onChange={(event) => setQuery(event.target.value)}
               placeholder="Search git history…"
+              list="query-suggestions"
+              autoComplete="off"
               className="flex-1 border-0 bg-transparent text-[1.05rem] focus:outline-none"
               aria-label="Search query"
             />
+            <datalist id="query-suggestions">
+              {suggestions.map((suggestion) => (
+                <option key={suggestion} value={suggestion} />
+              ))}
+            </datalist>
             <button
               type="submit"
               className="rounded-full border-0 bg-[#1a73e8] px-5 py-2 text-[0.95rem] font-semibold text-white transition-colors hover:bg-[#1558b0]"
@@ -181,30 +440,101 @@ function App() {
               />
               Regex
             </label>
+            <label className="flex items-center gap-1">
+              <input
+                type="radio"
+                name="mode"
+                value="fuzzy"
+                checked={mode === "fuzzy"}
+                onChange={() => setMode("fuzzy")}
+              />
+              Fuzzy
+            </label>
+            <label className="flex items-center gap-1">
+              <input
+                type="checkbox"
+                checked={wholeWord}
+                onChange={(event) => setWholeWord(event.target.checked)}
+              />
+              Whole word
+            </label>
+            {mode === "regex" && (
+              <label className="flex items-center gap-1">
+                <input
+                  type="checkbox"
+                  checked={multiline}
+                  onChange={(event) => setMultiline(event.target.checked)}
+                />
+                Multi-line
+              </label>
+            )}
+            <label className="flex items-center gap-1">
+              <input
+                type="checkbox"
+                checked={allMatches}
+                onChange={(event) => setAllMatches(event.target.checked)}
+              />
+              All matches
+            </label>
+            <label className="flex items-center gap-1">
+              Context
+              <input
+                type="number"
+                min={0}
+                max={100}
+                value={contextLines}
+                onChange={(event) =>
+                  setContextLines(
+                    Math.min(100, Math.max(0, Number(event.target.value) || 0)),
+                  )
+                }
+                className="w-14 rounded border border-black/10 px-1"
+              />
+            </label>
           </div>
         </form>
 
+        {truncated && (
+          <p className="mt-5 text-[0.95rem] text-[#5f6368]">
+            The search took too long; only the results found so far are shown.
+          </p>
+        )}
+
         {error && (
           <div role="alert" className="mt-5 text-[0.95rem] text-[#d93025]">
             {error}
           </div>
         )}
 
-        {loading && <p className="mt-5 text-[#5f6368]">Searching history…</p>}
+        {loading && (
+          <p className="mt-5 text-[#5f6368]">
+            Searching history…
+            {progress &&
+              ` ${progress.checked} / ${progress.total} candidates checked`}
+          </p>
+        )}
       </div>
 
-      {hasSubmitted && !loading && (
+      {hasSubmitted && (results.length > 0 || !loading) && (
         <section className="mx-auto mb-16 mt-10 w-full max-w-[900px] px-6">
+          {fileTarget && (
+            <FileViewer
+              target={fileTarget}
+              query={submitted.query}
+              mode={submitted.mode}
+              wholeWord={submitted.wholeWord}
+              multiline={submitted.multiline}
+              onNavigate={setFileTarget}
+              onClose={() => setFileTarget(null)}
+            />
+          )}
+
           {results.length === 0 && !error ? (
             <p className="text-[#5f6368]">
               No results yet. Try a broader query or switch modes.
             </p>
           ) : (
-            results.flatMap((hit) => {
-              if (!hit) {
-                return [];
-              }
-
+            results.flatMap(({ hit }) => {
               return [
                 <div
                   key={`${hit.file_path}-${hit.first_match.commit_sha}`}
@@ -218,8 +548,12 @@ function App() {
                     {hit.first_match.lines.length === 1 ? "" : "s"}
                   </p>
 
-                  {renderMatchSection("First seen", hit.first_match)}
-                  {renderMatchSection("Last seen", hit.last_match)}
+                  {renderMatchSection("First seen", hit.first_match, (commit) =>
+                    setFileTarget({ path: hit.file_path, commit }),
+                  )}
+                  {renderMatchSection("Last seen", hit.last_match, (commit) =>
+                    setFileTarget({ path: hit.file_path, commit }),
+                  )}
                 </div>,
               ];
             })
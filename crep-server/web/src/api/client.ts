@@ -1,12 +1,32 @@
 import type {
+  BlameResponse,
+  FileResponse,
+  OutputMode,
   SearchMode,
   SearchRequest,
   SearchResponse,
+  SearchStreamDone,
+  SearchStreamHit,
+  SearchStreamProgress,
+  SuggestResponse,
   ErrorResponse,
 } from "./types";
 
 type SearchOptions = {
   mode?: SearchMode;
+  // Only matches the terms at the word boundaries.
+  wholeWord?: boolean;
+  // Regex matches can span multiple lines.
+  multiline?: boolean;
+  // Reports every match in a file instead of the first few.
+  allMatches?: boolean;
+  // Number of the lines around every match.
+  beforeContext?: number;
+  afterContext?: number;
+  // `count` and `files_with_matches` are answered from the index alone.
+  output?: OutputMode;
+  // Checks the `count` and `files_with_matches` candidates against the files.
+  verify?: boolean;
   limit?: number;
 };
 
@@ -28,6 +48,27 @@ export async function executeSearch(
   if (options.mode) {
     payload.mode = options.mode;
   }
+  if (options.wholeWord) {
+    payload.whole_word = true;
+  }
+  if (options.multiline) {
+    payload.multiline = true;
+  }
+  if (options.allMatches) {
+    payload.all_matches = true;
+  }
+  if (options.beforeContext !== undefined) {
+    payload.before_context = options.beforeContext;
+  }
+  if (options.afterContext !== undefined) {
+    payload.after_context = options.afterContext;
+  }
+  if (options.output) {
+    payload.output = options.output;
+  }
+  if (options.verify) {
+    payload.verify = true;
+  }
 
   const response = await fetch("/api/search", {
     method: "POST",
@@ -38,19 +79,162 @@ export async function executeSearch(
   });
 
   if (!response.ok) {
-    let message = `Request failed with status ${response.status}`;
-
-    try {
-      const body = (await response.json()) as Partial<ErrorResponse>;
-      if (body?.message) {
-        message = body.message;
-      }
-    } catch {
-      // Ignore JSON parse failures – fall back to status text.
+    throw new Error(await readErrorMessage(response));
+  }
+
+  return (await response.json()) as SearchResponse;
+}
+
+type FileOptions = {
+  // Defaults to the indexed HEAD.
+  commit?: string;
+  // Highlights the matches of the query in the file.
+  query?: string;
+  mode?: SearchMode;
+  wholeWord?: boolean;
+  multiline?: boolean;
+};
+
+export async function fetchFile(
+  path: string,
+  options: FileOptions = {},
+): Promise<FileResponse> {
+  const params = new URLSearchParams({ path });
+  if (options.commit) {
+    params.set("commit", options.commit);
+  }
+  if (options.query?.trim()) {
+    params.set("query", options.query.trim());
+  }
+  if (options.mode) {
+    params.set("mode", options.mode);
+  }
+  if (options.wholeWord) {
+    params.set("whole_word", "true");
+  }
+  if (options.multiline) {
+    params.set("multiline", "true");
+  }
+
+  const response = await fetch(`/api/file?${params.toString()}`);
+  if (!response.ok) {
+    throw new Error(await readErrorMessage(response));
+  }
+
+  return (await response.json()) as FileResponse;
+}
+
+export async function fetchBlame(path: string): Promise<BlameResponse> {
+  const params = new URLSearchParams({ path });
+
+  const response = await fetch(`/api/blame?${params.toString()}`);
+  if (!response.ok) {
+    throw new Error(await readErrorMessage(response));
+  }
+
+  return (await response.json()) as BlameResponse;
+}
+
+export async function fetchSuggestions(
+  prefix: string,
+  signal?: AbortSignal,
+): Promise<SuggestResponse> {
+  const params = new URLSearchParams({ prefix, limit: "8" });
+
+  const response = await fetch(`/api/suggest?${params.toString()}`, {
+    signal,
+  });
+  if (!response.ok) {
+    throw new Error(await readErrorMessage(response));
+  }
+
+  return (await response.json()) as SuggestResponse;
+}
+
+async function readErrorMessage(response: Response): Promise<string> {
+  let message = `Request failed with status ${response.status}`;
+
+  try {
+    const body = (await response.json()) as Partial<ErrorResponse>;
+    if (body?.message) {
+      message = body.message;
     }
+  } catch {
+    // Ignore JSON parse failures – fall back to status text.
+  }
+
+  return message;
+}
 
-    throw new Error(message);
+type SearchStreamHandlers = {
+  onHit: (hit: SearchStreamHit) => void;
+  onProgress?: (progress: SearchStreamProgress) => void;
+  onDone?: (done: SearchStreamDone) => void;
+  onError?: (message: string) => void;
+};
+
+// Streams the search hits as soon as the server converts them. Returns the
+// function that cancels the search; closing the stream also stops the work on
+// the server.
+export function streamSearch(
+  query: string,
+  options: SearchOptions,
+  handlers: SearchStreamHandlers,
+): () => void {
+  const trimmed = query.trim();
+  if (!trimmed) {
+    throw new Error("Please enter a search query.");
   }
 
-  return (await response.json()) as SearchResponse;
+  const params = new URLSearchParams({ query: trimmed });
+  if (options.mode) {
+    params.set("mode", options.mode);
+  }
+  if (options.wholeWord) {
+    params.set("whole_word", "true");
+  }
+  if (options.multiline) {
+    params.set("multiline", "true");
+  }
+  if (options.allMatches) {
+    params.set("all_matches", "true");
+  }
+  if (options.beforeContext !== undefined) {
+    params.set("before_context", String(options.beforeContext));
+  }
+  if (options.afterContext !== undefined) {
+    params.set("after_context", String(options.afterContext));
+  }
+
+  const source = new EventSource(`/api/search/stream?${params.toString()}`);
+
+  source.addEventListener("hit", (event) => {
+    handlers.onHit(JSON.parse((event as MessageEvent).data));
+  });
+
+  source.addEventListener("progress", (event) => {
+    handlers.onProgress?.(JSON.parse((event as MessageEvent).data));
+  });
+
+  source.addEventListener("done", (event) => {
+    source.close();
+    handlers.onDone?.(JSON.parse((event as MessageEvent).data));
+  });
+
+  source.addEventListener("error", (event) => {
+    const data = (event as MessageEvent).data;
+    if (typeof data === "string") {
+      // Errors reported by the server for a single hit.
+      const body = JSON.parse(data) as Partial<ErrorResponse>;
+      handlers.onError?.(body.message ?? "Unknown search error.");
+      return;
+    }
+
+    // Connection level error. EventSource would reconnect and restart the
+    // search, which is never what we want.
+    source.close();
+    handlers.onError?.("Search stream was interrupted.");
+  });
+
+  return () => source.close();
 }
@@ -9,32 +9,98 @@
 
 
 export type paths = {
+  "/api/suggest": {
+    get: operations["suggest"];
+  };
+  "/api/blame": {
+    get: operations["blame"];
+  };
+  "/api/file": {
+    get: operations["file"];
+  };
   "/api/search": {
     post: operations["search"];
   };
+  "/api/search/stream": {
+    get: operations["search_stream"];
+  };
 };
 
 export type webhooks = Record<string, never>;
 
 export type components = {
   schemas: {
+    BlameRange: {
+      author: string;
+      commit_date: string;
+      commit_sha: string;
+      commit_summary: string;
+      end_line: number;
+      start_line: number;
+    };
+    BlameResponse: {
+      head_commit: string;
+      path: string;
+      ranges: components["schemas"]["BlameRange"][];
+    };
+    CaptureSpan: {
+      end: number;
+      index: number;
+      name?: string | null;
+      start: number;
+      utf16_end: number;
+      utf16_start: number;
+    };
+    ContextWindow: {
+      end_line: number;
+      start_line: number;
+    };
+    FileMatch: {
+      at_head: boolean;
+      file_path: string;
+      num_commits: number;
+    };
     ErrorResponse: {
       message: string;
     };
+    FileResponse: {
+      commit_sha: string;
+      is_head: boolean;
+      last_modified_commit?: string | null;
+      lines: components["schemas"]["LineMatch"][];
+      next_commit?: string | null;
+      path: string;
+      previous_commit?: string | null;
+      total_lines: number;
+    };
+    IdentifierSuggestion: {
+      count: number;
+      identifier: string;
+    };
     LineHighlight: {
       column: number;
+      end_column: number;
+      end_line?: number | null;
+      groups?: components["schemas"]["CaptureSpan"][];
+      length: number;
       term: string;
+      utf16_column: number;
+      utf16_end_column: number;
+      utf16_length: number;
     };
     LineMatch: {
       content: string;
       highlights: components["schemas"]["LineHighlight"][];
+      introduced_in?: string | null;
       line_number: number;
     };
     MatchDetail: {
       commit_date: string;
       commit_sha: string;
       commit_summary: string;
+      context_windows: components["schemas"]["ContextWindow"][];
       lines: components["schemas"]["LineMatch"][];
+      match_count: number;
     };
     SearchHit: {
       file_path: string;
@@ -42,15 +108,50 @@ export type components = {
       last_match?: null | components["schemas"]["MatchDetail"];
     };
     /** @enum {string} */
-    SearchMode: "plain" | "regex";
+    SearchMode: "plain" | "regex" | "fuzzy";
     SearchRequest: {
+      after_context?: number | null;
+      before_context?: number | null;
+      max_edits?: number | null;
       mode?: components["schemas"]["SearchMode"];
       page?: number;
       page_size?: number;
+      all_matches?: boolean;
+      multiline?: boolean;
+      output?: components["schemas"]["OutputMode"];
       query: string;
+      verify?: boolean;
+      whole_word?: boolean;
+    };
+    SearchStreamDone: {
+      head_commit: string;
+      num_hits: number;
+      truncated: boolean;
     };
+    SearchStreamHit: {
+      hit: components["schemas"]["SearchHit"];
+      index: number;
+    };
+    SearchStreamProgress: {
+      checked: number;
+      total: number;
+    };
+    SuggestResponse: {
+      identifiers: components["schemas"]["IdentifierSuggestion"][];
+      paths: string[];
+    };
+    SearchCount: {
+      num_commits: number;
+      num_files: number;
+      num_files_at_head: number;
+    };
+    OutputMode: "matches" | "count" | "files_with_matches";
     SearchResponse: {
+      count?: null | components["schemas"]["SearchCount"];
+      files?: components["schemas"]["FileMatch"][] | null;
+      head_commit: string;
       results: (null | components["schemas"]["SearchHit"])[];
+      truncated: boolean;
     };
   };
   responses: never;
@@ -66,6 +167,91 @@ export type external = Record<string, never>;
 
 export type operations = {
 
+  suggest: {
+    parameters: {
+      query: {
+        prefix: string;
+        limit?: number | null;
+      };
+    };
+    responses: {
+      /** @description Completions for the prefix */
+      200: {
+        content: {
+          "application/json": components["schemas"]["SuggestResponse"];
+        };
+      };
+      /** @description Internal server error */
+      500: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+    };
+  };
+  blame: {
+    parameters: {
+      query: {
+        path: string;
+      };
+    };
+    responses: {
+      /** @description Origin of every line at the indexed HEAD */
+      200: {
+        content: {
+          "application/json": components["schemas"]["BlameResponse"];
+        };
+      };
+      /** @description File not found at HEAD */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+      /** @description Internal server error */
+      500: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+    };
+  };
+  file: {
+    parameters: {
+      query: {
+        path: string;
+        commit?: string | null;
+        start_line?: number | null;
+        end_line?: number | null;
+        query?: string | null;
+        mode?: components["schemas"]["SearchMode"];
+        max_edits?: number | null;
+        whole_word?: boolean;
+        multiline?: boolean;
+      };
+    };
+    responses: {
+      /** @description File content at the commit */
+      200: {
+        content: {
+          "application/json": components["schemas"]["FileResponse"];
+        };
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+      /** @description File or commit not found */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+    };
+  };
+
   search: {
     requestBody: {
       content: {
@@ -93,14 +279,57 @@ export type operations = {
       };
     };
   };
+  search_stream: {
+    parameters: {
+      query: {
+        query: string;
+        mode?: components["schemas"]["SearchMode"];
+        max_edits?: number | null;
+        whole_word?: boolean;
+        multiline?: boolean;
+        all_matches?: boolean;
+        before_context?: number | null;
+        after_context?: number | null;
+      };
+    };
+    responses: {
+      /** @description Server-Sent Events. `hit` events carry SearchStreamHit, `progress` events SearchStreamProgress and the final `done` event SearchStreamDone */
+      200: {
+        content: {
+          "text/event-stream": string;
+        };
+      };
+      /** @description Invalid query */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResponse"];
+        };
+      };
+    };
+  };
 };
 
 // Re-export individual schema types for ease of use.
+export type BlameRange = components["schemas"]["BlameRange"];
+export type BlameResponse = components["schemas"]["BlameResponse"];
+export type IdentifierSuggestion =
+  components["schemas"]["IdentifierSuggestion"];
+export type SuggestResponse = components["schemas"]["SuggestResponse"];
 export type ErrorResponse = components["schemas"]["ErrorResponse"];
+export type FileResponse = components["schemas"]["FileResponse"];
 export type LineHighlight = components["schemas"]["LineHighlight"];
+export type CaptureSpan = components["schemas"]["CaptureSpan"];
+export type ContextWindow = components["schemas"]["ContextWindow"];
 export type LineMatch = components["schemas"]["LineMatch"];
 export type MatchDetail = components["schemas"]["MatchDetail"];
 export type SearchHit = components["schemas"]["SearchHit"];
 export type SearchMode = components["schemas"]["SearchMode"];
+export type OutputMode = components["schemas"]["OutputMode"];
+export type SearchCount = components["schemas"]["SearchCount"];
+export type FileMatch = components["schemas"]["FileMatch"];
 export type SearchRequest = components["schemas"]["SearchRequest"];
 export type SearchResponse = components["schemas"]["SearchResponse"];
+export type SearchStreamDone = components["schemas"]["SearchStreamDone"];
+export type SearchStreamHit = components["schemas"]["SearchStreamHit"];
+export type SearchStreamProgress =
+  components["schemas"]["SearchStreamProgress"];
@@ -1,5 +1,8 @@
+#[cfg(feature = "bench")]
 use std::collections::BTreeSet;
+#[cfg(feature = "bench")]
 use std::collections::HashMap;
+#[cfg(feature = "bench")]
 use std::collections::HashSet;
 
 #[cfg(feature = "bench")]
@@ -28,6 +31,7 @@ pub fn split_lines_to_tokens_v0(
     )
 }
 
+#[cfg(feature = "bench")]
 fn split_by_trigram<'a>(
     line: &'a str,
     total_words: &mut HashSet<&'a str>,